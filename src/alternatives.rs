@@ -0,0 +1,113 @@
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
+
+use crate::graph::{astar_path, haversine_meters, straight_line_cost};
+use crate::types::restriction::TurnRestriction;
+use crate::types::{coord::Coord, edge::Edge};
+
+/// Largest share of an alternative's length that may run along an earlier route.
+pub const MAX_SIMILARITY: f64 = 0.7;
+/// How many times the cost of the best route an alternative may cost.
+pub const MAX_STRETCH: f64 = 1.5;
+/// Extra cost factor for an edge per earlier route that used it.
+const PENALTY: f64 = 0.5;
+
+/// Finds up to `k` alternatives to the `main` route, each with its cost.
+/// Alternatives come from the penalty method: the edges of the main route and
+/// of every later search get more expensive, and a search is kept if it is
+/// different enough from the routes so far and not too much longer than the
+/// main one. `main` is whatever route is shown as the best, so it may come
+/// from any search strategy.
+pub fn alternative_routes(
+    graph: &HashMap<Coord, Vec<Edge>>,
+    restrictions: &HashMap<Coord, Vec<TurnRestriction>>,
+    main: &[Coord],
+    k: usize,
+) -> Vec<(Vec<Coord>, i64)> {
+    let (Some(&start), Some(&goal)) = (main.first(), main.last()) else {
+        return Vec::new();
+    };
+    let best = path_cost(graph, main);
+    let mut routes: Vec<(Vec<Coord>, i64)> = Vec::new();
+    let mut uses: HashMap<(Coord, Coord), u32> = HashMap::new();
+    penalise(&mut uses, main);
+
+    for _ in 0..k * 3 {
+        if routes.len() >= k {
+            break;
+        }
+        let heuristic = |node| straight_line_cost(node, goal);
+        let edge_cost = |node, edge: &Edge| {
+            let uses = uses.get(&(node, edge.target)).copied().unwrap_or(0);
+            (edge.cost as f64 * (1.0 + PENALTY * uses as f64)) as i64
+        };
+        let Some((path, _)) = astar_path(
+            graph,
+            restrictions,
+            start,
+            goal,
+            heuristic,
+            edge_cost,
+            &Cell::new(0),
+        ) else {
+            break;
+        };
+        penalise(&mut uses, &path);
+
+        let cost = path_cost(graph, &path);
+        if cost as f64 > best as f64 * MAX_STRETCH {
+            continue;
+        }
+        if similarity(&path, main) <= MAX_SIMILARITY
+            && routes
+                .iter()
+                .all(|(route, _)| similarity(&path, route) <= MAX_SIMILARITY)
+        {
+            routes.push((path, cost));
+        }
+    }
+    routes
+}
+
+/// Counts one more use of every edge of `path`, in both directions.
+fn penalise(uses: &mut HashMap<(Coord, Coord), u32>, path: &[Coord]) {
+    for w in path.windows(2) {
+        *uses.entry((w[0], w[1])).or_default() += 1;
+        *uses.entry((w[1], w[0])).or_default() += 1;
+    }
+}
+
+/// Unpenalised cost of a path, taking the cheapest edge between each pair of nodes.
+fn path_cost(graph: &HashMap<Coord, Vec<Edge>>, path: &[Coord]) -> i64 {
+    path.windows(2)
+        .filter_map(|w| {
+            graph
+                .get(&w[0])?
+                .iter()
+                .filter(|edge| edge.target == w[1])
+                .map(|edge| edge.cost)
+                .min()
+        })
+        .sum()
+}
+
+/// Share of the length of `path` that also lies on `other`, in either direction.
+pub fn similarity(path: &[Coord], other: &[Coord]) -> f64 {
+    let shared: HashSet<(Coord, Coord)> = other
+        .windows(2)
+        .flat_map(|w| [(w[0], w[1]), (w[1], w[0])])
+        .collect();
+    let (mut total, mut overlap) = (0.0, 0.0);
+    for w in path.windows(2) {
+        let length = haversine_meters(w[0].lon, w[0].lat, w[1].lon, w[1].lat);
+        total += length;
+        if shared.contains(&(w[0], w[1])) {
+            overlap += length;
+        }
+    }
+    if total > 0.0 {
+        overlap / total
+    } else {
+        1.0
+    }
+}
@@ -0,0 +1,183 @@
+use serde_json::Value;
+use std::path::Path;
+
+use crate::import::{elements, positions};
+use crate::multipolygon::contains;
+use crate::types::cached_data::Polygon;
+use crate::utils::parse_bbox;
+
+/// The area kept when parsing with a clip.
+pub enum Clip {
+    BBox(f64, f64, f64, f64),
+    /// Polygons as outer ring followed by holes, with the bounding box of
+    /// them all to rule out what is far away quickly.
+    Polygons {
+        polygons: Vec<Polygon>,
+        bounds: (f64, f64, f64, f64),
+    },
+}
+
+impl Clip {
+    /// Parses a `min_lon,min_lat,max_lon,max_lat` bounding box, or else reads
+    /// the Polygon and MultiPolygon features of a GeoJSON file.
+    pub fn parse(arg: &str) -> Result<Clip, Box<dyn std::error::Error>> {
+        if let Some((min_lon, min_lat, max_lon, max_lat)) = parse_bbox(arg) {
+            return Ok(Clip::BBox(min_lon, min_lat, max_lon, max_lat));
+        }
+        let file = std::fs::File::open(Path::new(arg)).map_err(|e| {
+            format!(
+                "{} is neither a bounding box nor a readable file: {}",
+                arg, e
+            )
+        })?;
+        let json: Value = serde_json::from_reader(std::io::BufReader::new(file))?;
+        let geometries: Vec<&Value> = match json["type"].as_str() {
+            Some("FeatureCollection") => elements(&json["features"])?
+                .iter()
+                .map(|feature| &feature["geometry"])
+                .collect(),
+            Some("Feature") => vec![&json["geometry"]],
+            _ => vec![&json],
+        };
+
+        let mut polygons = Vec::new();
+        for geometry in geometries {
+            let coordinates = &geometry["coordinates"];
+            match geometry["type"].as_str() {
+                Some("Polygon") => polygons.push(rings(coordinates)?),
+                Some("MultiPolygon") => {
+                    for polygon in elements(coordinates)? {
+                        polygons.push(rings(polygon)?);
+                    }
+                }
+                _ => {}
+            }
+        }
+        if polygons.is_empty() {
+            return Err(format!("{} is neither a bounding box nor GeoJSON polygons", arg).into());
+        }
+        let bounds = polygons.iter().flatten().flatten().fold(
+            (f64::MAX, f64::MAX, f64::MIN, f64::MIN),
+            |(min_lon, min_lat, max_lon, max_lat), &(lon, lat)| {
+                (
+                    min_lon.min(lon),
+                    min_lat.min(lat),
+                    max_lon.max(lon),
+                    max_lat.max(lat),
+                )
+            },
+        );
+        Ok(Clip::Polygons { polygons, bounds })
+    }
+
+    pub fn contains(&self, (lon, lat): (f64, f64)) -> bool {
+        match self {
+            Clip::BBox(min_lon, min_lat, max_lon, max_lat) => {
+                lon >= *min_lon && lon <= *max_lon && lat >= *min_lat && lat <= *max_lat
+            }
+            Clip::Polygons { polygons, bounds } => {
+                let (min_lon, min_lat, max_lon, max_lat) = *bounds;
+                if lon < min_lon || lon > max_lon || lat < min_lat || lat > max_lat {
+                    return false;
+                }
+                polygons.iter().any(|polygon| match polygon.split_first() {
+                    Some((outer, holes)) => {
+                        contains(outer, (lon, lat))
+                            && !holes.iter().any(|hole| contains(hole, (lon, lat)))
+                    }
+                    None => false,
+                })
+            }
+        }
+    }
+
+    /// Whether a way touches the clip: a node inside it, a segment crossing
+    /// its edge, or, for a closed way, an area around the whole clip.
+    pub fn intersects(&self, coords: &[(f64, f64)], closed: bool) -> bool {
+        let (min_lon, min_lat, max_lon, max_lat) = self.bounds();
+        // Entirely to one side, so it can neither cross nor surround the clip
+        let far = coords.iter().all(|&(lon, _)| lon < min_lon)
+            || coords.iter().all(|&(lon, _)| lon > max_lon)
+            || coords.iter().all(|&(_, lat)| lat < min_lat)
+            || coords.iter().all(|&(_, lat)| lat > max_lat);
+        if far {
+            return false;
+        }
+        if coords.iter().any(|&coord| self.contains(coord)) {
+            return true;
+        }
+        let crosses = coords
+            .windows(2)
+            .any(|segment| self.edges(|a, b| segments_cross(segment[0], segment[1], a, b)));
+        if crosses {
+            return true;
+        }
+        // With no edge crossing, the clip is either all inside the area or
+        // all outside it
+        closed && contains(coords, self.corner())
+    }
+
+    fn bounds(&self) -> (f64, f64, f64, f64) {
+        match self {
+            Clip::BBox(min_lon, min_lat, max_lon, max_lat) => {
+                (*min_lon, *min_lat, *max_lon, *max_lat)
+            }
+            Clip::Polygons { bounds, .. } => *bounds,
+        }
+    }
+
+    /// Whether `crosses` holds for any edge of the clip.
+    fn edges(&self, crosses: impl Fn((f64, f64), (f64, f64)) -> bool) -> bool {
+        let ring_crosses =
+            |ring: &[(f64, f64)]| ring.windows(2).any(|edge| crosses(edge[0], edge[1]));
+        match self {
+            Clip::BBox(min_lon, min_lat, max_lon, max_lat) => ring_crosses(&[
+                (*min_lon, *min_lat),
+                (*max_lon, *min_lat),
+                (*max_lon, *max_lat),
+                (*min_lon, *max_lat),
+                (*min_lon, *min_lat),
+            ]),
+            Clip::Polygons { polygons, .. } => {
+                polygons.iter().flatten().any(|ring| ring_crosses(ring))
+            }
+        }
+    }
+
+    /// A point on the edge of the clip.
+    fn corner(&self) -> (f64, f64) {
+        match self {
+            Clip::BBox(min_lon, min_lat, _, _) => (*min_lon, *min_lat),
+            Clip::Polygons { polygons, .. } => polygons
+                .iter()
+                .flatten()
+                .flatten()
+                .next()
+                .copied()
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Whether segments `a`-`b` and `c`-`d` cross or touch.
+fn segments_cross(a: (f64, f64), b: (f64, f64), c: (f64, f64), d: (f64, f64)) -> bool {
+    let side = |p: (f64, f64), q: (f64, f64), r: (f64, f64)| {
+        (q.0 - p.0) * (r.1 - p.1) - (q.1 - p.1) * (r.0 - p.0)
+    };
+    let within = |p: (f64, f64), q: (f64, f64), r: (f64, f64)| {
+        r.0 >= p.0.min(q.0) && r.0 <= p.0.max(q.0) && r.1 >= p.1.min(q.1) && r.1 <= p.1.max(q.1)
+    };
+    let (d1, d2) = (side(c, d, a), side(c, d, b));
+    let (d3, d4) = (side(a, b, c), side(a, b, d));
+    if d1 * d2 < 0.0 && d3 * d4 < 0.0 {
+        return true;
+    }
+    (d1 == 0.0 && within(c, d, a))
+        || (d2 == 0.0 && within(c, d, b))
+        || (d3 == 0.0 && within(a, b, c))
+        || (d4 == 0.0 && within(a, b, d))
+}
+
+fn rings(polygon: &Value) -> Result<Polygon, Box<dyn std::error::Error>> {
+    elements(polygon)?.iter().map(positions).collect()
+}
@@ -0,0 +1,378 @@
+use image::RgbaImage;
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use tiff::decoder::{Decoder, DecodingResult};
+use tiff::tags::Tag;
+
+use crate::drawing::Projection;
+use crate::graph::COST_PER_METER;
+use crate::types::{coord::Coord, edge::Edge};
+
+const METERS_PER_DEGREE_LAT: f64 = 110_574.0;
+const METERS_PER_DEGREE_LON_AT_EQUATOR: f64 = 111_320.0;
+/// Fraction of a sample spacing a position may lie outside the grid.
+const EDGE_TOLERANCE: f64 = 1e-6;
+/// Height that marks a void in SRTM tiles.
+const HGT_VOID: i16 = -32768;
+/// The sun for hillshading, from the north-west as on most maps.
+const SUN_AZIMUTH_DEGREES: f64 = 315.0;
+const SUN_ALTITUDE_DEGREES: f64 = 45.0;
+/// Opacity of the darkest shadow.
+const SHADOW_STRENGTH: f64 = 0.6;
+/// Flat distance in meters that a meter of climbing costs as much as. Riders
+/// commonly reckon one meter up to be worth eight to twelve on the flat.
+const FLAT_METERS_PER_CLIMB: f64 = 10.0;
+
+/// A contour line piece between two points as lon/lat, with its height.
+pub type ContourSegment = (f64, [(f64, f64); 2]);
+
+/// An elevation model: a grid of heights in meters, north up, in lon/lat.
+pub struct Dem {
+    width: usize,
+    height: usize,
+    /// Longitude and latitude of the top left sample.
+    origin: (f64, f64),
+    /// Degrees between samples, eastwards and southwards.
+    step: (f64, f64),
+    /// Heights row by row from the north, NaN where there is no data.
+    heights: Vec<f32>,
+}
+
+impl std::fmt::Debug for Dem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Dem")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("origin", &self.origin)
+            .field("step", &self.step)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Dem {
+    /// Reads an SRTM `.hgt` tile or a GeoTIFF in EPSG:4326, depending on the
+    /// file extension.
+    pub fn open(path: impl AsRef<Path>) -> Result<Dem, Box<dyn std::error::Error>> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("hgt") => Dem::read_hgt(path),
+            Some("tif") | Some("tiff") => Dem::read_geotiff(path),
+            _ => Err(format!("{} is neither a .hgt nor a GeoTIFF file", path.display()).into()),
+        }
+    }
+
+    /// Reads an SRTM tile. The name gives the south-west corner, as in
+    /// `N52E013.hgt`, and the size whether it has 1 or 3 arc-second samples.
+    fn read_hgt(path: &Path) -> Result<Dem, Box<dyn std::error::Error>> {
+        let name = path
+            .file_stem()
+            .and_then(|name| name.to_str())
+            .map(str::to_ascii_uppercase)
+            .unwrap_or_default();
+        let corner = |hemisphere: &str, digits: &str| -> Option<f64> {
+            let value: f64 = digits.parse().ok()?;
+            match hemisphere {
+                "N" | "E" => Some(value),
+                "S" | "W" => Some(-value),
+                _ => None,
+            }
+        };
+        let (lat, lon) = name
+            .get(..7)
+            .and_then(|name| {
+                Some((
+                    corner(&name[..1], &name[1..3])?,
+                    corner(&name[3..4], &name[4..7])?,
+                ))
+            })
+            .ok_or_else(|| format!("{} is not named like N52E013.hgt", path.display()))?;
+
+        let bytes = std::fs::read(path)?;
+        let size = ((bytes.len() / 2) as f64).sqrt() as usize;
+        if size < 2 || size * size * 2 != bytes.len() {
+            return Err(format!("{} is not a square grid of heights", path.display()).into());
+        }
+        let heights = bytes
+            .chunks_exact(2)
+            .map(|pair| match i16::from_be_bytes([pair[0], pair[1]]) {
+                HGT_VOID => f32::NAN,
+                height => height as f32,
+            })
+            .collect();
+        let step = 1.0 / (size - 1) as f64;
+        Ok(Dem {
+            width: size,
+            height: size,
+            origin: (lon, lat + 1.0),
+            step: (step, step),
+            heights,
+        })
+    }
+
+    /// Reads the first band of a GeoTIFF. Its georeferencing must be a pixel
+    /// scale and tie point in degrees, as GDAL writes for EPSG:4326.
+    fn read_geotiff(path: &Path) -> Result<Dem, Box<dyn std::error::Error>> {
+        let mut decoder = Decoder::new(BufReader::new(File::open(path)?))?;
+        let (width, height) = decoder.dimensions()?;
+        let (width, height) = (width as usize, height as usize);
+        let scale = decoder.get_tag_f64_vec(Tag::ModelPixelScaleTag)?;
+        let tiepoint = decoder.get_tag_f64_vec(Tag::ModelTiepointTag)?;
+        if scale.len() < 2 || tiepoint.len() < 6 {
+            return Err(format!("{} has no usable georeferencing", path.display()).into());
+        }
+        let keys = decoder
+            .find_tag_unsigned_vec::<u16>(Tag::GeoKeyDirectoryTag)?
+            .unwrap_or_default();
+        // Keys stored in the directory itself, which all the ones read are
+        let key = |id: u16| {
+            keys.chunks_exact(4)
+                .skip(1)
+                .find(|key| key[0] == id && key[1] == 0)
+                .map(|key| key[3])
+        };
+        // GTModelTypeGeoKey and GeographicTypeGeoKey
+        if key(1024) != Some(2) || key(2048) != Some(4326) {
+            return Err(format!(
+                "{} is not in geographic WGS 84 coordinates (EPSG:4326)",
+                path.display()
+            )
+            .into());
+        }
+        if decoder.find_tag_unsigned::<u16>(Tag::PlanarConfiguration)? == Some(2) {
+            return Err(format!(
+                "{} stores its samples in separate planes, which is not supported",
+                path.display()
+            )
+            .into());
+        }
+        // Tie points refer to the corner of a pixel unless the raster is
+        // marked as pixel-is-point, so move them to the sample centre
+        let offset = if key(1025) == Some(2) { 0.0 } else { 0.5 };
+        let origin = (
+            tiepoint[3] + (offset - tiepoint[0]) * scale[0],
+            tiepoint[4] - (offset - tiepoint[1]) * scale[1],
+        );
+        let no_data: Option<f64> = match decoder.find_tag(Tag::GdalNodata)? {
+            Some(value) => value
+                .into_string()?
+                .trim()
+                .trim_end_matches('\0')
+                .parse()
+                .ok(),
+            None => None,
+        };
+        let samples = decoder
+            .find_tag_unsigned::<usize>(Tag::SamplesPerPixel)?
+            .unwrap_or(1);
+
+        let values: Vec<f64> = match decoder.read_image()? {
+            DecodingResult::U8(values) => values.into_iter().map(f64::from).collect(),
+            DecodingResult::U16(values) => values.into_iter().map(f64::from).collect(),
+            DecodingResult::U32(values) => values.into_iter().map(f64::from).collect(),
+            DecodingResult::U64(values) => values.into_iter().map(|v| v as f64).collect(),
+            DecodingResult::I8(values) => values.into_iter().map(f64::from).collect(),
+            DecodingResult::I16(values) => values.into_iter().map(f64::from).collect(),
+            DecodingResult::I32(values) => values.into_iter().map(f64::from).collect(),
+            DecodingResult::I64(values) => values.into_iter().map(|v| v as f64).collect(),
+            DecodingResult::F32(values) => values.into_iter().map(f64::from).collect(),
+            DecodingResult::F64(values) => values,
+        };
+        let heights = values
+            .into_iter()
+            .step_by(samples)
+            .map(|value| {
+                if no_data == Some(value) {
+                    f32::NAN
+                } else {
+                    value as f32
+                }
+            })
+            .collect();
+        Ok(Dem {
+            width,
+            height,
+            origin,
+            step: (scale[0], scale[1]),
+            heights,
+        })
+    }
+
+    fn sample(&self, x: usize, y: usize) -> f64 {
+        self.heights[y * self.width + x] as f64
+    }
+
+    /// Height in meters at a position, interpolated between the four nearest
+    /// samples. None outside the grid or next to a void.
+    pub fn elevation(&self, lon: f64, lat: f64) -> Option<f64> {
+        // Positions on the edge samples may round to just outside the grid
+        let inside = |index: f64, size: usize| {
+            (-EDGE_TOLERANCE..=(size - 1) as f64 + EDGE_TOLERANCE)
+                .contains(&index)
+                .then(|| index.clamp(0.0, (size - 1) as f64))
+        };
+        let x = inside((lon - self.origin.0) / self.step.0, self.width)?;
+        let y = inside((self.origin.1 - lat) / self.step.1, self.height)?;
+        let (x0, y0) = (
+            (x as usize).min(self.width - 2),
+            (y as usize).min(self.height - 2),
+        );
+        let (tx, ty) = (x - x0 as f64, y - y0 as f64);
+        let top = self.sample(x0, y0) * (1.0 - tx) + self.sample(x0 + 1, y0) * tx;
+        let bottom = self.sample(x0, y0 + 1) * (1.0 - tx) + self.sample(x0 + 1, y0 + 1) * tx;
+        let height = top * (1.0 - ty) + bottom * ty;
+        (!height.is_nan()).then_some(height)
+    }
+
+    /// Shadows of the terrain lit from the north-west, as black of varying
+    /// opacity over `projection`. Flat ground and slopes facing the light are
+    /// left clear so the layer can go over coloured areas.
+    pub fn hillshade(&self, projection: &Projection) -> RgbaImage {
+        let (width, height) = (projection.width as u32, projection.height as u32);
+        let azimuth = SUN_AZIMUTH_DEGREES.to_radians();
+        let altitude = SUN_ALTITUDE_DEGREES.to_radians();
+        let sun = (
+            altitude.cos() * azimuth.sin(),
+            altitude.cos() * azimuth.cos(),
+            altitude.sin(),
+        );
+        let (dlon, dlat) = self.step;
+
+        let mut pixels = vec![0u8; width as usize * height as usize * 4];
+        pixels
+            .par_chunks_mut(width as usize * 4)
+            .enumerate()
+            .for_each(|(y, row)| {
+                let (_, lat) = projection.unproject(0.0, y as f64 + 0.5);
+                let meters_east =
+                    2.0 * dlon * METERS_PER_DEGREE_LON_AT_EQUATOR * lat.to_radians().cos();
+                let meters_north = 2.0 * dlat * METERS_PER_DEGREE_LAT;
+                for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+                    let (lon, lat) = projection.unproject(x as f64 + 0.5, y as f64 + 0.5);
+                    let heights = (
+                        self.elevation(lon + dlon, lat),
+                        self.elevation(lon - dlon, lat),
+                        self.elevation(lon, lat + dlat),
+                        self.elevation(lon, lat - dlat),
+                    );
+                    let (Some(east), Some(west), Some(north), Some(south)) = heights else {
+                        continue;
+                    };
+                    // The surface normal is (-dz/dx, -dz/dy, 1), unnormalised
+                    let (nx, ny) = (
+                        -(east - west) / meters_east,
+                        -(north - south) / meters_north,
+                    );
+                    let light =
+                        (nx * sun.0 + ny * sun.1 + sun.2) / (nx * nx + ny * ny + 1.0).sqrt();
+                    let shadow = ((sun.2 - light.max(0.0)) / sun.2).clamp(0.0, 1.0);
+                    pixel[3] = (shadow * SHADOW_STRENGTH * 255.0) as u8;
+                }
+            });
+        RgbaImage::from_raw(width, height, pixels).unwrap()
+    }
+
+    /// Contour lines every `interval` meters inside a bounding box, traced
+    /// cell by cell with marching squares.
+    pub fn contours(
+        &self,
+        (min_lon, min_lat, max_lon, max_lat): (f64, f64, f64, f64),
+        interval: f64,
+    ) -> Vec<ContourSegment> {
+        let column = |lon: f64| ((lon - self.origin.0) / self.step.0).floor();
+        let row = |lat: f64| ((self.origin.1 - lat) / self.step.1).floor();
+        let clamp =
+            |value: f64, size: usize| value.clamp(0.0, size.saturating_sub(1) as f64) as usize;
+        let (first_x, last_x) = (
+            clamp(column(min_lon), self.width),
+            clamp(column(max_lon) + 1.0, self.width),
+        );
+        let (first_y, last_y) = (
+            clamp(row(max_lat), self.height),
+            clamp(row(min_lat) + 1.0, self.height),
+        );
+        let position = |x: f64, y: f64| {
+            (
+                self.origin.0 + x * self.step.0,
+                self.origin.1 - y * self.step.1,
+            )
+        };
+
+        let mut segments = Vec::new();
+        for y in first_y..last_y {
+            for x in first_x..last_x {
+                // Corners clockwise from the top left
+                let corners = [(x, y), (x + 1, y), (x + 1, y + 1), (x, y + 1)];
+                let heights = corners.map(|(x, y)| self.sample(x, y));
+                if heights.iter().any(|height| height.is_nan()) {
+                    continue;
+                }
+                let low = heights.iter().copied().fold(f64::MAX, f64::min);
+                let high = heights.iter().copied().fold(f64::MIN, f64::max);
+                let mut level = (low / interval).ceil() * interval;
+                while level <= high {
+                    // Where the level crosses each side, going round the cell
+                    let mut crossings = Vec::with_capacity(4);
+                    for side in 0..4 {
+                        let (a, b) = (side, (side + 1) % 4);
+                        if (heights[a] >= level) != (heights[b] >= level) {
+                            let t = (level - heights[a]) / (heights[b] - heights[a]);
+                            let (ax, ay) = corners[a];
+                            let (bx, by) = corners[b];
+                            crossings.push(position(
+                                ax as f64 + t * (bx as f64 - ax as f64),
+                                ay as f64 + t * (by as f64 - ay as f64),
+                            ));
+                        }
+                    }
+                    match crossings[..] {
+                        [a, b] => segments.push((level, [a, b])),
+                        [a, b, c, d] => {
+                            // A saddle: the centre decides which way the lines run
+                            let centre = heights.iter().sum::<f64>() / 4.0;
+                            if (centre >= level) == (heights[0] >= level) {
+                                segments.push((level, [a, b]));
+                                segments.push((level, [c, d]));
+                            } else {
+                                segments.push((level, [a, d]));
+                                segments.push((level, [b, c]));
+                            }
+                        }
+                        _ => {}
+                    }
+                    level += interval;
+                }
+            }
+        }
+        segments
+    }
+}
+
+/// Makes climbing cost extra on every edge of the graph, as for cycling or
+/// walking, by adding the height gained times `FLAT_METERS_PER_CLIMB` to its
+/// length. Costs only go up, so distance based search bounds stay valid.
+/// Returns the number of edges that got more expensive.
+pub fn add_climb_costs(graph: &mut HashMap<Coord, Vec<Edge>>, dem: &Dem) -> usize {
+    let mut count = 0;
+    for (node, edges) in graph.iter_mut() {
+        let Some(start) = dem.elevation(node.lon, node.lat) else {
+            continue;
+        };
+        for edge in edges {
+            let Some(end) = dem.elevation(edge.target.lon, edge.target.lat) else {
+                continue;
+            };
+            if end > start {
+                edge.cost += ((end - start) * FLAT_METERS_PER_CLIMB * COST_PER_METER) as i64;
+                count += 1;
+            }
+        }
+    }
+    count
+}
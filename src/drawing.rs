@@ -2,47 +2,331 @@ use image::{Pixel, Rgba, RgbaImage};
 use imageproc::drawing::draw_polygon_mut;
 use imageproc::point::Point;
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::BufWriter;
 use std::path::Path;
 use std::sync::Arc;
 use std::time::Instant;
 
-fn calculate_bounding_box(ways: &[&[Vec<(f64, f64)>]]) -> (f64, f64, f64, f64) {
-    ways.iter()
-        .flat_map(|way_group| way_group.iter().flatten())
-        .fold(
-            (f64::MAX, f64::MAX, f64::MIN, f64::MIN),
-            |(min_lon, min_lat, max_lon, max_lat), &(lon, lat)| {
-                (
-                    min_lon.min(lon),
-                    min_lat.min(lat),
-                    max_lon.max(lon),
-                    max_lat.max(lat),
-                )
-            },
-        )
-}
-
-fn lon_lat_to_pixel(
-    lon: f64,
-    lat: f64,
-    min_lon: f64,
-    min_lat: f64,
-    max_lon: f64,
-    max_lat: f64,
-    img_size: u32,
-) -> (i32, i32) {
-    let lon_range = max_lon - min_lon;
-    let lat_range = max_lat - min_lat;
+use crate::dem::Dem;
+use crate::georef::{write_geotiff, write_world_file};
+use crate::isochrone::Isochrone;
+use crate::labels::Labeler;
+use crate::poi::PoiStyles;
+use crate::simplify::{douglas_peucker, visvalingam};
+use crate::types::cached_data::{CachedData, Polygon};
+use crate::types::category::Category;
+use crate::types::highway::{Highway, HighwayClass};
+use crate::types::poi::Poi;
+
+/// Bounding box of the highways, waterways and railways, which is the extent
+/// the map is drawn at.
+pub fn data_bounding_box(data: &CachedData) -> (f64, f64, f64, f64) {
+    calculate_bounding_box(
+        data.highways
+            .iter()
+            .map(|highway| highway.coords.as_slice())
+            .chain(data.waterways.iter().map(Vec::as_slice))
+            .chain(data.railways.iter().map(Vec::as_slice)),
+    )
+}
+
+pub fn calculate_bounding_box<'a>(
+    ways: impl IntoIterator<Item = &'a [(f64, f64)]>,
+) -> (f64, f64, f64, f64) {
+    ways.into_iter().flatten().fold(
+        (f64::MAX, f64::MAX, f64::MIN, f64::MIN),
+        |(min_lon, min_lat, max_lon, max_lat), &(lon, lat)| {
+            (
+                min_lon.min(lon),
+                min_lat.min(lat),
+                max_lon.max(lon),
+                max_lat.max(lat),
+            )
+        },
+    )
+}
+
+const METERS_PER_DEGREE_LAT: f64 = 110_574.0;
+
+/// Linear mapping from lon/lat onto a `width` x `height` drawing surface with
+/// the origin in the top left corner.
+#[derive(Debug, Clone, Copy)]
+pub struct Projection {
+    pub min_lon: f64,
+    pub min_lat: f64,
+    pub max_lon: f64,
+    pub max_lat: f64,
+    pub width: f64,
+    pub height: f64,
+    /// Spaces latitudes like Web Mercator does instead of linearly, so that
+    /// drawings line up with slippy map tiles.
+    pub mercator: bool,
+}
+
+impl Projection {
+    pub fn new(
+        (min_lon, min_lat, max_lon, max_lat): (f64, f64, f64, f64),
+        width: f64,
+        height: f64,
+    ) -> Self {
+        Projection {
+            min_lon,
+            min_lat,
+            max_lon,
+            max_lat,
+            width,
+            height,
+            mercator: false,
+        }
+    }
 
-    let x = ((lon - min_lon) / lon_range * img_size as f64) as i32;
-    let y = ((max_lat - lat) / lat_range * img_size as f64) as i32;
+    /// A Web Mercator projection of the bounds, as used for map tiles.
+    pub fn mercator(bounds: (f64, f64, f64, f64), width: f64, height: f64) -> Self {
+        Projection {
+            mercator: true,
+            ..Projection::new(bounds, width, height)
+        }
+    }
+
+    pub fn project(&self, lon: f64, lat: f64) -> (f64, f64) {
+        let lon_range = self.max_lon - self.min_lon;
+        let x = (lon - self.min_lon) / lon_range * self.width;
+
+        let (top, bottom, lat) = if self.mercator {
+            (
+                mercator_y(self.max_lat),
+                mercator_y(self.min_lat),
+                mercator_y(lat),
+            )
+        } else {
+            (self.max_lat, self.min_lat, lat)
+        };
+        let y = (top - lat) / (top - bottom) * self.height;
+
+        (x, y)
+    }
 
-    (x, y)
+    /// Ground resolution in the north-south direction.
+    pub fn meters_per_pixel(&self) -> f64 {
+        (self.max_lat - self.min_lat) * METERS_PER_DEGREE_LAT / self.height
+    }
+
+    /// The lon/lat at a point of the drawing surface.
+    pub fn unproject(&self, x: f64, y: f64) -> (f64, f64) {
+        let lon = self.min_lon + x / self.width * (self.max_lon - self.min_lon);
+        let lat = if self.mercator {
+            let (top, bottom) = (mercator_y(self.max_lat), mercator_y(self.min_lat));
+            (top - y / self.height * (top - bottom))
+                .sinh()
+                .atan()
+                .to_degrees()
+        } else {
+            self.max_lat - y / self.height * (self.max_lat - self.min_lat)
+        };
+        (lon, lat)
+    }
+
+    pub fn to_pixel(&self, lon: f64, lat: f64) -> (i32, i32) {
+        let (x, y) = self.project(lon, lat);
+        (x as i32, y as i32)
+    }
 }
 
-fn plot(img: &mut RgbaImage, x: i32, y: i32, color: Rgba<u8>, alpha: f32) {
+/// Web Mercator northing of a latitude, in radians of arc at the equator.
+fn mercator_y(lat: f64) -> f64 {
+    (std::f64::consts::FRAC_PI_4 + lat.to_radians() / 2.0)
+        .tan()
+        .ln()
+}
+
+/// Something the map layers can be drawn onto. Points are already projected
+/// into surface coordinates.
+pub trait DrawTarget {
+    fn fill_polygon(&mut self, points: &[(f64, f64)], color: Rgba<u8>);
+    fn stroke_line(&mut self, points: &[(f64, f64)], color: Rgba<u8>, width: f64);
+    /// Fills an area given as its outer ring followed by its holes. Rings are
+    /// filled by the even-odd rule, so disjoint rings are filled once each.
+    fn fill_rings(&mut self, rings: &[Vec<(f64, f64)>], color: Rgba<u8>);
+    /// Blends an image the size of the surface over what is drawn.
+    fn blend_image(&mut self, image: &RgbaImage);
+}
+
+impl DrawTarget for RgbaImage {
+    fn fill_polygon(&mut self, points: &[(f64, f64)], color: Rgba<u8>) {
+        let mut pixels: Vec<Point<i32>> = Vec::with_capacity(points.len());
+
+        for &(x, y) in points {
+            let new_point = Point::new(x as i32, y as i32);
+            if pixels.last() != Some(&new_point) {
+                pixels.push(new_point);
+            }
+        }
+
+        // make sure the polygon is not closed
+        if let Some(first) = pixels.first() {
+            if let Some(last) = pixels.last() {
+                if first == last {
+                    pixels.pop();
+                }
+            }
+        }
+
+        draw_polygon_mut(self, &pixels, color);
+    }
+
+    fn stroke_line(&mut self, points: &[(f64, f64)], color: Rgba<u8>, width: f64) {
+        let thickness = (width / 2.0) as i32;
+        // Segments are cut at the image edge, with room for the line width
+        let margin = (thickness + 1) as f64;
+        let bounds = (
+            -margin,
+            -margin,
+            self.width() as f64 + margin,
+            self.height() as f64 + margin,
+        );
+        for p in points.windows(2) {
+            let Some((start, end)) = clip_segment(p[0], p[1], bounds) else {
+                continue;
+            };
+            let (x0, y0) = (start.0 as i32, start.1 as i32);
+            let (x1, y1) = (end.0 as i32, end.1 as i32);
+
+            if thickness == 0 {
+                draw_line_wu(self, x0, y0, x1, y1, color);
+                continue;
+            }
+
+            // Draw lines offset by a certain amount perpendicular to the line direction
+            for offset in -thickness..=thickness {
+                let (offset_x, offset_y) = perpendicular_offset(x0, y0, x1, y1, offset);
+                draw_line_wu(
+                    self,
+                    x0 + offset_x,
+                    y0 + offset_y,
+                    x1 + offset_x,
+                    y1 + offset_y,
+                    color,
+                );
+            }
+        }
+    }
+
+    fn fill_rings(&mut self, rings: &[Vec<(f64, f64)>], color: Rgba<u8>) {
+        if rings.len() == 1 && color[3] == 255 {
+            self.fill_polygon(&rings[0], color);
+            return;
+        }
+        let edges: Vec<((f64, f64), (f64, f64))> = rings
+            .iter()
+            .filter(|ring| ring.len() >= 3)
+            .flat_map(|ring| {
+                ring.iter()
+                    .zip(ring.iter().cycle().skip(1))
+                    .map(|(&a, &b)| (a, b))
+            })
+            .collect();
+        let (min_y, max_y) = edges.iter().fold((f64::MAX, f64::MIN), |(lo, hi), (a, _)| {
+            (lo.min(a.1), hi.max(a.1))
+        });
+        let first_row = min_y.floor().max(0.0) as u32;
+        let last_row = max_y.ceil().min(self.height() as f64) as u32;
+
+        // Scanlines through the pixel centres, filled between every other
+        // crossing so holes stay open
+        let mut crossings = Vec::new();
+        for y in first_row..last_row {
+            let center = y as f64 + 0.5;
+            crossings.clear();
+            for &((x0, y0), (x1, y1)) in &edges {
+                if (y0 > center) != (y1 > center) {
+                    crossings.push(x0 + (center - y0) / (y1 - y0) * (x1 - x0));
+                }
+            }
+            crossings.sort_by(f64::total_cmp);
+            for span in crossings.chunks_exact(2) {
+                let start = (span[0] - 0.5).ceil().max(0.0) as u32;
+                let end = ((span[1] - 0.5).ceil().max(0.0) as u32).min(self.width());
+                for x in start..end {
+                    self.get_pixel_mut(x, y).blend(&color);
+                }
+            }
+        }
+    }
+
+    fn blend_image(&mut self, image: &RgbaImage) {
+        for (pixel, over) in self.pixels_mut().zip(image.pixels()) {
+            pixel.blend(over);
+        }
+    }
+}
+
+/// Liang–Barsky clipping of the segment from `a` to `b` to the box
+/// `(min_x, min_y, max_x, max_y)`. Returns the part inside, if any.
+pub(crate) fn clip_segment(
+    a: (f64, f64),
+    b: (f64, f64),
+    (min_x, min_y, max_x, max_y): (f64, f64, f64, f64),
+) -> Option<((f64, f64), (f64, f64))> {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let (mut t0, mut t1) = (0.0f64, 1.0f64);
+    for (p, q) in [
+        (-dx, a.0 - min_x),
+        (dx, max_x - a.0),
+        (-dy, a.1 - min_y),
+        (dy, max_y - a.1),
+    ] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None;
+            }
+        } else if p < 0.0 {
+            t0 = t0.max(q / p);
+        } else {
+            t1 = t1.min(q / p);
+        }
+    }
+    if t0 > t1 {
+        return None;
+    }
+    let at = |t: f64| {
+        if t == 0.0 {
+            a
+        } else if t == 1.0 {
+            b
+        } else {
+            (a.0 + t * dx, a.1 + t * dy)
+        }
+    };
+    Some((at(t0), at(t1)))
+}
+
+fn project_way(way: &[(f64, f64)], projection: &Projection) -> Vec<(f64, f64)> {
+    way.iter()
+        .map(|&(lon, lat)| projection.project(lon, lat))
+        .collect()
+}
+
+/// How far in pixels a simplified outline may stray from the original.
+/// Vertices within it of their neighbours would land on the same pixels.
+const PIXEL_TOLERANCE: f64 = 0.5;
+
+/// Projects a line and drops the vertices that make no visible difference.
+fn project_line(way: &[(f64, f64)], projection: &Projection) -> Vec<(f64, f64)> {
+    douglas_peucker(&project_way(way, projection), PIXEL_TOLERANCE)
+}
+
+/// Projects a polygon outline and drops the vertices that make no visible
+/// difference.
+fn project_polygon(ring: &[(f64, f64)], projection: &Projection) -> Vec<(f64, f64)> {
+    visvalingam(
+        &project_way(ring, projection),
+        PIXEL_TOLERANCE * PIXEL_TOLERANCE,
+    )
+}
+
+pub fn plot(img: &mut RgbaImage, x: i32, y: i32, color: Rgba<u8>, alpha: f32) {
     if x >= 0 && y >= 0 && x < img.width() as i32 && y < img.height() as i32 {
         let pixel = img.get_pixel_mut(x as u32, y as u32);
         *pixel = interpolate(*pixel, color, alpha);
@@ -113,28 +397,96 @@ fn draw_line_wu(img: &mut RgbaImage, x0: i32, y0: i32, x1: i32, y1: i32, color:
     }
 }
 
-pub fn draw_map(
-    highways: &[Vec<(f64, f64)>],
-    waterways: &[Vec<(f64, f64)>],
-    railways: &[Vec<(f64, f64)>],
-    buildings: &[Vec<(f64, f64)>],
-    naturals: &[Vec<(f64, f64)>],
-    aeroways: &[Vec<(f64, f64)>],
-    multipolygons: &[Vec<Vec<(f64, f64)>>], // Added multipolygons parameter
-    path: &[(f64, f64)],
-) {
-    if highways.is_empty()
-        && waterways.is_empty()
-        && railways.is_empty()
-        && buildings.is_empty()
-        && multipolygons.is_empty()
+/// A group of features that is drawn in one pass.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layer {
+    Landuse,
+    Naturals,
+    Aeroways,
+    Multipolygons,
+    Water,
+    Parks,
+    /// Terrain shadows from the elevation model.
+    Hillshade,
+    /// Contour lines from the elevation model.
+    Contours,
+    Waterways,
+    Buildings,
+    Roads,
+    Railways,
+    Isochrone,
+    Pois,
+    Route,
+}
+
+/// Back to front drawing order: areas first, then the terrain over them, then
+/// lines and symbols on top.
+pub const DEFAULT_LAYERS: [Layer; 15] = [
+    Layer::Landuse,
+    Layer::Naturals,
+    Layer::Aeroways,
+    Layer::Multipolygons,
+    Layer::Water,
+    Layer::Parks,
+    Layer::Hillshade,
+    Layer::Contours,
+    Layer::Waterways,
+    Layer::Buildings,
+    Layer::Roads,
+    Layer::Railways,
+    Layer::Isochrone,
+    Layer::Pois,
+    Layer::Route,
+];
+
+/// Options for rendering the map image.
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    /// Draw street and place names.
+    pub labels: bool,
+    /// Symbols the POIs are drawn with.
+    pub poi_styles: PoiStyles,
+    /// Layers to draw, back to front.
+    pub layers: Vec<Layer>,
+    /// Reachable area to shade on the `Isochrone` layer.
+    pub isochrone: Option<Isochrone>,
+    /// Alternative routes drawn under the main route on the `Route` layer.
+    pub alternatives: Vec<Vec<(f64, f64)>>,
+    /// Elevation model for the `Hillshade` and `Contours` layers.
+    pub dem: Option<Arc<Dem>>,
+    /// Meters between contour lines.
+    pub contour_interval: f64,
+    /// Also write the stitched map as a GeoTIFF.
+    pub geotiff: bool,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            labels: true,
+            poi_styles: PoiStyles::default(),
+            layers: DEFAULT_LAYERS.to_vec(),
+            isochrone: None,
+            alternatives: Vec::new(),
+            dem: None,
+            contour_interval: 20.0,
+            geotiff: false,
+        }
+    }
+}
+
+pub fn draw_map(data: &CachedData, path: &[(f64, f64)], options: &RenderOptions) {
+    if data.highways.is_empty()
+        && data.waterways.is_empty()
+        && data.railways.is_empty()
+        && data.buildings.is_empty()
+        && data.multipolygons.is_empty()
     {
         println!("No ways to draw.");
         return;
     }
 
-    let (min_lon, min_lat, max_lon, max_lat) =
-        calculate_bounding_box(&[highways, waterways, railways]);
+    let (min_lon, min_lat, max_lon, max_lat) = data_bounding_box(data);
 
     println!(
         "Bounding box: ({}, {}), ({}, {})",
@@ -162,223 +514,289 @@ pub fn draw_map(
         let tile_min_lat = min_lat + y as f64 * lat_step;
         let tile_max_lat = tile_min_lat + lat_step;
 
-        let mut img: image::ImageBuffer<Rgba<u8>, Vec<u8>> = RgbaImage::new(img_size, img_size);
-
-        draw_buildings(
-            &mut img,
-            naturals,
-            tile_min_lon,
-            tile_min_lat,
-            tile_max_lon,
-            tile_max_lat,
-            img_size,
-            Rgba([0, 255, 0, 100]), // Green
-        );
-
-        draw_multipolygons(
-            &mut img,
-            multipolygons,
-            tile_min_lon,
-            tile_min_lat,
-            tile_max_lon,
-            tile_max_lat,
-            img_size,
-            Rgba([128, 128, 128, 100]), // Gray
-        );
-
-        draw_buildings(
-            &mut img,
-            aeroways,
-            tile_min_lon,
-            tile_min_lat,
-            tile_max_lon,
-            tile_max_lat,
-            img_size,
-            Rgba([169, 169, 169, 255]),
-        );
-
-        draw_buildings(
-            &mut img,
-            buildings,
-            tile_min_lon,
-            tile_min_lat,
-            tile_max_lon,
-            tile_max_lat,
-            img_size,
-            Rgba([245, 245, 220, 255]), //beige
-        );
-
-        draw_ways(
-            &mut img,
-            highways,
-            tile_min_lon,
-            tile_min_lat,
-            tile_max_lon,
-            tile_max_lat,
-            img_size,
-            Rgba([255, 255, 255, 255]),
-        );
-        draw_ways(
-            &mut img,
-            waterways,
-            tile_min_lon,
-            tile_min_lat,
-            tile_max_lon,
-            tile_max_lat,
-            img_size,
-            Rgba([0, 0, 255, 255]),
-        );
-        draw_ways(
-            &mut img,
-            railways,
-            tile_min_lon,
-            tile_min_lat,
-            tile_max_lon,
-            tile_max_lat,
-            img_size,
-            Rgba([255, 0, 0, 255]),
-        );
-
-        draw_path(
-            &mut img,
-            path,
-            tile_min_lon,
-            tile_min_lat,
-            tile_max_lon,
-            tile_max_lat,
-            img_size,
-            Rgba([0, 255, 0, 255]),
-            4,
+        let projection = Projection::new(
+            (tile_min_lon, tile_min_lat, tile_max_lon, tile_max_lat),
+            img_size as f64,
+            img_size as f64,
         );
+        let img = render_image(data, &projection, path, options);
 
         let file_name = format!("{}/{}_{}.png", output_dir, x, y);
         img.save(&file_name).unwrap();
+        write_world_file(&file_name, &projection).unwrap();
         println!("Tile {}_{} rendered in {:?}", x, y, time_start.elapsed());
     });
 
-    stitch_images(tiles_x, tiles_y, img_size, output_dir, "stitched_map.png");
+    let stitched_image = stitch_images(tiles_x, tiles_y, img_size, output_dir, "stitched_map.png");
+    let projection = Projection::new(
+        (min_lon, min_lat, max_lon, max_lat),
+        (img_size * tiles_x as u32) as f64,
+        (img_size * tiles_y as u32) as f64,
+    );
+    write_world_file("stitched_map.png", &projection).unwrap();
+    if options.geotiff {
+        let time_start = Instant::now();
+        write_geotiff(&stitched_image, &projection, "stitched_map.tif").unwrap();
+        println!("GeoTIFF written in {:?}", time_start.elapsed());
+    }
+}
+
+/// Renders an image covering `projection`, with labels if `options` asks for them.
+pub fn render_image(
+    data: &CachedData,
+    projection: &Projection,
+    path: &[(f64, f64)],
+    options: &RenderOptions,
+) -> RgbaImage {
+    let mut img = RgbaImage::new(projection.width as u32, projection.height as u32);
+    draw_layers(&mut img, data, projection, path, options);
+    if options.labels {
+        Labeler::new().draw_labels(&mut img, data, projection);
+    }
+    img
 }
 
-fn draw_ways(
-    img: &mut RgbaImage,
-    ways: &[Vec<(f64, f64)>],
-    min_lon: f64,
-    min_lat: f64,
-    max_lon: f64,
-    max_lat: f64,
-    img_size: u32,
-    color: Rgba<u8>,
+/// Draws the layers in `options` and the route onto `target`, in list order.
+pub fn draw_layers(
+    target: &mut impl DrawTarget,
+    data: &CachedData,
+    projection: &Projection,
+    path: &[(f64, f64)],
+    options: &RenderOptions,
 ) {
-    for way in ways {
-        for w in way.windows(2) {
-            let (x0, y0) =
-                lon_lat_to_pixel(w[0].0, w[0].1, min_lon, min_lat, max_lon, max_lat, img_size);
-            let (x1, y1) =
-                lon_lat_to_pixel(w[1].0, w[1].1, min_lon, min_lat, max_lon, max_lat, img_size);
-
-            if x0 >= 0
-                && x0 < img_size as i32
-                && y0 >= 0
-                && y0 < img_size as i32
-                && x1 >= 0
-                && x1 < img_size as i32
-                && y1 >= 0
-                && y1 < img_size as i32
-            {
-                draw_line_wu(img, x0, y0, x1, y1, color);
+    for layer in &options.layers {
+        match layer {
+            Layer::Landuse => draw_areas(
+                target,
+                &data.landuse,
+                data.polygons_in(Category::Landuse),
+                projection,
+                Rgba([224, 218, 206, 255]),
+            ),
+            Layer::Naturals => draw_areas(
+                target,
+                &data.naturals,
+                data.polygons_in(Category::Natural),
+                projection,
+                Rgba([214, 204, 170, 255]),
+            ),
+            Layer::Aeroways => draw_areas(
+                target,
+                &data.aeroways,
+                data.polygons_in(Category::Aeroway),
+                projection,
+                Rgba([169, 169, 169, 255]),
+            ),
+            Layer::Multipolygons => draw_multipolygons(
+                target,
+                &data.multipolygons,
+                projection,
+                Rgba([128, 128, 128, 100]), // Gray
+            ),
+            Layer::Water => draw_areas(
+                target,
+                &data.water,
+                data.polygons_in(Category::Water),
+                projection,
+                Rgba([120, 170, 230, 255]),
+            ),
+            Layer::Parks => draw_areas(
+                target,
+                &data.parks,
+                data.polygons_in(Category::Park),
+                projection,
+                Rgba([60, 150, 60, 255]),
+            ),
+            Layer::Hillshade => {
+                if let Some(dem) = &options.dem {
+                    target.blend_image(&dem.hillshade(projection));
+                }
+            }
+            Layer::Contours => {
+                if let Some(dem) = &options.dem {
+                    draw_contours(target, dem, projection, options.contour_interval);
+                }
+            }
+            Layer::Waterways => {
+                draw_ways(target, &data.waterways, projection, Rgba([0, 0, 255, 255]))
+            }
+            Layer::Buildings => draw_areas(
+                target,
+                &data.buildings,
+                data.polygons_in(Category::Building),
+                projection,
+                Rgba([245, 245, 220, 255]), //beige
+            ),
+            Layer::Roads => draw_roads(target, &data.highways, projection),
+            Layer::Railways => {
+                draw_ways(target, &data.railways, projection, Rgba([255, 0, 0, 255]))
+            }
+            Layer::Isochrone => {
+                if let Some(isochrone) = &options.isochrone {
+                    let cell_size = projection.width / 256.0;
+                    let runs = isochrone.area_polygons(&data.graph, projection, cell_size);
+                    target.fill_rings(&runs, Rgba([255, 140, 0, 90]));
+                }
+            }
+            Layer::Pois => draw_pois(target, &data.pois, projection, &options.poi_styles),
+            Layer::Route => {
+                // Alternatives go first so the main route ends up on top
+                for (i, alternative) in options.alternatives.iter().enumerate().rev() {
+                    let color = ALTERNATIVE_COLORS[i % ALTERNATIVE_COLORS.len()];
+                    draw_path(target, alternative, projection, color, 7.0);
+                }
+                draw_path(target, path, projection, Rgba([0, 255, 0, 255]), 9.0)
             }
         }
     }
 }
 
-fn draw_buildings(
-    img: &mut RgbaImage,
-    buildings: &[Vec<(f64, f64)>],
-    min_lon: f64,
-    min_lat: f64,
-    max_lon: f64,
-    max_lat: f64,
-    img_size: u32,
+fn draw_ways<W: AsRef<[(f64, f64)]>>(
+    target: &mut impl DrawTarget,
+    ways: &[W],
+    projection: &Projection,
     color: Rgba<u8>,
 ) {
-    for building in buildings {
-        let mut pixels = Vec::new();
+    for way in ways {
+        target.stroke_line(&project_line(way.as_ref(), projection), color, 1.0);
+    }
+}
 
-        for point in building {
-            let (x, y) = lon_lat_to_pixel(
-                point.0, point.1, min_lon, min_lat, max_lon, max_lat, img_size,
-            );
-            let new_point = Point::new(x, y);
-            if !pixels.contains(&new_point) {
-                pixels.push(new_point);
-            }
+/// How a road class is drawn. Widths are in pixels of the rendered image.
+#[derive(Debug, Clone, Copy)]
+pub struct RoadStyle {
+    pub color: Rgba<u8>,
+    pub width: f64,
+    /// Drawn below the road, slightly wider, to outline it.
+    pub casing: Option<(Rgba<u8>, f64)>,
+    /// The class is hidden once the map is zoomed out past this many meters per pixel.
+    pub max_meters_per_pixel: f64,
+}
+
+pub fn road_style(class: HighwayClass) -> RoadStyle {
+    let (color, width, cased, max_meters_per_pixel) = match class {
+        HighwayClass::Motorway => ([232, 146, 162, 255], 8.0, true, f64::MAX),
+        HighwayClass::Trunk => ([249, 178, 156, 255], 7.0, true, f64::MAX),
+        HighwayClass::Primary => ([252, 214, 164, 255], 6.0, true, f64::MAX),
+        HighwayClass::Secondary => ([246, 250, 187, 255], 5.0, true, 60.0),
+        HighwayClass::Tertiary => ([255, 255, 255, 255], 4.0, true, 30.0),
+        HighwayClass::Residential => ([255, 255, 255, 255], 3.0, true, 10.0),
+        HighwayClass::Service => ([255, 255, 255, 255], 1.0, false, 4.0),
+        HighwayClass::Path => ([250, 128, 114, 255], 1.0, false, 2.0),
+    };
+    RoadStyle {
+        color: Rgba(color),
+        width,
+        casing: cased.then_some((Rgba([140, 140, 140, 255]), width + 3.0)),
+        max_meters_per_pixel,
+    }
+}
+
+/// Whether roads of this class are shown at the projection's scale.
+pub fn road_visible(class: HighwayClass, projection: &Projection) -> bool {
+    projection.meters_per_pixel() <= road_style(class).max_meters_per_pixel
+}
+
+/// Draws roads by class in ascending importance, so major roads end up on top.
+fn draw_roads(target: &mut impl DrawTarget, highways: &[Highway], projection: &Projection) {
+    let mut by_class: HashMap<HighwayClass, Vec<Vec<(f64, f64)>>> = HashMap::new();
+    for highway in highways {
+        let class = highway.class();
+        if road_visible(class, projection) {
+            by_class
+                .entry(class)
+                .or_default()
+                .push(project_line(&highway.coords, projection));
         }
+    }
 
-        // make sure the polygon is not closed
-        if let Some(first) = pixels.first() {
-            if let Some(last) = pixels.last() {
-                if first == last {
-                    pixels.pop();
-                }
+    for class in HighwayClass::ALL {
+        let Some(lines) = by_class.get(&class) else {
+            continue;
+        };
+        let style = road_style(class);
+        if let Some((casing_color, casing_width)) = style.casing {
+            for line in lines {
+                target.stroke_line(line, casing_color, casing_width);
             }
         }
-
-        draw_polygon_mut(img, &pixels, color);
+        for line in lines {
+            target.stroke_line(line, style.color, style.width);
+        }
     }
 }
 
-fn draw_path(
-    img: &mut RgbaImage,
-    path: &[(f64, f64)],
-    min_lon: f64,
-    min_lat: f64,
-    max_lon: f64,
-    max_lat: f64,
-    img_size: u32,
+/// Fills the closed ways of a layer, then the polygons assembled from its
+/// multipolygon relations with their holes left open.
+fn draw_areas<'a>(
+    target: &mut impl DrawTarget,
+    ways: &[Vec<(f64, f64)>],
+    polygons: impl Iterator<Item = &'a Polygon>,
+    projection: &Projection,
     color: Rgba<u8>,
-    thickness: i32, // Added parameter for line thickness
 ) {
-    for points in path.windows(2) {
-        let (x0, y0) = lon_lat_to_pixel(
-            points[0].0,
-            points[0].1,
-            min_lon,
-            min_lat,
-            max_lon,
-            max_lat,
-            img_size,
-        );
-        let (x1, y1) = lon_lat_to_pixel(
-            points[1].0,
-            points[1].1,
-            min_lon,
-            min_lat,
-            max_lon,
-            max_lat,
-            img_size,
-        );
+    for way in ways {
+        target.fill_polygon(&project_polygon(way, projection), color);
+    }
+    for polygon in polygons {
+        let rings: Vec<_> = polygon
+            .iter()
+            .map(|ring| project_polygon(ring, projection))
+            .collect();
+        target.fill_rings(&rings, color);
+    }
+}
 
-        // Draw lines offset by a certain amount perpendicular to the line direction
-        for offset in -thickness..=thickness {
-            let (offset_x, offset_y) = perpendicular_offset(x0, y0, x1, y1, offset);
-            if (x0 + offset_x).abs() < img_size as i32
-                && (y0 + offset_y).abs() < img_size as i32
-                && (x1 + offset_x).abs() < img_size as i32
-                && (y1 + offset_y).abs() < img_size as i32
-            {
-                draw_line_wu(
-                    img,
-                    x0 + offset_x,
-                    y0 + offset_y,
-                    x1 + offset_x,
-                    y1 + offset_y,
-                    color,
-                );
+/// Draws contour lines, with every fifth one thicker.
+fn draw_contours(target: &mut impl DrawTarget, dem: &Dem, projection: &Projection, interval: f64) {
+    let bbox = (
+        projection.min_lon,
+        projection.min_lat,
+        projection.max_lon,
+        projection.max_lat,
+    );
+    let color = Rgba([170, 120, 70, 255]);
+    for (level, [a, b]) in dem.contours(bbox, interval) {
+        let index = (level / interval).round() as i64 % 5 == 0;
+        let line = [projection.project(a.0, a.1), projection.project(b.0, b.1)];
+        target.stroke_line(&line, color, if index { 2.0 } else { 1.0 });
+    }
+}
+
+fn draw_pois(
+    target: &mut impl DrawTarget,
+    pois: &[Poi],
+    projection: &Projection,
+    styles: &PoiStyles,
+) {
+    for poi in pois {
+        if let Some(symbol) = styles.symbol_for(poi) {
+            let (x, y) = projection.project(poi.lon, poi.lat);
+            if x < 0.0 || y < 0.0 || x > projection.width || y > projection.height {
+                continue;
             }
+            target.fill_polygon(&symbol.outline(x, y), symbol.color);
         }
     }
 }
 
+/// Colors of alternative routes, in order.
+const ALTERNATIVE_COLORS: [Rgba<u8>; 3] = [
+    Rgba([30, 110, 255, 255]),
+    Rgba([200, 60, 220, 255]),
+    Rgba([255, 160, 0, 255]),
+];
+
+fn draw_path(
+    target: &mut impl DrawTarget,
+    path: &[(f64, f64)],
+    projection: &Projection,
+    color: Rgba<u8>,
+    width: f64,
+) {
+    target.stroke_line(&project_line(path, projection), color, width);
+}
+
 fn perpendicular_offset(x0: i32, y0: i32, x1: i32, y1: i32, offset: i32) -> (i32, i32) {
     let dx = x1 - x0;
     let dy = y1 - y0;
@@ -397,7 +815,7 @@ fn stitch_images(
     img_size: u32,
     tile_prefix: &str,
     output_file: &str,
-) {
+) -> RgbaImage {
     let total_width = img_size * tiles_x as u32;
     let total_height = img_size * tiles_y as u32;
 
@@ -443,40 +861,17 @@ fn stitch_images(
     stitched_image
         .write_to(fout, image::ImageFormat::Png)
         .unwrap();
+    Arc::into_inner(stitched_image).unwrap()
 }
 
 fn draw_multipolygons(
-    img: &mut RgbaImage,
+    target: &mut impl DrawTarget,
     multipolygons: &[Vec<Vec<(f64, f64)>>],
-    min_lon: f64,
-    min_lat: f64,
-    max_lon: f64,
-    max_lat: f64,
-    img_size: u32,
+    projection: &Projection,
     base_color: Rgba<u8>,
 ) {
     for multipolygon in multipolygons {
-        let mut polygon_points: Vec<Point<i32>> = Vec::new();
-
         for (i, polygon) in multipolygon.iter().enumerate() {
-            for &(lon, lat) in polygon {
-                let (x, y) =
-                    lon_lat_to_pixel(lon, lat, min_lon, min_lat, max_lon, max_lat, img_size);
-                let new_point = Point::new(x, y);
-                if !polygon_points.contains(&new_point) {
-                    polygon_points.push(new_point);
-                }
-            }
-
-            // make sure the polygon is not closed
-            if let Some(first) = polygon_points.first() {
-                if let Some(last) = polygon_points.last() {
-                    if first == last {
-                        polygon_points.pop();
-                    }
-                }
-            }
-
             // Adjust color intensity for each polygon
             let color_adjustment = (i as u8 * 30) % 255;
             let adjusted_color = Rgba([
@@ -487,8 +882,7 @@ fn draw_multipolygons(
             ]);
 
             // Draw the polygon
-            draw_polygon_mut(img, &polygon_points, adjusted_color);
-            polygon_points.clear();
+            target.fill_polygon(&project_polygon(polygon, projection), adjusted_color);
         }
     }
 }
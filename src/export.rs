@@ -0,0 +1,293 @@
+use serde_json::{json, Map, Value};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::flatgeobuf::write_flatgeobuf;
+use crate::graph::path_length_meters;
+use crate::multipolygon::assemble;
+use crate::osm::{area_category, way_category};
+use crate::simplify::{douglas_peucker, visvalingam};
+use crate::types::cached_data::{CachedData, Polygon, WayCoords};
+use crate::types::coord::Coord;
+
+/// Writes a route as a GPX 1.1 track. The total distance in meters goes into
+/// the track description.
+pub fn write_gpx(
+    path: &[Coord],
+    name: &str,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let distance = path_length_meters(path);
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        out,
+        r#"<gpx version="1.1" creator="maps" xmlns="http://www.topografix.com/GPX/1/1">"#
+    )?;
+    writeln!(out, "  <trk>")?;
+    writeln!(out, "    <name>{}</name>", escape_xml(name))?;
+    writeln!(out, "    <desc>Distance: {:.1} m</desc>", distance)?;
+    writeln!(out, "    <trkseg>")?;
+    for coord in path {
+        writeln!(
+            out,
+            r#"      <trkpt lat="{:.7}" lon="{:.7}"></trkpt>"#,
+            coord.lat, coord.lon
+        )?;
+    }
+    writeln!(out, "    </trkseg>")?;
+    writeln!(out, "  </trk>")?;
+    writeln!(out, "</gpx>")?;
+    Ok(())
+}
+
+/// A route as a GeoJSON Feature with a LineString geometry and its total
+/// distance in meters as the `distance_m` property.
+pub fn route_to_geojson(path: &[Coord]) -> Value {
+    let coordinates: Vec<_> = path.iter().map(|coord| [coord.lon, coord.lat]).collect();
+    json!({
+        "type": "Feature",
+        "geometry": {
+            "type": "LineString",
+            "coordinates": coordinates,
+        },
+        "properties": {
+            "distance_m": path_length_meters(path),
+        },
+    })
+}
+
+pub fn write_geojson(
+    path: &[Coord],
+    out: &mut impl Write,
+) -> Result<(), Box<dyn std::error::Error>> {
+    serde_json::to_writer_pretty(&mut *out, &route_to_geojson(path))?;
+    writeln!(out)?;
+    Ok(())
+}
+
+/// Writes the route as both `<prefix>.gpx` and `<prefix>.geojson`.
+pub fn export_route(path: &[Coord], prefix: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let gpx = &mut BufWriter::new(File::create(Path::new(&format!("{}.gpx", prefix)))?);
+    write_gpx(path, prefix, gpx)?;
+    gpx.flush()?;
+
+    let geojson = &mut BufWriter::new(File::create(Path::new(&format!("{}.geojson", prefix)))?);
+    write_geojson(path, geojson)?;
+    geojson.flush()?;
+    Ok(())
+}
+
+/// An extracted feature with its OSM tags, ready to be written out.
+pub struct Feature {
+    /// OSM id, negative for features from overlays.
+    pub id: i64,
+    /// `node`, `way` or `relation`.
+    pub osm_type: &'static str,
+    /// The category the feature was sorted into, e.g. `highway` or `building`.
+    pub layer: &'static str,
+    pub tags: Vec<(String, String)>,
+    pub geometry: Geometry,
+}
+
+pub enum Geometry {
+    Point((f64, f64)),
+    LineString(WayCoords),
+    Polygon(Polygon),
+    MultiPolygon(Vec<Polygon>),
+}
+
+impl Geometry {
+    /// The geometry as GeoJSON, rounded to the 7 decimals OSM stores.
+    pub fn to_geojson(&self) -> Value {
+        let position =
+            |(lon, lat): (f64, f64)| [(lon * 1e7).round() / 1e7, (lat * 1e7).round() / 1e7];
+        let ring =
+            |ring: &WayCoords| -> Vec<[f64; 2]> { ring.iter().copied().map(position).collect() };
+        let polygon =
+            |polygon: &Polygon| -> Vec<Vec<[f64; 2]>> { polygon.iter().map(ring).collect() };
+        match self {
+            Geometry::Point(point) => json!({"type": "Point", "coordinates": position(*point)}),
+            Geometry::LineString(line) => json!({"type": "LineString", "coordinates": ring(line)}),
+            Geometry::Polygon(rings) => json!({"type": "Polygon", "coordinates": polygon(rings)}),
+            Geometry::MultiPolygon(polygons) => json!({
+                "type": "MultiPolygon",
+                "coordinates": polygons.iter().map(polygon).collect::<Vec<_>>(),
+            }),
+        }
+    }
+
+    /// Drops the vertices that make no visible difference on a web map at
+    /// `zoom`, where they would stray less than half a pixel of a 256px tile.
+    /// Lines use Douglas–Peucker and polygon rings Visvalingam, which keeps
+    /// rings closed.
+    pub fn simplify(&mut self, zoom: u32) {
+        let tolerance = 0.5 * 360.0 / (256.0 * 2f64.powi(zoom as i32));
+        // Degrees of latitude are stretched by Mercator, so measure there
+        let simplify = |coords: &mut WayCoords, ring: bool| {
+            let Some(&(_, lat)) = coords.first() else {
+                return;
+            };
+            let stretch = 1.0 / lat.to_radians().cos().max(0.01);
+            let stretched: WayCoords = coords
+                .iter()
+                .map(|&(lon, lat)| (lon, lat * stretch))
+                .collect();
+            let simplified = if ring {
+                visvalingam(&stretched, tolerance * tolerance)
+            } else {
+                douglas_peucker(&stretched, tolerance)
+            };
+            *coords = simplified
+                .into_iter()
+                .map(|(lon, lat)| (lon, lat / stretch))
+                .collect();
+        };
+        match self {
+            Geometry::Point(_) => {}
+            Geometry::LineString(line) => simplify(line, false),
+            Geometry::Polygon(rings) => rings.iter_mut().for_each(|ring| simplify(ring, true)),
+            Geometry::MultiPolygon(polygons) => polygons
+                .iter_mut()
+                .flatten()
+                .for_each(|ring| simplify(ring, true)),
+        }
+    }
+}
+
+/// Every feature the map is drawn from: ways by category, multipolygon
+/// relations assembled into areas, POIs and places. Closed ways in area
+/// categories become polygons and other ways lines.
+pub fn extract_features(data: &CachedData) -> Vec<Feature> {
+    let tags = |tags: &osmpbfreader::Tags| -> Vec<(String, String)> {
+        tags.iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    };
+    let mut features = Vec::new();
+
+    let mut ways: Vec<_> = data.ways.iter().collect();
+    ways.sort_by_key(|&(id, _)| *id);
+    for (&id, way) in ways {
+        if way.member_only {
+            continue;
+        }
+        let Some(category) = way_category(&way.tags, way.is_closed()) else {
+            continue;
+        };
+        let coords = way.coords(&data.nodes);
+        if coords.len() < 2 {
+            continue;
+        }
+        let geometry = if way.is_closed() && category.is_area() && coords.len() >= 4 {
+            Geometry::Polygon(vec![coords])
+        } else {
+            Geometry::LineString(coords)
+        };
+        features.push(Feature {
+            id,
+            osm_type: "way",
+            layer: category.name(),
+            tags: tags(&way.tags),
+            geometry,
+        });
+    }
+
+    for area in &data.areas {
+        let mut polygons = assemble(area, &data.ways, &data.nodes);
+        let geometry = match polygons.len() {
+            0 => continue,
+            1 => Geometry::Polygon(polygons.pop().unwrap()),
+            _ => Geometry::MultiPolygon(polygons),
+        };
+        features.push(Feature {
+            id: area.id,
+            osm_type: "relation",
+            layer: area_category(&area.tags).map_or("multipolygon", |category| category.name()),
+            tags: tags(&area.tags),
+            geometry,
+        });
+    }
+
+    for poi in &data.pois {
+        features.push(Feature {
+            id: poi.id,
+            osm_type: "node",
+            layer: "poi",
+            tags: poi.tags.clone(),
+            geometry: Geometry::Point((poi.lon, poi.lat)),
+        });
+    }
+    for place in &data.places {
+        features.push(Feature {
+            id: place.id,
+            osm_type: "node",
+            layer: "place",
+            tags: place.tags.clone(),
+            geometry: Geometry::Point((place.lon, place.lat)),
+        });
+    }
+    features
+}
+
+/// Writes features as a GeoJSON FeatureCollection. Properties are the OSM
+/// tags plus `osm_id`, `osm_type` and `layer`, which take the place of tags
+/// with the same keys such as OSM's own `layer`.
+pub fn write_features_geojson(
+    features: &[Feature],
+    out: &mut impl Write,
+) -> Result<(), Box<dyn std::error::Error>> {
+    writeln!(out, r#"{{"type":"FeatureCollection","features":["#)?;
+    for (i, feature) in features.iter().enumerate() {
+        let mut properties = Map::new();
+        for (key, value) in &feature.tags {
+            properties.insert(key.clone(), json!(value));
+        }
+        properties.insert("osm_id".to_string(), json!(feature.id));
+        properties.insert("osm_type".to_string(), json!(feature.osm_type));
+        properties.insert("layer".to_string(), json!(feature.layer));
+        let feature = json!({
+            "type": "Feature",
+            "geometry": feature.geometry.to_geojson(),
+            "properties": properties,
+        });
+        let separator = if i + 1 < features.len() { "," } else { "" };
+        writeln!(out, "{}{}", feature, separator)?;
+    }
+    writeln!(out, "]}}")?;
+    Ok(())
+}
+
+/// Writes every extracted feature to `path`, as FlatGeobuf if it ends in
+/// `.fgb` and as GeoJSON otherwise, simplified for viewing at `zoom` if
+/// given. Returns the number of features.
+pub fn export_features(
+    data: &CachedData,
+    path: impl AsRef<Path>,
+    zoom: Option<u32>,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let mut features = extract_features(data);
+    if let Some(zoom) = zoom {
+        for feature in &mut features {
+            feature.geometry.simplify(zoom);
+        }
+    }
+    let out = &mut BufWriter::new(File::create(path.as_ref())?);
+    match path
+        .as_ref()
+        .extension()
+        .and_then(|extension| extension.to_str())
+    {
+        Some("fgb") => write_flatgeobuf(&features, out)?,
+        _ => write_features_geojson(&features, out)?,
+    }
+    out.flush()?;
+    Ok(features.len())
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,307 @@
+use serde_json::{Map, Value};
+use std::io::Write;
+
+use crate::export::{Feature, Geometry};
+
+/// File signature, "fgb" with the major version 3.
+const MAGIC: [u8; 8] = [b'f', b'g', b'b', 3, b'f', b'g', b'b', 0];
+
+const UNKNOWN: u8 = 0;
+const POINT: u8 = 1;
+const LINE_STRING: u8 = 2;
+const POLYGON: u8 = 3;
+const MULTI_POLYGON: u8 = 6;
+
+const LONG: u8 = 7;
+const STRING: u8 = 11;
+const JSON: u8 = 12;
+
+/// Attribute columns of every feature. All tags go into `tags` as a JSON
+/// object since the set of keys is open ended.
+const COLUMNS: [(&str, u8); 5] = [
+    ("osm_id", LONG),
+    ("osm_type", STRING),
+    ("layer", STRING),
+    ("name", STRING),
+    ("tags", JSON),
+];
+
+/// Writes features as FlatGeobuf without a spatial index, in WGS 84. The
+/// header declares mixed geometry types and each feature has its own.
+pub fn write_flatgeobuf(
+    features: &[Feature],
+    out: &mut impl Write,
+) -> Result<(), Box<dyn std::error::Error>> {
+    out.write_all(&MAGIC)?;
+    out.write_all(&header(features).finish())?;
+    for feature in features {
+        let table = Table::default()
+            .field(0, Field::Table(geometry(&feature.geometry)))
+            .field(1, Field::Bytes(properties(feature)));
+        out.write_all(&table.finish())?;
+    }
+    Ok(())
+}
+
+fn header(features: &[Feature]) -> Table {
+    let mut envelope = [f64::MAX, f64::MAX, f64::MIN, f64::MIN];
+    for feature in features {
+        for (x, y) in points(&feature.geometry) {
+            envelope = [
+                envelope[0].min(x),
+                envelope[1].min(y),
+                envelope[2].max(x),
+                envelope[3].max(y),
+            ];
+        }
+    }
+    let columns = COLUMNS
+        .iter()
+        .map(|&(name, kind)| {
+            Table::default()
+                .field(0, Field::String(name.to_string()))
+                .field(1, Field::U8(kind))
+        })
+        .collect();
+    let crs = Table::default()
+        .field(0, Field::String("EPSG".to_string()))
+        .field(1, Field::I32(4326));
+
+    let mut header = Table::default()
+        .field(0, Field::String("maps".to_string()))
+        .field(2, Field::U8(UNKNOWN))
+        .field(7, Field::Tables(columns))
+        .field(8, Field::U64(features.len() as u64))
+        // Written out since the default of 16 would promise an index
+        .field(9, Field::U16(0))
+        .field(10, Field::Table(crs));
+    if !features.is_empty() {
+        header = header.field(1, Field::F64s(envelope.to_vec()));
+    }
+    header
+}
+
+fn geometry(geometry: &Geometry) -> Table {
+    let rings = |rings: &[Vec<(f64, f64)>], kind: u8| {
+        let xy = rings.iter().flatten().flat_map(|&(x, y)| [x, y]).collect();
+        let mut table = Table::default()
+            .field(1, Field::F64s(xy))
+            .field(6, Field::U8(kind));
+        if rings.len() > 1 {
+            let ends = rings
+                .iter()
+                .scan(0, |end, ring| {
+                    *end += ring.len() as u32;
+                    Some(*end)
+                })
+                .collect();
+            table = table.field(0, Field::U32s(ends));
+        }
+        table
+    };
+    match geometry {
+        Geometry::Point(point) => rings(&[vec![*point]], POINT),
+        Geometry::LineString(line) => rings(std::slice::from_ref(line), LINE_STRING),
+        Geometry::Polygon(polygon) => rings(polygon, POLYGON),
+        Geometry::MultiPolygon(polygons) => {
+            let parts = polygons
+                .iter()
+                .map(|polygon| rings(polygon, POLYGON))
+                .collect();
+            Table::default()
+                .field(6, Field::U8(MULTI_POLYGON))
+                .field(7, Field::Tables(parts))
+        }
+    }
+}
+
+fn points(geometry: &Geometry) -> Box<dyn Iterator<Item = (f64, f64)> + '_> {
+    match geometry {
+        Geometry::Point(point) => Box::new(std::iter::once(*point)),
+        Geometry::LineString(line) => Box::new(line.iter().copied()),
+        Geometry::Polygon(polygon) => Box::new(polygon.iter().flatten().copied()),
+        Geometry::MultiPolygon(polygons) => Box::new(polygons.iter().flatten().flatten().copied()),
+    }
+}
+
+/// Column values as a column index followed by the value, strings with their
+/// length in front.
+fn properties(feature: &Feature) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    bytes.extend(0u16.to_le_bytes());
+    bytes.extend(feature.id.to_le_bytes());
+    let mut string = |column: u16, value: &str| {
+        bytes.extend(column.to_le_bytes());
+        bytes.extend((value.len() as u32).to_le_bytes());
+        bytes.extend(value.as_bytes());
+    };
+    let name = feature.tags.iter().find(|(key, _)| key == "name");
+    let tags: Map<String, Value> = feature
+        .tags
+        .iter()
+        .map(|(key, value)| (key.clone(), Value::String(value.clone())))
+        .collect();
+
+    string(1, feature.osm_type);
+    string(2, feature.layer);
+    if let Some((_, name)) = name {
+        string(3, name);
+    }
+    string(4, &Value::Object(tags).to_string());
+    bytes
+}
+
+/// A flatbuffers table. Tables are written before the strings, vectors and
+/// tables they point to, so every offset points forward, and values are
+/// aligned to their size from the start of the size-prefixed buffer.
+#[derive(Default)]
+struct Table {
+    fields: Vec<(usize, Field)>,
+}
+
+enum Field {
+    U8(u8),
+    U16(u16),
+    I32(i32),
+    U64(u64),
+    String(String),
+    Bytes(Vec<u8>),
+    U32s(Vec<u32>),
+    F64s(Vec<f64>),
+    Table(Table),
+    Tables(Vec<Table>),
+}
+
+impl Table {
+    fn field(mut self, index: usize, value: Field) -> Self {
+        self.fields.push((index, value));
+        self
+    }
+
+    /// The table as a size-prefixed flatbuffer with it as the root.
+    fn finish(&self) -> Vec<u8> {
+        let mut buf = vec![0; 8];
+        let root = self.write(&mut buf);
+        patch_u32(&mut buf, 4, root - 4);
+        align(&mut buf, 8);
+        let size = buf.len() - 4;
+        patch_u32(&mut buf, 0, size);
+        buf
+    }
+
+    /// Writes the vtable, the table and then its children, returning where
+    /// the table starts.
+    fn write(&self, buf: &mut Vec<u8>) -> usize {
+        let slots = self
+            .fields
+            .iter()
+            .map(|&(index, _)| index + 1)
+            .max()
+            .unwrap_or(0);
+        align(buf, 2);
+        let vtable = buf.len();
+        buf.resize(vtable + 4 + 2 * slots, 0);
+        align(buf, 4);
+        let table = buf.len();
+        buf.extend(((table - vtable) as i32).to_le_bytes());
+
+        let mut children = Vec::new();
+        for (index, field) in &self.fields {
+            let size = match field {
+                Field::U8(_) => 1,
+                Field::U16(_) => 2,
+                Field::U64(_) => 8,
+                _ => 4,
+            };
+            align(buf, size);
+            let offset = buf.len() - table;
+            patch_u16(buf, vtable + 4 + 2 * index, offset);
+            match field {
+                Field::U8(value) => buf.push(*value),
+                Field::U16(value) => buf.extend(value.to_le_bytes()),
+                Field::I32(value) => buf.extend(value.to_le_bytes()),
+                Field::U64(value) => buf.extend(value.to_le_bytes()),
+                child => {
+                    children.push((buf.len(), child));
+                    buf.extend([0; 4]);
+                }
+            }
+        }
+        patch_u16(buf, vtable, 4 + 2 * slots);
+        let size = buf.len() - table;
+        patch_u16(buf, vtable + 2, size);
+
+        for (slot, child) in children {
+            let target = child.write(buf);
+            patch_u32(buf, slot, target - slot);
+        }
+        table
+    }
+}
+
+impl Field {
+    /// Writes a string, vector or table, returning where it starts.
+    fn write(&self, buf: &mut Vec<u8>) -> usize {
+        match self {
+            Field::String(value) => {
+                let start = vector_start(buf, value.len(), 1);
+                buf.extend(value.as_bytes());
+                buf.push(0);
+                start
+            }
+            Field::Bytes(values) => {
+                let start = vector_start(buf, values.len(), 1);
+                buf.extend(values);
+                start
+            }
+            Field::U32s(values) => {
+                let start = vector_start(buf, values.len(), 4);
+                buf.extend(values.iter().flat_map(|value| value.to_le_bytes()));
+                start
+            }
+            Field::F64s(values) => {
+                let start = vector_start(buf, values.len(), 8);
+                buf.extend(values.iter().flat_map(|value| value.to_le_bytes()));
+                start
+            }
+            Field::Table(table) => table.write(buf),
+            Field::Tables(tables) => {
+                let start = vector_start(buf, tables.len(), 4);
+                let slots = buf.len();
+                buf.resize(slots + 4 * tables.len(), 0);
+                for (i, table) in tables.iter().enumerate() {
+                    let target = table.write(buf);
+                    patch_u32(buf, slots + 4 * i, target - (slots + 4 * i));
+                }
+                start
+            }
+            Field::U8(_) | Field::U16(_) | Field::I32(_) | Field::U64(_) => {
+                unreachable!("scalars are stored inline")
+            }
+        }
+    }
+}
+
+/// Writes a vector length so the elements after it are aligned.
+fn vector_start(buf: &mut Vec<u8>, len: usize, element_size: usize) -> usize {
+    while !(buf.len() + 4).is_multiple_of(element_size.max(4)) || !buf.len().is_multiple_of(4) {
+        buf.push(0);
+    }
+    let start = buf.len();
+    buf.extend((len as u32).to_le_bytes());
+    start
+}
+
+fn align(buf: &mut Vec<u8>, size: usize) {
+    while !buf.len().is_multiple_of(size) {
+        buf.push(0);
+    }
+}
+
+fn patch_u16(buf: &mut [u8], pos: usize, value: usize) {
+    buf[pos..pos + 2].copy_from_slice(&(value as u16).to_le_bytes());
+}
+
+fn patch_u32(buf: &mut [u8], pos: usize, value: usize) {
+    buf[pos..pos + 4].copy_from_slice(&(value as u32).to_le_bytes());
+}
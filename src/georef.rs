@@ -0,0 +1,91 @@
+use image::RgbaImage;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use tiff::encoder::colortype::RGBA8;
+use tiff::encoder::compression::Deflate;
+use tiff::encoder::TiffEncoder;
+use tiff::tags::Tag;
+
+use crate::drawing::Projection;
+
+/// WGS 84 in the WKT flavour `.prj` files use. Our images are drawn linearly
+/// in lon/lat, which is exactly this coordinate system.
+const WGS84_WKT: &str = "GEOGCS[\"GCS_WGS_1984\",DATUM[\"D_WGS_1984\",\
+SPHEROID[\"WGS_1984\",6378137.0,298.257223563]],PRIMEM[\"Greenwich\",0.0],\
+UNIT[\"Degree\",0.0174532925199433]]";
+
+/// GeoTIFF keys: a geographic model, pixels as areas, in EPSG:4326.
+const GEO_KEYS: [u16; 16] = [
+    1, 1, 0, 3, // Version 1.1.0 with three keys
+    1024, 0, 1, 2, // GTModelTypeGeoKey: geographic
+    1025, 0, 1, 1, // GTRasterTypeGeoKey: pixel is area
+    2048, 0, 1, 4326, // GeographicTypeGeoKey: WGS 84
+];
+
+/// Degrees per pixel eastwards and southwards.
+fn pixel_size(projection: &Projection) -> (f64, f64) {
+    (
+        (projection.max_lon - projection.min_lon) / projection.width,
+        (projection.max_lat - projection.min_lat) / projection.height,
+    )
+}
+
+/// Writes a world file and a `.prj` next to an image covering `projection`,
+/// so GIS tools can place it. The world file extension follows the usual
+/// convention, `.pgw` for `.png`.
+pub fn write_world_file(
+    image_path: impl AsRef<Path>,
+    projection: &Projection,
+) -> std::io::Result<()> {
+    let image_path = image_path.as_ref();
+    let extension = image_path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default();
+    let world_extension = match (extension.chars().next(), extension.chars().last()) {
+        (Some(first), Some(last)) => format!("{}{}w", first, last),
+        _ => "wld".to_string(),
+    };
+
+    // Rotation terms are zero, and the position is the centre of the top
+    // left pixel
+    let (width, height) = pixel_size(projection);
+    let out = &mut BufWriter::new(File::create(image_path.with_extension(world_extension))?);
+    writeln!(out, "{}", width)?;
+    writeln!(out, "0.0")?;
+    writeln!(out, "0.0")?;
+    writeln!(out, "{}", -height)?;
+    writeln!(out, "{}", projection.min_lon + width / 2.0)?;
+    writeln!(out, "{}", projection.max_lat - height / 2.0)?;
+    out.flush()?;
+
+    std::fs::write(image_path.with_extension("prj"), WGS84_WKT)
+}
+
+/// Writes an image covering `projection` as a deflate compressed GeoTIFF in
+/// EPSG:4326.
+pub fn write_geotiff(
+    image: &RgbaImage,
+    projection: &Projection,
+    path: impl AsRef<Path>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (width, height) = pixel_size(projection);
+    let mut encoder = TiffEncoder::new(BufWriter::new(File::create(path.as_ref())?))?;
+    let mut tiff = encoder.new_image_with_compression::<RGBA8, _>(
+        image.width(),
+        image.height(),
+        Deflate::default(),
+    )?;
+    let tags = tiff.encoder();
+    // The fourth sample is unassociated alpha
+    tags.write_tag(Tag::ExtraSamples, 2u16)?;
+    tags.write_tag(Tag::ModelPixelScaleTag, &[width, height, 0.0][..])?;
+    tags.write_tag(
+        Tag::ModelTiepointTag,
+        &[0.0, 0.0, 0.0, projection.min_lon, projection.max_lat, 0.0][..],
+    )?;
+    tags.write_tag(Tag::GeoKeyDirectoryTag, &GEO_KEYS[..])?;
+    tiff.write_data(image.as_raw())?;
+    Ok(())
+}
@@ -1,13 +1,15 @@
-use std::collections::HashMap;
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
 
 use pathfinding::prelude::astar;
 
-use crate::types::{coord::Coord, edge::Edge};
+use crate::types::restriction::{RestrictionKind, TurnRestriction};
+use crate::types::{coord::Coord, edge::Edge, highway::Highway};
 
-pub fn build_graph(highways: &[Vec<(f64, f64)>]) -> HashMap<Coord, Vec<Edge>> {
+pub fn build_graph(highways: &[Highway]) -> HashMap<Coord, Vec<Edge>> {
     let mut graph = HashMap::new();
     for way in highways {
-        for window in way.windows(2) {
+        for window in way.coords.windows(2) {
             let (lon1, lat1) = window[0];
             let (lon2, lat2) = window[1];
             let start_id = Coord::new(lon1, lat1);
@@ -18,17 +20,42 @@ pub fn build_graph(highways: &[Vec<(f64, f64)>]) -> HashMap<Coord, Vec<Edge>> {
             graph.entry(start_id).or_insert_with(Vec::new).push(Edge {
                 target: end_id,
                 cost,
+                way: way.id,
             });
             graph.entry(end_id).or_insert_with(Vec::new).push(Edge {
                 target: start_id,
                 cost,
+                way: way.id,
             });
         }
     }
     graph
 }
 
+/// The graph with every edge turned around, for searching backwards from a goal.
+pub fn reverse_graph(graph: &HashMap<Coord, Vec<Edge>>) -> HashMap<Coord, Vec<Edge>> {
+    let mut reverse: HashMap<Coord, Vec<Edge>> = HashMap::new();
+    for (&node, edges) in graph {
+        for edge in edges {
+            reverse.entry(edge.target).or_default().push(Edge {
+                target: node,
+                cost: edge.cost,
+                way: edge.way,
+            });
+        }
+    }
+    reverse
+}
+
+/// Edge cost units per meter.
+pub const COST_PER_METER: f64 = 1e8;
+
 fn haversine_distance(lon1: f64, lat1: f64, lon2: f64, lat2: f64) -> i64 {
+    (haversine_meters(lon1, lat1, lon2, lat2) * COST_PER_METER) as i64
+}
+
+/// Great circle distance in meters.
+pub fn haversine_meters(lon1: f64, lat1: f64, lon2: f64, lat2: f64) -> f64 {
     let r = 6371e3; // Earth's radius in meters
     let phi1 = lat1.to_radians();
     let phi2 = lat2.to_radians();
@@ -38,28 +65,188 @@ fn haversine_distance(lon1: f64, lat1: f64, lon2: f64, lat2: f64) -> i64 {
     let a = (delta_phi / 2.0).sin() * (delta_phi / 2.0).sin()
         + phi1.cos() * phi2.cos() * (delta_lambda / 2.0).sin() * (delta_lambda / 2.0).sin();
     let c = 2.0 * a.sqrt().atan2((1.0 - a).sqrt());
-    ((r * c) * 1e8) as i64
+    r * c
+}
+
+/// The graph node closest to `coord`.
+pub fn nearest_node(graph: &HashMap<Coord, Vec<Edge>>, coord: Coord) -> Option<Coord> {
+    nearest_node_in(graph.keys(), coord)
+}
+
+/// The node among `nodes` closest to `coord`, e.g. to snap onto one component.
+pub fn nearest_node_in<'a>(
+    nodes: impl IntoIterator<Item = &'a Coord>,
+    coord: Coord,
+) -> Option<Coord> {
+    nodes.into_iter().copied().min_by(|a, b| {
+        let da = haversine_meters(a.lon, a.lat, coord.lon, coord.lat);
+        let db = haversine_meters(b.lon, b.lat, coord.lon, coord.lat);
+        da.total_cmp(&db)
+    })
+}
+
+/// Strongly connected components of the graph, largest first. Every node can
+/// reach every other node of its own component.
+pub fn strongly_connected_components(graph: &HashMap<Coord, Vec<Edge>>) -> Vec<Vec<Coord>> {
+    // Iterative Tarjan, since recursion would overflow the stack on large graphs
+    let mut index: HashMap<Coord, usize> = HashMap::new();
+    let mut lowlink: HashMap<Coord, usize> = HashMap::new();
+    let mut on_stack: HashSet<Coord> = HashSet::new();
+    let mut stack = Vec::new();
+    let mut components = Vec::new();
+
+    for &root in graph.keys() {
+        if index.contains_key(&root) {
+            continue;
+        }
+        let mut call_stack = vec![(root, 0)];
+        while let Some((node, position)) = call_stack.last_mut() {
+            let node = *node;
+            if *position == 0 {
+                index.insert(node, index.len());
+                lowlink.insert(node, index[&node]);
+                stack.push(node);
+                on_stack.insert(node);
+            }
+
+            let edges = graph.get(&node).map(Vec::as_slice).unwrap_or_default();
+            if let Some(edge) = edges.get(*position) {
+                *position += 1;
+                let target = edge.target;
+                if !index.contains_key(&target) {
+                    call_stack.push((target, 0));
+                } else if on_stack.contains(&target) {
+                    let low = lowlink[&node].min(index[&target]);
+                    lowlink.insert(node, low);
+                }
+                continue;
+            }
+
+            call_stack.pop();
+            if let Some(&(parent, _)) = call_stack.last() {
+                let low = lowlink[&parent].min(lowlink[&node]);
+                lowlink.insert(parent, low);
+            }
+            if lowlink[&node] == index[&node] {
+                let mut component = Vec::new();
+                while let Some(member) = stack.pop() {
+                    on_stack.remove(&member);
+                    component.push(member);
+                    if member == node {
+                        break;
+                    }
+                }
+                components.push(component);
+            }
+        }
+    }
+
+    components.sort_by_key(|component| std::cmp::Reverse(component.len()));
+    components
+}
+
+/// Nodes of the largest strongly connected component.
+pub fn largest_component(graph: &HashMap<Coord, Vec<Edge>>) -> Vec<Coord> {
+    strongly_connected_components(graph)
+        .into_iter()
+        .next()
+        .unwrap_or_default()
+}
+
+/// Removes every component with fewer than `min_size` nodes, along with the
+/// edges leading into them. Returns the number of nodes removed.
+pub fn prune_islands(graph: &mut HashMap<Coord, Vec<Edge>>, min_size: usize) -> usize {
+    let removed: HashSet<Coord> = strongly_connected_components(graph)
+        .into_iter()
+        .filter(|component| component.len() < min_size)
+        .flatten()
+        .collect();
+    graph.retain(|node, _| !removed.contains(node));
+    for edges in graph.values_mut() {
+        edges.retain(|edge| !removed.contains(&edge.target));
+    }
+    removed.len()
+}
+
+/// Total length of a path in meters.
+pub fn path_length_meters(path: &[Coord]) -> f64 {
+    path.windows(2)
+        .map(|w| haversine_meters(w[0].lon, w[0].lat, w[1].lon, w[1].lat))
+        .sum()
 }
 
+/// Finds the shortest path with A*. The search runs over (node, incoming way)
+/// states so that turn restrictions at a node can depend on the way the route
+/// arrived by.
 pub fn find_path(
     graph: &HashMap<Coord, Vec<Edge>>,
+    restrictions: &HashMap<Coord, Vec<TurnRestriction>>,
+    start: Coord,
+    goal: Coord,
+) -> Option<(Vec<Coord>, i64)> {
+    let heuristic = |node| straight_line_cost(node, goal);
+    let edge_cost = |_, edge: &Edge| edge.cost;
+    astar_path(
+        graph,
+        restrictions,
+        start,
+        goal,
+        heuristic,
+        edge_cost,
+        &Cell::new(0),
+    )
+}
+
+/// A* over (node, incoming way) states with a caller supplied heuristic, which
+/// must never overestimate the remaining cost. `edge_cost` may raise the cost
+/// of leaving a node by an edge. Each expanded state is counted in `expanded`.
+pub(crate) fn astar_path(
+    graph: &HashMap<Coord, Vec<Edge>>,
+    restrictions: &HashMap<Coord, Vec<TurnRestriction>>,
     start: Coord,
     goal: Coord,
+    heuristic: impl Fn(Coord) -> i64,
+    edge_cost: impl Fn(Coord, &Edge) -> i64,
+    expanded: &Cell<usize>,
 ) -> Option<(Vec<Coord>, i64)> {
-    astar(
-        &start,
-        |&node| {
+    let (states, cost) = astar(
+        &(start, None),
+        |&(node, incoming)| {
+            expanded.set(expanded.get() + 1);
             graph
                 .get(&node)
                 .unwrap_or(&vec![])
                 .iter()
-                .map(|edge| {
-                    let cost = edge.cost;
-                    (edge.target, cost)
-                })
+                .filter(|edge| turn_allowed(restrictions, node, incoming, edge.way))
+                .map(|edge| ((edge.target, Some(edge.way)), edge_cost(node, edge)))
                 .collect::<Vec<_>>()
         },
-        |&node| haversine_distance(node.lon, node.lat, goal.lon, goal.lat),
-        |&node| node == goal,
-    )
+        |&(node, _)| heuristic(node),
+        |&(node, _)| node == goal,
+    )?;
+    Some((states.into_iter().map(|(node, _)| node).collect(), cost))
+}
+
+/// Straight line lower bound on the cost between two nodes, in edge cost units.
+pub fn straight_line_cost(a: Coord, b: Coord) -> i64 {
+    haversine_distance(a.lon, a.lat, b.lon, b.lat)
+}
+
+/// Whether a route that reached `via` on `incoming` may continue onto `outgoing`.
+pub fn turn_allowed(
+    restrictions: &HashMap<Coord, Vec<TurnRestriction>>,
+    via: Coord,
+    incoming: Option<i64>,
+    outgoing: i64,
+) -> bool {
+    let (Some(incoming), Some(restrictions)) = (incoming, restrictions.get(&via)) else {
+        return true;
+    };
+    restrictions
+        .iter()
+        .filter(|restriction| restriction.from_way == incoming)
+        .all(|restriction| match restriction.kind {
+            RestrictionKind::No => restriction.to_way != outgoing,
+            RestrictionKind::Only => restriction.to_way == outgoing,
+        })
 }
@@ -0,0 +1,252 @@
+use osmpbfreader::Tags;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::graph::{build_graph, haversine_meters, largest_component};
+use crate::osm::{add_node, add_way, way_category};
+use crate::types::cached_data::{CachedData, WayCoords};
+use crate::types::coord::Coord;
+
+/// Overlay road ends this close to an existing road node are joined to it.
+const SNAP_METERS: f64 = 5.0;
+/// Grid cell size for finding road nodes to snap onto, about 11 m of latitude.
+const SNAP_CELL_DEGREES: f64 = 0.0001;
+
+/// Reads the points of every track and route in a GPX file, in file order.
+pub fn read_gpx(path: impl AsRef<Path>) -> Result<Vec<Coord>, Box<dyn std::error::Error>> {
+    let mut reader = Reader::from_file(path)?;
+    let mut buf = Vec::new();
+    let mut points = Vec::new();
+    loop {
+        match reader.read_event_into(&mut buf)? {
+            Event::Start(e) | Event::Empty(e)
+                if matches!(e.local_name().as_ref(), b"trkpt" | b"rtept") =>
+            {
+                points.push(Coord::new(attribute(&e, b"lon")?, attribute(&e, b"lat")?));
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    Ok(points)
+}
+
+/// Parses the value of a required attribute.
+pub(crate) fn attribute<T>(
+    element: &BytesStart,
+    name: &[u8],
+) -> Result<T, Box<dyn std::error::Error>>
+where
+    T: std::str::FromStr,
+    T::Err: std::error::Error + 'static,
+{
+    let value = element.try_get_attribute(name)?.ok_or_else(|| {
+        format!(
+            "<{}> is missing {}",
+            String::from_utf8_lossy(element.name().as_ref()),
+            String::from_utf8_lossy(name)
+        )
+    })?;
+    Ok(value.unescape_value()?.parse()?)
+}
+
+/// Reads the features of a GeoJSON file into the same categories as OSM data,
+/// using each feature's properties as its tags. A line with `highway` set
+/// becomes a road, a polygon with `building` set a building, and so on.
+/// Polygons with holes in an area layer are kept whole, other polygons by
+/// their outer ring, and features with no matching tag are skipped. Ways and
+/// points get ids counting down from -1 so they never clash with OSM ids.
+/// The routing graph is left empty.
+pub fn read_geojson(path: impl AsRef<Path>) -> Result<CachedData, Box<dyn std::error::Error>> {
+    let json: Value = serde_json::from_reader(std::io::BufReader::new(std::fs::File::open(path)?))?;
+    let features = match json["type"].as_str() {
+        Some("FeatureCollection") => json["features"]
+            .as_array()
+            .ok_or("FeatureCollection has no features")?
+            .iter()
+            .collect(),
+        Some("Feature") => vec![&json],
+        _ => return Err("GeoJSON must be a Feature or FeatureCollection".into()),
+    };
+
+    let mut data = CachedData::default();
+    let mut next_id = -1;
+    for feature in features {
+        let tags: Tags = feature["properties"]
+            .as_object()
+            .into_iter()
+            .flatten()
+            .map(|(key, value)| {
+                let value = match value {
+                    Value::String(value) => value.clone(),
+                    value => value.to_string(),
+                };
+                (key.as_str().into(), value.as_str().into())
+            })
+            .collect();
+        add_geometry(&mut data, &tags, &feature["geometry"], &mut next_id)?;
+    }
+    Ok(data)
+}
+
+fn add_geometry(
+    data: &mut CachedData,
+    tags: &Tags,
+    geometry: &Value,
+    next_id: &mut i64,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut id = || {
+        *next_id -= 1;
+        *next_id + 1
+    };
+    let coordinates = &geometry["coordinates"];
+    match geometry["type"].as_str() {
+        Some("Point") => {
+            let (lon, lat) = position(coordinates)?;
+            add_node(data, id(), tags, lon, lat);
+        }
+        Some("MultiPoint") => {
+            for point in elements(coordinates)? {
+                let (lon, lat) = position(point)?;
+                add_node(data, id(), tags, lon, lat);
+            }
+        }
+        Some("LineString") => {
+            let line = positions(coordinates)?;
+            let closed = line.len() > 2 && line.first() == line.last();
+            add_way(data, id(), tags, line, closed);
+        }
+        Some("MultiLineString") => {
+            for line in elements(coordinates)? {
+                let line = positions(line)?;
+                let closed = line.len() > 2 && line.first() == line.last();
+                add_way(data, id(), tags, line, closed);
+            }
+        }
+        Some("Polygon") => {
+            let rings = elements(coordinates)?.iter().map(positions);
+            add_polygon(data, id(), tags, rings.collect::<Result<_, _>>()?);
+        }
+        Some("MultiPolygon") => {
+            for polygon in elements(coordinates)? {
+                let rings = elements(polygon)?.iter().map(positions);
+                add_polygon(data, id(), tags, rings.collect::<Result<_, _>>()?);
+            }
+        }
+        Some("GeometryCollection") => {
+            for geometry in elements(&geometry["geometries"])? {
+                add_geometry(data, tags, geometry, next_id)?;
+            }
+        }
+        // Features without a geometry are allowed and carry nothing to draw
+        None if geometry.is_null() => {}
+        _ => return Err(format!("Unsupported geometry {}", geometry["type"]).into()),
+    }
+    Ok(())
+}
+
+/// Adds a polygon given as its outer ring followed by its holes.
+fn add_polygon(data: &mut CachedData, id: i64, tags: &Tags, rings: Vec<WayCoords>) {
+    match way_category(tags, true) {
+        Some(category) if category.is_area() && rings.len() > 1 => {
+            data.polygons.push((category, rings));
+        }
+        _ => {
+            if let Some(outer) = rings.into_iter().next() {
+                add_way(data, id, tags, outer, true);
+            }
+        }
+    }
+}
+
+pub(crate) fn elements(value: &Value) -> Result<&Vec<Value>, Box<dyn std::error::Error>> {
+    value
+        .as_array()
+        .ok_or_else(|| format!("Expected an array, found {}", value).into())
+}
+
+pub(crate) fn positions(value: &Value) -> Result<WayCoords, Box<dyn std::error::Error>> {
+    elements(value)?.iter().map(position).collect()
+}
+
+fn position(value: &Value) -> Result<(f64, f64), Box<dyn std::error::Error>> {
+    match elements(value)?.as_slice() {
+        [lon, lat, ..] => match (lon.as_f64(), lat.as_f64()) {
+            (Some(lon), Some(lat)) => Ok((lon, lat)),
+            _ => Err(format!("Expected a position, found {}", value).into()),
+        },
+        _ => Err(format!("Expected a position, found {}", value).into()),
+    }
+}
+
+/// Adds `overlay` on top of `data`. Overlay ids are moved below every id in
+/// `data`, and overlay road ends close to an existing road node are moved onto
+/// it so the two networks join. Overlay roads are added to the routing graph.
+pub fn merge_overlay(data: &mut CachedData, mut overlay: CachedData) {
+    let lowest = data
+        .highways
+        .iter()
+        .map(|highway| highway.id)
+        .chain(data.pois.iter().map(|poi| poi.id))
+        .min()
+        .unwrap_or(0)
+        .min(0);
+    let cell = |(lon, lat): (f64, f64)| {
+        (
+            (lon / SNAP_CELL_DEGREES).floor() as i64,
+            (lat / SNAP_CELL_DEGREES).floor() as i64,
+        )
+    };
+    let mut grid: HashMap<(i64, i64), Vec<(f64, f64)>> = HashMap::new();
+    for &(lon, lat) in data.highways.iter().flat_map(|highway| &highway.coords) {
+        grid.entry(cell((lon, lat))).or_default().push((lon, lat));
+    }
+    let snap = |point: &mut (f64, f64)| {
+        let (x, y) = cell(*point);
+        let nearest = (x - 1..=x + 1)
+            .flat_map(|x| (y - 1..=y + 1).map(move |y| (x, y)))
+            .filter_map(|key| grid.get(&key))
+            .flatten()
+            .map(|&node| (node, haversine_meters(point.0, point.1, node.0, node.1)))
+            .filter(|&(_, meters)| meters <= SNAP_METERS)
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+        if let Some((node, _)) = nearest {
+            *point = node;
+        }
+    };
+
+    for highway in &mut overlay.highways {
+        highway.id += lowest;
+        if let Some(first) = highway.coords.first_mut() {
+            snap(first);
+        }
+        if let Some(last) = highway.coords.last_mut() {
+            snap(last);
+        }
+    }
+    for poi in &mut overlay.pois {
+        poi.id += lowest;
+    }
+    for (node, edges) in build_graph(&overlay.highways) {
+        data.graph.entry(node).or_default().extend(edges);
+    }
+    data.component = largest_component(&data.graph);
+
+    data.highways.append(&mut overlay.highways);
+    data.waterways.append(&mut overlay.waterways);
+    data.railways.append(&mut overlay.railways);
+    data.buildings.append(&mut overlay.buildings);
+    data.naturals.append(&mut overlay.naturals);
+    data.aeroways.append(&mut overlay.aeroways);
+    data.landuse.append(&mut overlay.landuse);
+    data.water.append(&mut overlay.water);
+    data.parks.append(&mut overlay.parks);
+    data.multipolygons.append(&mut overlay.multipolygons);
+    data.polygons.append(&mut overlay.polygons);
+    data.places.append(&mut overlay.places);
+    data.pois.append(&mut overlay.pois);
+}
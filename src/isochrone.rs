@@ -0,0 +1,126 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use crate::drawing::Projection;
+use crate::graph::COST_PER_METER;
+use crate::types::{coord::Coord, edge::Edge};
+
+/// How far a search may go from its start.
+#[derive(Debug, Clone, Copy)]
+pub enum Budget {
+    Meters(f64),
+    /// Travel time at a constant speed.
+    Seconds {
+        seconds: f64,
+        speed_kmh: f64,
+    },
+}
+
+impl Budget {
+    pub fn meters(&self) -> f64 {
+        match *self {
+            Budget::Meters(meters) => meters,
+            Budget::Seconds { seconds, speed_kmh } => seconds * speed_kmh / 3.6,
+        }
+    }
+}
+
+/// Every node reachable from `origin` within a budget, with its distance in meters.
+#[derive(Debug, Clone)]
+pub struct Isochrone {
+    pub origin: Coord,
+    pub reachable: HashMap<Coord, f64>,
+}
+
+/// Runs Dijkstra from `origin`, stopping once the budget is used up. Turn
+/// restrictions are not taken into account.
+pub fn isochrone(graph: &HashMap<Coord, Vec<Edge>>, origin: Coord, budget: Budget) -> Isochrone {
+    let limit = (budget.meters() * COST_PER_METER) as i64;
+    let mut costs: HashMap<Coord, i64> = HashMap::from([(origin, 0)]);
+    let mut queue = BinaryHeap::from([Reverse((0, origin))]);
+
+    while let Some(Reverse((cost, node))) = queue.pop() {
+        if costs.get(&node).is_some_and(|&best| cost > best) {
+            continue;
+        }
+        for edge in graph.get(&node).into_iter().flatten() {
+            let next = cost + edge.cost;
+            if next > limit {
+                continue;
+            }
+            if costs.get(&edge.target).is_none_or(|&best| next < best) {
+                costs.insert(edge.target, next);
+                queue.push(Reverse((next, edge.target)));
+            }
+        }
+    }
+
+    Isochrone {
+        origin,
+        reachable: costs
+            .into_iter()
+            .map(|(node, cost)| (node, cost as f64 / COST_PER_METER))
+            .collect(),
+    }
+}
+
+impl Isochrone {
+    /// Rasterises the reachable area onto a grid of `cell_size` pixel cells.
+    /// Cells crossed by an edge between two reachable nodes, and their
+    /// neighbours, are shaded; each returned polygon is a horizontal run of
+    /// shaded cells. Runs never overlap, so filling them together with
+    /// `fill_rings` shades every pixel once.
+    pub fn area_polygons(
+        &self,
+        graph: &HashMap<Coord, Vec<Edge>>,
+        projection: &Projection,
+        cell_size: f64,
+    ) -> Vec<Vec<(f64, f64)>> {
+        let mut cells: HashSet<(i64, i64)> = HashSet::new();
+        let mut shade = |x: f64, y: f64| {
+            let (cx, cy) = (
+                (x / cell_size).floor() as i64,
+                (y / cell_size).floor() as i64,
+            );
+            for dx in -1..=1 {
+                for dy in -1..=1 {
+                    cells.insert((cx + dx, cy + dy));
+                }
+            }
+        };
+        for node in self.reachable.keys() {
+            let (x0, y0) = projection.project(node.lon, node.lat);
+            shade(x0, y0);
+            for edge in graph.get(node).into_iter().flatten() {
+                if !self.reachable.contains_key(&edge.target) {
+                    continue;
+                }
+                let (x1, y1) = projection.project(edge.target.lon, edge.target.lat);
+                let steps = ((x1 - x0).hypot(y1 - y0) / cell_size).ceil() as usize;
+                for step in 1..steps {
+                    let t = step as f64 / steps as f64;
+                    shade(x0 + (x1 - x0) * t, y0 + (y1 - y0) * t);
+                }
+            }
+        }
+
+        let mut sorted: Vec<_> = cells.into_iter().collect();
+        sorted.sort_by_key(|&(x, y)| (y, x));
+
+        let mut polygons = Vec::new();
+        let mut i = 0;
+        while i < sorted.len() {
+            let (start_x, y) = sorted[i];
+            let mut end_x = start_x;
+            while i + 1 < sorted.len() && sorted[i + 1] == (end_x + 1, y) {
+                end_x += 1;
+                i += 1;
+            }
+            let (x0, x1) = (start_x as f64 * cell_size, (end_x + 1) as f64 * cell_size);
+            let (y0, y1) = (y as f64 * cell_size, (y + 1) as f64 * cell_size);
+            polygons.push(vec![(x0, y0), (x1, y0), (x1, y1), (x0, y1)]);
+            i += 1;
+        }
+        polygons
+    }
+}
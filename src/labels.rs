@@ -0,0 +1,545 @@
+use ab_glyph::{point, Font, FontRef, GlyphId, OutlineCurve, OutlinedGlyph, PxScale, ScaleFont};
+use image::{Rgba, RgbaImage};
+use std::collections::HashMap;
+
+use crate::drawing::{plot, road_visible, Projection};
+use crate::types::cached_data::CachedData;
+
+static FONT_DATA: &[u8] = include_bytes!("../assets/DejaVuSans.ttf");
+
+const STREET_FONT_SIZE: f32 = 14.0;
+const TEXT_COLOR: Rgba<u8> = Rgba([255, 255, 255, 255]);
+const HALO_COLOR: Rgba<u8> = Rgba([0, 0, 0, 255]);
+const HALO_RADIUS: f64 = 1.5;
+// Size of the cells used to look up placed labels when checking for collisions
+const GRID_CELL: f64 = 64.0;
+// Repeated labels for the same street are only drawn if they are at least this far apart
+const MIN_REPEAT_DISTANCE: f64 = 400.0;
+// Street labels are dropped where the line bends more than this between two glyphs
+const MAX_GLYPH_ANGLE: f64 = std::f64::consts::FRAC_PI_4;
+// Straight pieces each glyph outline curve is split into for vector output
+const CURVE_STEPS: usize = 4;
+
+#[derive(Debug, Clone, Copy)]
+struct LabelBox {
+    min_x: f64,
+    min_y: f64,
+    max_x: f64,
+    max_y: f64,
+}
+
+impl LabelBox {
+    fn intersects(&self, other: &LabelBox) -> bool {
+        self.min_x < other.max_x
+            && other.min_x < self.max_x
+            && self.min_y < other.max_y
+            && other.min_y < self.max_y
+    }
+}
+
+/// A glyph positioned on the surface. `x`/`y` is the left end of its baseline.
+#[derive(Debug, Clone, Copy)]
+pub struct PlacedGlyph {
+    pub id: GlyphId,
+    pub x: f64,
+    pub y: f64,
+    pub angle: f64,
+}
+
+impl PlacedGlyph {
+    /// Outline of the glyph as closed contours in surface coordinates, with
+    /// curves split into straight pieces. Holes wind the other way, so the
+    /// contours fill with the nonzero rule.
+    pub fn contours(&self, font: &FontRef, scale: PxScale) -> Vec<Vec<(f64, f64)>> {
+        let Some(outline) = font.outline(self.id) else {
+            return Vec::new();
+        };
+        let factor = font.as_scaled(scale).scale_factor();
+        let (sin, cos) = self.angle.sin_cos();
+        // Font units grow upwards, surface coordinates downwards
+        let place = |p: ab_glyph::Point| {
+            let (u, v) = (
+                p.x as f64 * factor.horizontal as f64,
+                -p.y as f64 * factor.vertical as f64,
+            );
+            (self.x + u * cos - v * sin, self.y + u * sin + v * cos)
+        };
+
+        // Placing is affine, so the control points can be placed first
+        let mut contours: Vec<Vec<(f64, f64)>> = Vec::new();
+        for curve in &outline.curves {
+            let controls: Vec<(f64, f64)> = match *curve {
+                OutlineCurve::Line(a, b) => vec![place(a), place(b)],
+                OutlineCurve::Quad(a, b, c) => vec![place(a), place(b), place(c)],
+                OutlineCurve::Cubic(a, b, c, d) => vec![place(a), place(b), place(c), place(d)],
+            };
+            // A curve that does not continue the last one starts a new contour
+            let start = controls[0];
+            match contours.last_mut() {
+                Some(contour) if contour.last() == Some(&start) => {}
+                _ => contours.push(vec![start]),
+            }
+            let contour = contours.last_mut().unwrap();
+            if controls.len() == 2 {
+                contour.push(controls[1]);
+            } else {
+                contour.extend(
+                    (1..=CURVE_STEPS).map(|i| bezier(&controls, i as f64 / CURVE_STEPS as f64)),
+                );
+            }
+        }
+        contours
+    }
+}
+
+/// A surface labels can be drawn on.
+pub trait LabelTarget {
+    /// Width and height of the surface. Labels must fit inside.
+    fn size(&self) -> (f64, f64);
+    /// Draws a glyph of `font` at `scale`. A non zero `halo` widens the glyph
+    /// by that much, for the outline drawn behind the text.
+    fn draw_glyph(
+        &mut self,
+        font: &FontRef,
+        scale: PxScale,
+        glyph: &PlacedGlyph,
+        color: Rgba<u8>,
+        halo: f64,
+    );
+}
+
+impl LabelTarget for RgbaImage {
+    fn size(&self) -> (f64, f64) {
+        (self.width() as f64, self.height() as f64)
+    }
+
+    fn draw_glyph(
+        &mut self,
+        font: &FontRef,
+        scale: PxScale,
+        glyph: &PlacedGlyph,
+        color: Rgba<u8>,
+        halo: f64,
+    ) {
+        let outline = font.outline_glyph(glyph.id.with_scale_and_position(scale, point(0.0, 0.0)));
+        if let Some(outline) = outline {
+            draw_glyph(self, &outline, glyph, color, halo);
+        }
+    }
+}
+
+/// Draws street and place names with the bundled font, skipping any label
+/// that would overlap one that is already on the surface.
+pub struct Labeler {
+    font: FontRef<'static>,
+    /// Multiplies text sizes and distances, which are in pixels of the
+    /// raster map.
+    scale: f64,
+    placed: Vec<LabelBox>,
+    grid: HashMap<(i32, i32), Vec<usize>>,
+    placed_names: HashMap<String, Vec<(f64, f64)>>,
+}
+
+impl Default for Labeler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Labeler {
+    pub fn new() -> Self {
+        Self::with_scale(1.0)
+    }
+
+    /// A labeler for surfaces whose units differ from raster pixels, such
+    /// as PDF points.
+    pub fn with_scale(scale: f64) -> Self {
+        Labeler {
+            font: FontRef::try_from_slice(FONT_DATA).expect("Bundled font is invalid."),
+            scale,
+            placed: Vec::new(),
+            grid: HashMap::new(),
+            placed_names: HashMap::new(),
+        }
+    }
+
+    /// Labels places first, biggest first, and then streets, longest first.
+    pub fn draw_labels(
+        &mut self,
+        target: &mut impl LabelTarget,
+        data: &CachedData,
+        projection: &Projection,
+    ) {
+        let mut places: Vec<_> = data.places.iter().collect();
+        places.sort_by_key(|place| place_rank(&place.kind));
+        for place in places {
+            let (x, y) = projection.project(place.lon, place.lat);
+            let size = place_font_size(&place.kind) * self.scale as f32;
+            self.draw_point_label(target, &place.name, x, y, size);
+        }
+
+        let mut streets: Vec<_> = data
+            .highways
+            .iter()
+            .filter_map(|highway| {
+                let name = highway.name.as_deref()?;
+                if !road_visible(highway.class(), projection) {
+                    return None;
+                }
+                let line: Vec<_> = highway
+                    .coords
+                    .iter()
+                    .map(|&(lon, lat)| projection.project(lon, lat))
+                    .collect();
+                let length = line_length(&line);
+                Some((name, line, length))
+            })
+            .collect();
+        streets.sort_by(|a, b| b.2.total_cmp(&a.2));
+        for (name, line, _) in streets {
+            self.draw_line_label(target, name, &line);
+        }
+    }
+
+    /// Draws `text` centered on a point.
+    pub fn draw_point_label(
+        &mut self,
+        target: &mut impl LabelTarget,
+        text: &str,
+        x: f64,
+        y: f64,
+        size: f32,
+    ) {
+        let scale = PxScale::from(size);
+        let (glyphs, width) = self.layout(text, scale);
+        let scaled = self.font.as_scaled(scale);
+        let baseline = y + (scaled.ascent() + scaled.descent()) as f64 / 2.0;
+        let start = x - width / 2.0;
+
+        let placed: Vec<_> = glyphs
+            .iter()
+            .map(|&(id, offset, _)| PlacedGlyph {
+                id,
+                x: start + offset,
+                y: baseline,
+                angle: 0.0,
+            })
+            .collect();
+        let advances: Vec<_> = glyphs.iter().map(|&(_, _, advance)| advance).collect();
+        self.try_place(target, text, (x, y), &placed, &advances, scale);
+    }
+
+    /// Draws `text` along the middle of a projected line, following its bends.
+    pub fn draw_line_label(
+        &mut self,
+        target: &mut impl LabelTarget,
+        text: &str,
+        line: &[(f64, f64)],
+    ) {
+        if line.len() < 2 {
+            return;
+        }
+        let font_size = STREET_FONT_SIZE * self.scale as f32;
+        let scale = PxScale::from(font_size);
+        let (glyphs, width) = self.layout(text, scale);
+        let length = line_length(line);
+        if width + font_size as f64 > length {
+            return;
+        }
+
+        // Keep text reading left to right
+        let mut line = line.to_vec();
+        if line[0].0 > line[line.len() - 1].0 {
+            line.reverse();
+        }
+
+        let scaled = self.font.as_scaled(scale);
+        let center_offset = (scaled.ascent() + scaled.descent()) as f64 / 2.0;
+        let start = (length - width) / 2.0;
+
+        let mut placed = Vec::with_capacity(glyphs.len());
+        let mut previous_angle = None;
+        for &(id, offset, advance) in &glyphs {
+            let ((cx, cy), angle) = point_along(&line, start + offset + advance / 2.0);
+            if let Some(previous) = previous_angle {
+                let mut bend: f64 = angle - previous;
+                bend = bend.sin().atan2(bend.cos()).abs();
+                if bend > MAX_GLYPH_ANGLE {
+                    return;
+                }
+            }
+            previous_angle = Some(angle);
+
+            let (sin, cos) = angle.sin_cos();
+            placed.push(PlacedGlyph {
+                id,
+                x: cx - advance / 2.0 * cos - center_offset * sin,
+                y: cy - advance / 2.0 * sin + center_offset * cos,
+                angle,
+            });
+        }
+        let advances: Vec<_> = glyphs.iter().map(|&(_, _, advance)| advance).collect();
+        let anchor = point_along(&line, length / 2.0).0;
+        self.try_place(target, text, anchor, &placed, &advances, scale);
+    }
+
+    /// Lays out `text` on a straight baseline, returning each glyph with its
+    /// offset and advance, and the total width.
+    fn layout(&self, text: &str, scale: PxScale) -> (Vec<(GlyphId, f64, f64)>, f64) {
+        let scaled = self.font.as_scaled(scale);
+        let mut glyphs = Vec::new();
+        let mut caret = 0.0;
+        let mut previous: Option<GlyphId> = None;
+        for c in text.chars() {
+            let id = scaled.glyph_id(c);
+            if let Some(previous) = previous {
+                caret += scaled.kern(previous, id) as f64;
+            }
+            let advance = scaled.h_advance(id) as f64;
+            glyphs.push((id, caret, advance));
+            caret += advance;
+            previous = Some(id);
+        }
+        (glyphs, caret)
+    }
+
+    fn try_place(
+        &mut self,
+        target: &mut impl LabelTarget,
+        text: &str,
+        anchor: (f64, f64),
+        glyphs: &[PlacedGlyph],
+        advances: &[f64],
+        scale: PxScale,
+    ) {
+        if glyphs.is_empty() {
+            return;
+        }
+        if let Some(previous) = self.placed_names.get(text) {
+            let too_close = previous.iter().any(|&(x, y)| {
+                (x - anchor.0).hypot(y - anchor.1) < MIN_REPEAT_DISTANCE * self.scale
+            });
+            if too_close {
+                return;
+            }
+        }
+
+        let scaled = self.font.as_scaled(scale);
+        let (ascent, descent) = (scaled.ascent() as f64, scaled.descent() as f64);
+        let boxes: Vec<_> = glyphs
+            .iter()
+            .zip(advances)
+            .map(|(glyph, &advance)| glyph_box(glyph, advance, ascent, descent))
+            .collect();
+
+        let (width, height) = target.size();
+        let outside = boxes
+            .iter()
+            .any(|b| b.min_x < 0.0 || b.min_y < 0.0 || b.max_x >= width || b.max_y >= height);
+        if outside || boxes.iter().any(|b| self.collides(b)) {
+            return;
+        }
+
+        for glyph in glyphs {
+            target.draw_glyph(
+                &self.font,
+                scale,
+                glyph,
+                HALO_COLOR,
+                HALO_RADIUS * self.scale,
+            );
+        }
+        for glyph in glyphs {
+            target.draw_glyph(&self.font, scale, glyph, TEXT_COLOR, 0.0);
+        }
+
+        for b in boxes {
+            self.insert(b);
+        }
+        self.placed_names
+            .entry(text.to_string())
+            .or_default()
+            .push(anchor);
+    }
+
+    fn cells(b: &LabelBox) -> impl Iterator<Item = (i32, i32)> {
+        let (x0, x1) = ((b.min_x / GRID_CELL) as i32, (b.max_x / GRID_CELL) as i32);
+        let (y0, y1) = ((b.min_y / GRID_CELL) as i32, (b.max_y / GRID_CELL) as i32);
+        (x0..=x1).flat_map(move |x| (y0..=y1).map(move |y| (x, y)))
+    }
+
+    fn collides(&self, b: &LabelBox) -> bool {
+        Self::cells(b).any(|cell| {
+            self.grid
+                .get(&cell)
+                .is_some_and(|indices| indices.iter().any(|&i| self.placed[i].intersects(b)))
+        })
+    }
+
+    fn insert(&mut self, b: LabelBox) {
+        let index = self.placed.len();
+        self.placed.push(b);
+        for cell in Self::cells(&b) {
+            self.grid.entry(cell).or_default().push(index);
+        }
+    }
+}
+
+fn place_rank(kind: &str) -> u8 {
+    match kind {
+        "city" => 0,
+        "town" => 1,
+        "suburb" | "village" => 2,
+        "neighbourhood" | "quarter" | "hamlet" => 3,
+        _ => 4,
+    }
+}
+
+fn place_font_size(kind: &str) -> f32 {
+    match kind {
+        "city" => 28.0,
+        "town" => 22.0,
+        "suburb" | "village" => 18.0,
+        _ => 15.0,
+    }
+}
+
+fn line_length(line: &[(f64, f64)]) -> f64 {
+    line.windows(2)
+        .map(|w| (w[1].0 - w[0].0).hypot(w[1].1 - w[0].1))
+        .sum()
+}
+
+/// Returns the point `distance` along the line and the direction of the segment it is on.
+fn point_along(line: &[(f64, f64)], distance: f64) -> ((f64, f64), f64) {
+    let mut remaining = distance;
+    let last = line.len() - 2;
+    for (i, w) in line.windows(2).enumerate() {
+        let (dx, dy) = (w[1].0 - w[0].0, w[1].1 - w[0].1);
+        let segment = dx.hypot(dy);
+        if remaining <= segment || i == last {
+            let t = if segment > 0.0 {
+                (remaining / segment).min(1.0)
+            } else {
+                0.0
+            };
+            return ((w[0].0 + dx * t, w[0].1 + dy * t), dy.atan2(dx));
+        }
+        remaining -= segment;
+    }
+    (line[0], 0.0)
+}
+
+/// Point at `t` along a Bézier curve, by de Casteljau's construction.
+fn bezier(controls: &[(f64, f64)], t: f64) -> (f64, f64) {
+    let mut points = controls.to_vec();
+    while points.len() > 1 {
+        points = points
+            .windows(2)
+            .map(|w| {
+                (
+                    w[0].0 + (w[1].0 - w[0].0) * t,
+                    w[0].1 + (w[1].1 - w[0].1) * t,
+                )
+            })
+            .collect();
+    }
+    points[0]
+}
+
+/// Bounding box of a rotated glyph cell, from the ascent to the descent.
+fn glyph_box(glyph: &PlacedGlyph, advance: f64, ascent: f64, descent: f64) -> LabelBox {
+    let (sin, cos) = glyph.angle.sin_cos();
+    let corners = [
+        (0.0, -ascent),
+        (advance, -ascent),
+        (0.0, -descent),
+        (advance, -descent),
+    ];
+    corners.iter().fold(
+        LabelBox {
+            min_x: f64::MAX,
+            min_y: f64::MAX,
+            max_x: f64::MIN,
+            max_y: f64::MIN,
+        },
+        |b, &(u, v)| {
+            let x = glyph.x + u * cos - v * sin;
+            let y = glyph.y + u * sin + v * cos;
+            LabelBox {
+                min_x: b.min_x.min(x),
+                min_y: b.min_y.min(y),
+                max_x: b.max_x.max(x),
+                max_y: b.max_y.max(y),
+            }
+        },
+    )
+}
+
+/// Rasterises a glyph rotated by its angle. With a non zero `spread` the
+/// coverage is dilated, which is used to draw the halo behind the text.
+fn draw_glyph(
+    img: &mut RgbaImage,
+    outline: &OutlinedGlyph,
+    glyph: &PlacedGlyph,
+    color: Rgba<u8>,
+    spread: f64,
+) {
+    let bounds = outline.px_bounds();
+    let (w, h) = (bounds.width() as usize, bounds.height() as usize);
+    let mut coverage = vec![0.0f32; w * h];
+    outline.draw(|x, y, c| {
+        if (x as usize) < w && (y as usize) < h {
+            coverage[y as usize * w + x as usize] = c;
+        }
+    });
+
+    let sample = |u: f64, v: f64| -> f32 {
+        let (gx, gy) = (u - bounds.min.x as f64, v - bounds.min.y as f64);
+        if gx < 0.0 || gy < 0.0 || gx >= w as f64 || gy >= h as f64 {
+            return 0.0;
+        }
+        coverage[gy as usize * w + gx as usize]
+    };
+
+    let (sin, cos) = glyph.angle.sin_cos();
+    let margin = spread.ceil() + 1.0;
+    let corners = [
+        (bounds.min.x as f64, bounds.min.y as f64),
+        (bounds.max.x as f64, bounds.min.y as f64),
+        (bounds.min.x as f64, bounds.max.y as f64),
+        (bounds.max.x as f64, bounds.max.y as f64),
+    ];
+    let xs = corners.iter().map(|&(u, v)| glyph.x + u * cos - v * sin);
+    let ys = corners.iter().map(|&(u, v)| glyph.y + u * sin + v * cos);
+    let (min_x, max_x) = xs.fold((f64::MAX, f64::MIN), |(lo, hi), x| (lo.min(x), hi.max(x)));
+    let (min_y, max_y) = ys.fold((f64::MAX, f64::MIN), |(lo, hi), y| (lo.min(y), hi.max(y)));
+
+    let offsets: Vec<(f64, f64)> = if spread > 0.0 {
+        (0..8)
+            .map(|i| {
+                let a = i as f64 * std::f64::consts::FRAC_PI_4;
+                (a.cos() * spread, a.sin() * spread)
+            })
+            .chain(std::iter::once((0.0, 0.0)))
+            .collect()
+    } else {
+        vec![(0.0, 0.0)]
+    };
+
+    for y in (min_y - margin) as i32..=(max_y + margin) as i32 {
+        for x in (min_x - margin) as i32..=(max_x + margin) as i32 {
+            // Map the pixel center back into the unrotated glyph
+            let (dx, dy) = (x as f64 + 0.5 - glyph.x, y as f64 + 0.5 - glyph.y);
+            let u = dx * cos + dy * sin;
+            let v = -dx * sin + dy * cos;
+            let alpha = offsets
+                .iter()
+                .map(|&(ou, ov)| sample(u + ou, v + ov))
+                .fold(0.0f32, f32::max);
+            if alpha > 0.0 {
+                plot(img, x, y, color, alpha.min(1.0));
+            }
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use serde::{Deserialize, Serialize};
+
+use crate::graph::largest_component;
+use crate::types::{coord::Coord, edge::Edge};
+
+pub const DEFAULT_LANDMARKS: usize = 16;
+
+const UNREACHABLE: i64 = i64::MAX;
+
+/// Precomputed costs to and from a few landmark nodes. By the triangle
+/// inequality these give a lower bound on the cost between any two nodes,
+/// which is usually much tighter than the straight line distance.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Landmarks {
+    pub nodes: Vec<Coord>,
+    /// Column of each graph node in the cost tables.
+    index: HashMap<Coord, usize>,
+    /// `from[l][i]` is the cost from landmark `l` to node `i`.
+    from: Vec<Vec<i64>>,
+    /// `to[l][i]` is the cost from node `i` to landmark `l`.
+    to: Vec<Vec<i64>>,
+    /// Hash of every edge's target and cost, see [`edge_hash`].
+    edges: u64,
+}
+
+impl Landmarks {
+    /// Picks `count` landmarks spread around the edge of the largest component,
+    /// each one as far as possible from those already chosen. `reverse` is
+    /// `graph` with every edge turned around.
+    pub fn select(
+        graph: &HashMap<Coord, Vec<Edge>>,
+        reverse: &HashMap<Coord, Vec<Edge>>,
+        count: usize,
+    ) -> Self {
+        let mut columns: Vec<Coord> = graph.keys().copied().collect();
+        columns.sort();
+        let index: HashMap<Coord, usize> = columns
+            .iter()
+            .enumerate()
+            .map(|(i, &node)| (node, i))
+            .collect();
+        let table = |costs: HashMap<Coord, i64>| {
+            columns
+                .iter()
+                .map(|node| costs.get(node).copied().unwrap_or(UNREACHABLE))
+                .collect::<Vec<_>>()
+        };
+        let farthest = |costs: &[i64]| {
+            (0..costs.len())
+                .filter(|&i| costs[i] != UNREACHABLE)
+                .max_by_key(|&i| costs[i])
+        };
+
+        let mut nodes = Vec::new();
+        let mut from = Vec::new();
+        if let Some(seed) = largest_component(graph).into_iter().min() {
+            // Cost from the nearest landmark chosen so far. Starting from an
+            // arbitrary node puts the first landmark on the edge of the graph.
+            let mut closest = table(dijkstra(graph, seed));
+            while nodes.len() < count {
+                let Some(landmark) = farthest(&closest).map(|i| columns[i]) else {
+                    break;
+                };
+                if nodes.contains(&landmark) {
+                    break;
+                }
+                let costs = table(dijkstra(graph, landmark));
+                if nodes.is_empty() {
+                    closest.clone_from(&costs);
+                } else {
+                    for (closest, &cost) in closest.iter_mut().zip(&costs) {
+                        *closest = (*closest).min(cost);
+                    }
+                }
+                nodes.push(landmark);
+                from.push(costs);
+            }
+        }
+        let to = nodes
+            .par_iter()
+            .map(|&landmark| table(dijkstra(reverse, landmark)))
+            .collect();
+
+        Landmarks {
+            nodes,
+            index,
+            from,
+            to,
+            edges: edge_hash(graph),
+        }
+    }
+
+    /// Whether the tables were computed for this graph. Changed edges matter
+    /// as much as changed nodes, since a cheaper edge breaks the bounds.
+    pub fn matches(&self, graph: &HashMap<Coord, Vec<Edge>>) -> bool {
+        self.index.len() == graph.len()
+            && graph.keys().all(|node| self.index.contains_key(node))
+            && self.edges == edge_hash(graph)
+    }
+
+    /// A cost that the cheapest path from `a` to `b` is guaranteed to reach.
+    pub fn lower_bound(&self, a: Coord, b: Coord) -> i64 {
+        let (Some(&a), Some(&b)) = (self.index.get(&a), self.index.get(&b)) else {
+            return 0;
+        };
+        let bound = |x: i64, y: i64| {
+            if x == UNREACHABLE || y == UNREACHABLE {
+                0
+            } else {
+                x - y
+            }
+        };
+        self.from
+            .iter()
+            .zip(&self.to)
+            .map(|(from, to)| bound(from[b], from[a]).max(bound(to[a], to[b])))
+            .max()
+            .unwrap_or(0)
+            .max(0)
+    }
+}
+
+/// Hash of the target and cost of every edge, independent of the order the
+/// graph is stored in.
+fn edge_hash(graph: &HashMap<Coord, Vec<Edge>>) -> u64 {
+    let mut nodes: Vec<&Coord> = graph.keys().collect();
+    nodes.sort();
+    let mut hasher = DefaultHasher::new();
+    for node in nodes {
+        let mut edges: Vec<(Coord, i64)> = graph[node]
+            .iter()
+            .map(|edge| (edge.target, edge.cost))
+            .collect();
+        edges.sort();
+        node.hash(&mut hasher);
+        edges.hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+/// Costs from `origin` to every node it can reach.
+fn dijkstra(graph: &HashMap<Coord, Vec<Edge>>, origin: Coord) -> HashMap<Coord, i64> {
+    let mut costs: HashMap<Coord, i64> = HashMap::from([(origin, 0)]);
+    let mut queue = BinaryHeap::from([Reverse((0, origin))]);
+    while let Some(Reverse((cost, node))) = queue.pop() {
+        if costs.get(&node).is_some_and(|&best| cost > best) {
+            continue;
+        }
+        for edge in graph.get(&node).into_iter().flatten() {
+            let next = cost + edge.cost;
+            if costs.get(&edge.target).is_none_or(|&best| next < best) {
+                costs.insert(edge.target, next);
+                queue.push(Reverse((next, edge.target)));
+            }
+        }
+    }
+    costs
+}
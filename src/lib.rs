@@ -1,6 +1,29 @@
+pub mod alternatives;
 pub mod cache;
+pub mod clip;
+pub mod dem;
 pub mod drawing;
+pub mod export;
+pub mod flatgeobuf;
+pub mod georef;
 pub mod graph;
+pub mod import;
+pub mod isochrone;
+pub mod labels;
+pub mod landmarks;
+pub mod matching;
+pub mod matrix;
+pub mod multipolygon;
+pub mod mvt;
+pub mod navigation;
 pub mod osm;
+pub mod osm_xml;
+pub mod pdf;
+pub mod poi;
+pub mod routing;
+pub mod server;
+pub mod simplify;
 pub mod types;
+pub mod update;
 pub mod utils;
+pub mod waypoints;
@@ -1,17 +1,36 @@
 extern crate osmpbfreader;
 extern crate rand;
 
+use maps::alternatives::{alternative_routes, similarity};
 use maps::cache::{load_cache, save_cache};
-use maps::drawing::draw_map;
-use maps::graph::find_path;
+use maps::clip::Clip;
+use maps::dem::{add_climb_costs, Dem};
+use maps::drawing::{draw_map, RenderOptions};
+use maps::export::{export_features, export_route};
+use maps::graph::{
+    find_path, largest_component, nearest_node_in, path_length_meters, prune_islands,
+    reverse_graph, strongly_connected_components, COST_PER_METER,
+};
+use maps::import::{merge_overlay, read_geojson, read_gpx};
+use maps::isochrone::{isochrone, Budget};
+use maps::landmarks::{Landmarks, DEFAULT_LANDMARKS};
+use maps::matching::{match_track, EdgeIndex};
+use maps::matrix::distance_matrix;
+use maps::mvt::write_vector_tiles;
+use maps::navigation::instructions;
 use maps::osm::read_osm_data;
+use maps::osm_xml::OsmXmlReader;
+use maps::pdf::{parse_scale, write_pdf, PaperSize};
+use maps::poi::pois_in_bbox;
+use maps::routing::{Router, Strategy};
+use maps::server::serve;
 use maps::types::cached_data::CachedData;
-use maps::types::cached_data::WayCoords;
 use maps::types::coord::Coord;
-use maps::types::edge::Edge;
-use maps::utils::get_random_node;
-use std::collections::HashMap;
-use std::ffi::OsStr;
+use maps::update::apply_changes;
+use maps::utils::{get_random_node, parse_bbox, parse_coord};
+use maps::waypoints::{optimise_order, route_via};
+use std::ffi::{OsStr, OsString};
+use std::sync::Arc;
 use std::time::Instant;
 
 fn main() {
@@ -19,18 +38,155 @@ fn main() {
     std::env::set_var("RUST_BACKTRACE", "1");
     let start_time = Instant::now();
 
-    let args: Vec<_> = std::env::args_os().collect();
-    if args.len() != 2 {
+    let (flags, args): (Vec<_>, Vec<_>) =
+        std::env::args_os().partition(|arg| arg.to_str().is_some_and(|arg| arg.starts_with("--")));
+    let dem = match flag_value(&flags, "--dem=").map(Dem::open) {
+        Some(Ok(dem)) => Some(Arc::new(dem)),
+        Some(Err(e)) => {
+            println!("Failed to read elevation data: {}", e);
+            return;
+        }
+        None => None,
+    };
+    let mut options = RenderOptions {
+        labels: !flags.iter().any(|flag| flag == "--no-labels"),
+        dem,
+        geotiff: flags.iter().any(|flag| flag == "--geotiff"),
+        ..Default::default()
+    };
+    match flag_value(&flags, "--contours=").map(str::parse::<f64>) {
+        Some(Ok(interval)) if interval > 0.0 => options.contour_interval = interval,
+        Some(_) => {
+            println!("--contours must be a height in meters.");
+            return;
+        }
+        None => {}
+    }
+    let avoid_climbs = flags.iter().any(|flag| flag == "--avoid-climbs");
+    if avoid_climbs && options.dem.is_none() {
+        println!("--avoid-climbs needs elevation data from --dem.");
+        return;
+    }
+    let strategy = match flag_value(&flags, "--strategy=").map(str::parse::<Strategy>) {
+        Some(Ok(strategy)) => strategy,
+        Some(Err(e)) => {
+            println!("{}", e);
+            return;
+        }
+        None => Strategy::AStar,
+    };
+    let Some(alternatives) =
+        flag_value(&flags, "--alternatives=").map_or(Some(0), |k| k.parse().ok())
+    else {
+        println!("--alternatives must be a number of routes.");
         return;
+    };
+
+    let overlays: Vec<&str> = flags
+        .iter()
+        .filter_map(|flag| flag.to_str()?.strip_prefix("--overlay="))
+        .collect();
+
+    match args.get(1).and_then(|arg| arg.to_str()) {
+        Some("pdf") if args.len() == 5 => {
+            run_pdf(&args[2], &overlays, &args[3], &args[4], &options)
+        }
+        Some("pois") if args.len() == 4 || args.len() == 5 => {
+            run_pois(&args[2], &args[3], args.get(4).map(|bbox| bbox.as_os_str()))
+        }
+        Some("isochrone") if args.len() == 5 => {
+            run_isochrone(&args[2], &overlays, &args[3], &args[4], options)
+        }
+        Some("matrix") if args.len() == 5 => run_matrix(&args[2], &overlays, &args[3], &args[4]),
+        Some("components") if args.len() == 3 || args.len() == 4 => {
+            run_components(&args[2], args.get(3).map(|size| size.as_os_str()))
+        }
+        Some("clip") if args.len() == 5 => run_clip(&args[2], &args[3], &args[4]),
+        Some("export") if args.len() == 4 || args.len() == 5 => {
+            run_export(&args[2], &args[3], args.get(4).map(|zoom| zoom.as_os_str()))
+        }
+        Some("mvt") if args.len() == 4 || args.len() == 5 => run_mvt(
+            &args[2],
+            &args[3],
+            args.get(4).map(|zooms| zooms.as_os_str()),
+        ),
+        Some("update") if args.len() == 4 => run_update(&args[2], &args[3]),
+        Some("route") if args.len() == 4 => run_route(
+            &args[2],
+            &overlays,
+            &args[3],
+            flags.iter().any(|flag| flag == "--optimise"),
+            flags.iter().any(|flag| flag == "--round-trip"),
+            options.dem.as_deref().filter(|_| avoid_climbs),
+            &options,
+        ),
+        Some("match") if args.len() == 4 => run_match(&args[2], &args[3], &options),
+        Some("serve") if args.len() == 3 || args.len() == 4 => {
+            let addr = args.get(3).and_then(|addr| addr.to_str());
+            run_serve(
+                &args[2],
+                &overlays,
+                addr.unwrap_or("127.0.0.1:8080"),
+                options,
+            )
+        }
+        Some("bench") if args.len() == 3 || args.len() == 4 => {
+            run_bench(&args[2], args.get(3).map(|count| count.as_os_str()))
+        }
+        _ if args.len() == 2 => run_map(&args[1], &overlays, options, strategy, alternatives),
+        _ => return,
     }
-    let filename = &args[1];
 
+    let total_duration = start_time.elapsed();
+    println!("Total execution time: {:?}", total_duration);
+}
+
+/// Value of a `--name=value` flag, with `prefix` being `--name=`.
+fn flag_value<'a>(flags: &'a [OsString], prefix: &str) -> Option<&'a str> {
+    flags
+        .iter()
+        .find_map(|flag| flag.to_str()?.strip_prefix(prefix))
+}
+
+fn run_map(
+    filename: &OsStr,
+    overlays: &[&str],
+    mut options: RenderOptions,
+    strategy: Strategy,
+    alternatives: usize,
+) {
     // Check if cache exists
-    let (_nodes, highways, waterways, railways, buildings, naturals, aeroways, multipolygons, graph) =
-        load_or_parse_data(filename);
+    let data = load_with_overlays(filename, overlays);
+    let landmarks = (strategy == Strategy::Alt)
+        .then(|| load_or_build_landmarks(filename, &data, overlays.is_empty()));
 
     // Run A* search
-    let path_result = run_a_star(&highways, graph);
+    let path_result = run_a_star(&data, strategy, landmarks.as_ref());
+    if !path_result.is_empty() {
+        export_route(&path_result, "route").expect("Failed to export route.");
+        println!(
+            "Route exported to route.gpx and route.geojson ({:.0} m)",
+            path_length_meters(&path_result)
+        );
+        for instruction in instructions(&path_result, &data.graph, &data.highways) {
+            println!("{}", instruction.text());
+        }
+    }
+    if alternatives > 0 && !path_result.is_empty() {
+        let routes =
+            alternative_routes(&data.graph, &data.restrictions, &path_result, alternatives);
+        for (path, cost) in &routes {
+            println!(
+                "Alternative with cost {} ({:.0}% shared with the main route)",
+                cost,
+                similarity(path, &path_result) * 100.0
+            );
+        }
+        options.alternatives = routes
+            .into_iter()
+            .map(|(path, _)| path.iter().map(|coord| (coord.lon, coord.lat)).collect())
+            .collect();
+    }
     let path_result_f64 = path_result
         .iter()
         .map(|&coord| (coord.lon, coord.lat))
@@ -38,81 +194,571 @@ fn main() {
 
     // Draw map
     let draw_start_time = Instant::now();
-    draw_map(
-        &highways,
-        &waterways,
-        &railways,
-        &buildings,
-        &naturals,
-        &aeroways,
-        &multipolygons,
-        &path_result_f64,
-    );
+    draw_map(&data, &path_result_f64, &options);
     let draw_duration = draw_start_time.elapsed();
     println!("Map drawn in {:?}", draw_duration);
+}
 
-    let total_duration = start_time.elapsed();
-    println!("Total execution time: {:?}", total_duration);
+fn run_pdf(
+    filename: &OsStr,
+    overlays: &[&str],
+    paper: &OsStr,
+    scale: &OsStr,
+    options: &RenderOptions,
+) {
+    let paper: PaperSize = match paper.to_str().unwrap_or_default().parse() {
+        Ok(paper) => paper,
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
+    let Some(scale) = scale.to_str().and_then(parse_scale) else {
+        println!("Scale must look like 1:10000.");
+        return;
+    };
+
+    let data = load_with_overlays(filename, overlays);
+
+    let draw_start_time = Instant::now();
+    write_pdf(&data, &[], options, paper, scale, "map.pdf").expect("Failed to write PDF.");
+    println!("PDF drawn in {:?}", draw_start_time.elapsed());
+}
+
+/// Prints the POIs matching `key=value` (or just `key`) inside an optional
+/// `min_lon,min_lat,max_lon,max_lat` bounding box.
+fn run_pois(filename: &OsStr, tag: &OsStr, bbox: Option<&OsStr>) {
+    let tag = tag.to_str().unwrap_or_default();
+    let (key, value) = match tag.split_once('=') {
+        Some((key, value)) => (key, Some(value)),
+        None => (tag, None),
+    };
+    let bbox = match bbox.map(|bbox| bbox.to_str().and_then(parse_bbox)) {
+        Some(Some(bbox)) => bbox,
+        Some(None) => {
+            println!("Bounding box must look like min_lon,min_lat,max_lon,max_lat.");
+            return;
+        }
+        None => (f64::MIN, f64::MIN, f64::MAX, f64::MAX),
+    };
+
+    let data = load_or_parse_data(filename);
+    let pois = pois_in_bbox(&data.pois, key, value, bbox);
+    for poi in &pois {
+        let kinds: Vec<String> = poi
+            .kinds
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect();
+        println!(
+            "{} {} {} ({}, {})",
+            poi.id,
+            kinds.join(";"),
+            poi.name.as_deref().unwrap_or("-"),
+            poi.lon,
+            poi.lat
+        );
+    }
+    println!("{} POIs found.", pois.len());
+}
+
+/// Shades everything reachable from a `lon,lat` point within a budget given
+/// in meters (`2000m`) or minutes of driving at 30 km/h (`10min`).
+fn run_isochrone(
+    filename: &OsStr,
+    overlays: &[&str],
+    origin: &OsStr,
+    budget: &OsStr,
+    mut options: RenderOptions,
+) {
+    let Some(origin) = origin.to_str().and_then(parse_coord) else {
+        println!("Origin must look like lon,lat.");
+        return;
+    };
+    let budget = budget.to_str().unwrap_or_default();
+    let budget = if let Some(minutes) = budget.strip_suffix("min") {
+        minutes.parse().ok().map(|minutes: f64| Budget::Seconds {
+            seconds: minutes * 60.0,
+            speed_kmh: 30.0,
+        })
+    } else {
+        budget
+            .trim_end_matches('m')
+            .parse()
+            .ok()
+            .map(Budget::Meters)
+    };
+    let Some(budget) = budget else {
+        println!("Budget must look like 2000m or 10min.");
+        return;
+    };
+
+    let data = load_with_overlays(filename, overlays);
+    let Some(start) = nearest_node_in(&data.component, origin) else {
+        println!("Graph is empty.");
+        return;
+    };
+
+    let search_start_time = Instant::now();
+    let reachable = isochrone(&data.graph, start, budget);
+    println!(
+        "{} nodes reachable within {:.0} m, found in {:?}",
+        reachable.reachable.len(),
+        budget.meters(),
+        search_start_time.elapsed()
+    );
+
+    options.isochrone = Some(reachable);
+    let draw_start_time = Instant::now();
+    draw_map(&data, &[], &options);
+    println!("Map drawn in {:?}", draw_start_time.elapsed());
+}
+
+/// Prints the travel distance in meters between each origin and destination,
+/// both given as `lon,lat;lon,lat;...` lists.
+fn run_matrix(filename: &OsStr, overlays: &[&str], origins: &OsStr, destinations: &OsStr) {
+    let (Some(origins), Some(destinations)) =
+        (parse_coord_list(origins), parse_coord_list(destinations))
+    else {
+        println!("Points must look like lon,lat;lon,lat.");
+        return;
+    };
+
+    let data = load_with_overlays(filename, overlays);
+    let (Some(origins), Some(destinations)) = (
+        snap_all(&data.component, origins),
+        snap_all(&data.component, destinations),
+    ) else {
+        println!("Graph is empty.");
+        return;
+    };
+
+    let matrix_start_time = Instant::now();
+    let matrix = distance_matrix(&data.graph, &data.restrictions, &origins, &destinations);
+    println!(
+        "{}x{} matrix computed in {:?}",
+        origins.len(),
+        destinations.len(),
+        matrix_start_time.elapsed()
+    );
+    for row in matrix {
+        let row: Vec<String> = row
+            .iter()
+            .map(|cost| match cost {
+                Some(cost) => format!("{:.0}", *cost as f64 / COST_PER_METER),
+                None => "-".to_string(),
+            })
+            .collect();
+        println!("{}", row.join("\t"));
+    }
+}
+
+/// Parses only the part of the file inside a bounding box or GeoJSON polygon
+/// and caches it as `<name>.cache`, so later commands can be run on `<name>`.
+fn run_clip(filename: &OsStr, clip: &OsStr, name: &OsStr) {
+    let clip = match Clip::parse(&clip.to_string_lossy()) {
+        Ok(clip) => clip,
+        Err(e) => {
+            println!("Failed to read clip: {}", e);
+            return;
+        }
+    };
+
+    let parse_start_time = Instant::now();
+    let mut data = read_osm_data(filename, Some(&clip));
+    data.graph = maps::graph::build_graph(&data.highways);
+    data.component = largest_component(&data.graph);
+    println!(
+        "Kept {} nodes, {} ways and {} roads in {:?}",
+        data.nodes.len(),
+        data.ways.len(),
+        data.highways.len(),
+        parse_start_time.elapsed()
+    );
+
+    let cache_filename = format!("{}.cache", name.to_str().unwrap());
+    save_cache(OsStr::new(&cache_filename), &data).expect("Failed to save cache.");
+    println!("Saved {}", cache_filename);
+}
+
+/// Writes the extracted features to GeoJSON or FlatGeobuf, simplified for a
+/// zoom level if one is given.
+fn run_export(filename: &OsStr, output: &OsStr, zoom: Option<&OsStr>) {
+    let zoom = match zoom.map(|zoom| zoom.to_str().and_then(|zoom| zoom.parse::<u32>().ok())) {
+        Some(Some(zoom)) if zoom <= 24 => Some(zoom),
+        Some(_) => {
+            println!("Zoom level must be a number from 0 to 24");
+            return;
+        }
+        None => None,
+    };
+    let data = load_or_parse_data(filename);
+    let export_start_time = Instant::now();
+    match export_features(&data, output, zoom) {
+        Ok(count) => println!(
+            "Exported {} features to {} in {:?}",
+            count,
+            output.to_string_lossy(),
+            export_start_time.elapsed()
+        ),
+        Err(e) => println!("Failed to export features: {}", e),
+    }
+}
+
+/// Writes vector tiles for a `min-max` zoom range, 0-14 by default.
+fn run_mvt(filename: &OsStr, dir: &OsStr, zooms: Option<&OsStr>) {
+    let zooms = match zooms {
+        Some(zooms) => {
+            let range = zooms.to_str().and_then(|zooms| {
+                let (min, max) = zooms.split_once('-')?;
+                Some(min.parse::<u32>().ok()?..=max.parse::<u32>().ok()?)
+            });
+            match range {
+                Some(range) if !range.is_empty() && *range.end() <= 24 => range,
+                _ => {
+                    println!("Zoom levels must be a range such as 0-14");
+                    return;
+                }
+            }
+        }
+        None => 0..=14,
+    };
+    let data = load_or_parse_data(filename);
+    let tiles_start_time = Instant::now();
+    match write_vector_tiles(&data, zooms, dir) {
+        Ok(count) => println!(
+            "Wrote {} tiles to {} in {:?}",
+            count,
+            dir.to_string_lossy(),
+            tiles_start_time.elapsed()
+        ),
+        Err(e) => println!("Failed to write vector tiles: {}", e),
+    }
+}
+
+/// Applies an `.osc` change file to the cache and rewrites it.
+fn run_update(filename: &OsStr, changes: &OsStr) {
+    let mut data = load_or_parse_data(filename);
+
+    let update_start_time = Instant::now();
+    let changes: Result<Vec<_>, _> =
+        OsmXmlReader::from_file(changes).and_then(|reader| reader.changes().collect());
+    let changes = match changes {
+        Ok(changes) => changes,
+        Err(e) => {
+            println!("Failed to read changes: {}", e);
+            return;
+        }
+    };
+    let stats = apply_changes(&mut data, changes);
+    println!(
+        "Applied {} node and {} way changes, rebuilding {} ways, in {:?}",
+        stats.nodes,
+        stats.ways,
+        stats.rebuilt_ways,
+        update_start_time.elapsed()
+    );
+    if stats.skipped_relations > 0 {
+        println!(
+            "Skipped {} relation changes, re-import to pick them up.",
+            stats.skipped_relations
+        );
+    }
+
+    let cache_filename = format!("{}.cache", filename.to_str().unwrap());
+    save_cache(OsStr::new(&cache_filename), &data).expect("Failed to save cache.");
+    remove_landmarks(filename);
 }
 
-fn load_or_parse_data(
+/// Reports the connected components of the road graph. With a minimum size,
+/// components smaller than it are pruned and the cache is rewritten.
+fn run_components(filename: &OsStr, min_size: Option<&OsStr>) {
+    let min_size = match min_size.map(|size| size.to_str().and_then(|size| size.parse().ok())) {
+        Some(Some(min_size)) => Some(min_size),
+        Some(None) => {
+            println!("Minimum size must be a number of nodes.");
+            return;
+        }
+        None => None,
+    };
+
+    let mut data = load_or_parse_data(filename);
+    let components = strongly_connected_components(&data.graph);
+    println!(
+        "{} components, the largest has {} of {} nodes.",
+        components.len(),
+        components.first().map_or(0, Vec::len),
+        data.graph.len()
+    );
+    for size in [2, 10, 100] {
+        let count = components
+            .iter()
+            .filter(|component| component.len() < size)
+            .count();
+        println!("{} components with fewer than {} nodes", count, size);
+    }
+
+    if let Some(min_size) = min_size {
+        let removed = prune_islands(&mut data.graph, min_size);
+        data.component = largest_component(&data.graph);
+        data.min_component_size = Some(min_size);
+        println!(
+            "Pruned {} nodes in components under {} nodes.",
+            removed, min_size
+        );
+        let cache_filename = format!("{}.cache", filename.to_str().unwrap());
+        save_cache(OsStr::new(&cache_filename), &data).expect("Failed to save cache.");
+        remove_landmarks(filename);
+    }
+}
+
+/// Times every search strategy on the same random queries within the largest
+/// component, against plain `find_path`.
+fn run_bench(filename: &OsStr, count: Option<&OsStr>) {
+    let Some(count) = count.map_or(Some(20), |count| count.to_str()?.parse().ok()) else {
+        println!("Query count must be a number.");
+        return;
+    };
+
+    let data = load_or_parse_data(filename);
+    let landmarks = load_or_build_landmarks(filename, &data, true);
+    let router = Router::new(&data.graph, &data.restrictions, Some(&landmarks));
+    let queries: Vec<(Coord, Coord)> = (0..count)
+        .map(|_| {
+            (
+                get_random_node(&data.component),
+                get_random_node(&data.component),
+            )
+        })
+        .collect();
+
+    let bench_start_time = Instant::now();
+    let expected: Vec<Option<i64>> = queries
+        .iter()
+        .map(|&(start, goal)| {
+            find_path(&data.graph, &data.restrictions, start, goal).map(|(_, cost)| cost)
+        })
+        .collect();
+    println!(
+        "{:<14} {:>12?}",
+        "find_path",
+        bench_start_time.elapsed() / count.max(1) as u32
+    );
+
+    for strategy in Strategy::ALL {
+        let bench_start_time = Instant::now();
+        let mut expanded = 0;
+        let mut mismatches = 0;
+        for (&(start, goal), &expected) in queries.iter().zip(&expected) {
+            let (path, states) = router.find_path(start, goal, strategy);
+            expanded += states;
+            if path.map(|(_, cost)| cost) != expected {
+                mismatches += 1;
+            }
+        }
+        println!(
+            "{:<14} {:>12?} {:>10} states/query {} mismatches",
+            format!("{:?}", strategy),
+            bench_start_time.elapsed() / count.max(1) as u32,
+            expanded / count.max(1),
+            mismatches
+        );
+    }
+}
+
+/// Landmarks for ALT searches, cached next to the data cache and rebuilt when
+/// the graph has changed since. Without `cached`, as for a graph with
+/// overlays, they are computed afresh and the cache is left alone.
+fn load_or_build_landmarks(filename: &OsStr, data: &CachedData, cached: bool) -> Landmarks {
+    let landmarks_filename = format!("{}.landmarks", filename.to_str().unwrap());
+    if cached {
+        if let Ok(landmarks) = load_cache::<Landmarks>(&landmarks_filename) {
+            if landmarks.matches(&data.graph) {
+                return landmarks;
+            }
+        }
+    }
+
+    let landmarks_start_time = Instant::now();
+    let reverse = reverse_graph(&data.graph);
+    let landmarks = Landmarks::select(&data.graph, &reverse, DEFAULT_LANDMARKS);
+    println!(
+        "{} landmarks computed in {:?}",
+        landmarks.nodes.len(),
+        landmarks_start_time.elapsed()
+    );
+    if cached {
+        save_cache(OsStr::new(&landmarks_filename), &landmarks).expect("Failed to save landmarks.");
+    }
+    landmarks
+}
+
+/// Deletes the cached landmarks of a graph that has changed, so they are
+/// rebuilt when next needed.
+fn remove_landmarks(filename: &OsStr) {
+    let landmarks_filename = format!("{}.landmarks", filename.to_str().unwrap());
+    match std::fs::remove_file(&landmarks_filename) {
+        Ok(()) => println!(
+            "Removed {}, landmarks are rebuilt when needed.",
+            landmarks_filename
+        ),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => println!("Failed to remove {}: {}", landmarks_filename, e),
+    }
+}
+
+/// Routes through `lon,lat;lon,lat;...` waypoints in order, or in the
+/// shortest order found when optimising. A round trip returns to the first.
+/// Given an elevation model in `climbs`, going uphill costs extra.
+fn run_route(
     filename: &OsStr,
-) -> (
-    HashMap<i64, (f64, f64)>,
-    Vec<WayCoords>,
-    Vec<WayCoords>,
-    Vec<WayCoords>,
-    Vec<WayCoords>,
-    Vec<WayCoords>,
-    Vec<WayCoords>,
-    Vec<Vec<WayCoords>>,
-    HashMap<Coord, Vec<Edge>>,
+    overlays: &[&str],
+    waypoints: &OsStr,
+    optimise: bool,
+    round_trip: bool,
+    climbs: Option<&Dem>,
+    options: &RenderOptions,
 ) {
+    let Some(waypoints) = parse_coord_list(waypoints).filter(|waypoints| waypoints.len() >= 2)
+    else {
+        println!("Waypoints must look like lon,lat;lon,lat.");
+        return;
+    };
+
+    let mut data = load_with_overlays(filename, overlays);
+    if let Some(dem) = climbs {
+        let climbs_start_time = Instant::now();
+        let count = add_climb_costs(&mut data.graph, dem);
+        println!(
+            "Climbing costs added to {} edges in {:?}",
+            count,
+            climbs_start_time.elapsed()
+        );
+    }
+    let Some(mut waypoints) = snap_all(&data.component, waypoints) else {
+        println!("Graph is empty.");
+        return;
+    };
+    if optimise {
+        let optimise_start_time = Instant::now();
+        waypoints = optimise_order(&data.graph, &data.restrictions, &waypoints, round_trip);
+        println!(
+            "Waypoint order optimised in {:?}",
+            optimise_start_time.elapsed()
+        );
+    } else if round_trip {
+        waypoints.push(waypoints[0]);
+    }
+    for (i, waypoint) in waypoints.iter().enumerate() {
+        println!("Waypoint {}: {}, {}", i + 1, waypoint.lon, waypoint.lat);
+    }
+
+    let Some((path, cost)) = route_via(&data.graph, &data.restrictions, &waypoints) else {
+        println!("No path found.");
+        return;
+    };
+    println!("Path found with cost {}", cost);
+    export_route(&path, "route").expect("Failed to export route.");
+    println!(
+        "Route exported to route.gpx and route.geojson ({:.0} m)",
+        path_length_meters(&path)
+    );
+    for instruction in instructions(&path, &data.graph, &data.highways) {
+        println!("{}", instruction.text());
+    }
+
+    let path: Vec<_> = path.iter().map(|coord| (coord.lon, coord.lat)).collect();
+    let draw_start_time = Instant::now();
+    draw_map(&data, &path, options);
+    println!("Map drawn in {:?}", draw_start_time.elapsed());
+}
+
+/// Snaps a GPX track onto the roads, then exports and draws the matched route.
+fn run_match(filename: &OsStr, track: &OsStr, options: &RenderOptions) {
+    let track = match read_gpx(track) {
+        Ok(track) => track,
+        Err(e) => {
+            println!("Failed to read track: {}", e);
+            return;
+        }
+    };
+
+    let data = load_or_parse_data(filename);
+    let match_start_time = Instant::now();
+    let index = EdgeIndex::new(&data.graph);
+    let Some(matched) = match_track(&data.graph, &index, &track) else {
+        println!("No roads near the track.");
+        return;
+    };
+    println!(
+        "Matched {} points onto {} ways with confidence {:.2} in {:?}",
+        track.len(),
+        matched.ways.len(),
+        matched.confidence,
+        match_start_time.elapsed()
+    );
+
+    export_route(&matched.path, "matched").expect("Failed to export route.");
+    println!(
+        "Route exported to matched.gpx and matched.geojson ({:.0} m)",
+        path_length_meters(&matched.path)
+    );
+
+    let path: Vec<_> = matched
+        .path
+        .iter()
+        .map(|coord| (coord.lon, coord.lat))
+        .collect();
+    let draw_start_time = Instant::now();
+    draw_map(&data, &path, options);
+    println!("Map drawn in {:?}", draw_start_time.elapsed());
+}
+
+/// Loads the data once and answers tile, image and route requests over HTTP.
+fn run_serve(filename: &OsStr, overlays: &[&str], addr: &str, options: RenderOptions) {
+    let data = load_with_overlays(filename, overlays);
+    if let Err(e) = serve(data, options, addr) {
+        println!("Server failed: {}", e);
+    }
+}
+
+/// Snaps every point to the nearest of `nodes`.
+fn snap_all(nodes: &[Coord], points: Vec<Coord>) -> Option<Vec<Coord>> {
+    points
+        .into_iter()
+        .map(|point| nearest_node_in(nodes, point))
+        .collect()
+}
+
+fn parse_coord_list(list: &OsStr) -> Option<Vec<Coord>> {
+    list.to_str()?.split(';').map(parse_coord).collect()
+}
+
+fn load_or_parse_data(filename: &OsStr) -> CachedData {
     let start_time = Instant::now();
     let cache_filename = format!("{}.cache", filename.to_str().unwrap());
     let data = if let Ok(cached_data) = load_cache::<CachedData>(&cache_filename) {
         println!("Loaded data from cache.");
         println!("Loaded data in {:?}", start_time.elapsed());
-        (
-            cached_data.nodes,
-            cached_data.highways,
-            cached_data.waterways,
-            cached_data.railways,
-            cached_data.buildings,
-            cached_data.naturals,
-            cached_data.aeroways,
-            cached_data.multipolygons,
-            cached_data.graph,
-        )
+        cached_data
     } else {
         println!("Parsing OSM data.");
         let parse_start_time = Instant::now();
-        let parsed_data = read_osm_data(filename);
+        let mut parsed_data = read_osm_data(filename, None);
         let parse_duration = parse_start_time.elapsed();
         println!("OSM data parsed in {:?}", parse_duration);
 
         let build_graph_start_time = Instant::now();
-        let graph = maps::graph::build_graph(&parsed_data.1);
+        parsed_data.graph = maps::graph::build_graph(&parsed_data.highways);
+        parsed_data.component = largest_component(&parsed_data.graph);
         let build_graph_duration = build_graph_start_time.elapsed();
         println!("Graph built in {:?}", build_graph_duration);
 
         let save_start_time = Instant::now();
-        let return_data = (
-            parsed_data.0,
-            parsed_data.1,
-            parsed_data.2,
-            parsed_data.3,
-            parsed_data.4,
-            parsed_data.5,
-            parsed_data.6,
-            parsed_data.7,
-            graph,
-        );
-
-        save_cache(OsStr::new(&cache_filename), &return_data).expect("Failed to save cache.");
+        save_cache(OsStr::new(&cache_filename), &parsed_data).expect("Failed to save cache.");
         let save_duration = save_start_time.elapsed();
         println!("Cache saved in {:?}", save_duration);
-        return_data
+        parsed_data
     };
 
     let cache_or_parse_duration = start_time.elapsed();
@@ -124,21 +770,26 @@ fn load_or_parse_data(
     data
 }
 
-fn run_a_star(highways: &[WayCoords], graph: HashMap<Coord, Vec<Edge>>) -> Vec<Coord> {
-    let a_star_start_time = Instant::now();
-    // pick a random node from nodes as start and goal
-
-    let start_random = get_random_node(highways);
-    let goal_random = get_random_node(highways);
-    let start_random_coord = Coord {
-        lat: start_random.1,
-        lon: start_random.0,
-    };
+/// Loads the cached OSM data and adds each GeoJSON overlay on top. Overlays
+/// are never written to the cache.
+fn load_with_overlays(filename: &OsStr, overlays: &[&str]) -> CachedData {
+    let mut data = load_or_parse_data(filename);
+    for overlay in overlays {
+        match read_geojson(overlay) {
+            Ok(overlay_data) => merge_overlay(&mut data, overlay_data),
+            Err(e) => println!("Skipping overlay {}: {}", overlay, e),
+        }
+    }
+    data
+}
 
-    let goal_random_coord = Coord {
-        lat: goal_random.1,
-        lon: goal_random.0,
-    };
+fn run_a_star(data: &CachedData, strategy: Strategy, landmarks: Option<&Landmarks>) -> Vec<Coord> {
+    let graph = &data.graph;
+    let a_star_start_time = Instant::now();
+    // pick a random node from the largest component as start and goal so
+    // that a path exists
+    let start_random_coord = get_random_node(&data.component);
+    let goal_random_coord = get_random_node(&data.component);
 
     println!("Start: {:?}", start_random_coord);
     println!("Goal: {:?}", goal_random_coord);
@@ -148,8 +799,12 @@ fn run_a_star(highways: &[WayCoords], graph: HashMap<Coord, Vec<Edge>>) -> Vec<C
         graph.get(&start_random_coord).unwrap().len()
     );
 
+    let router = Router::new(graph, &data.restrictions, landmarks);
+    let (path, expanded) = router.find_path(start_random_coord, goal_random_coord, strategy);
+    println!("{:?} search expanded {} states", strategy, expanded);
+
     let path_result;
-    if let Some((result, cost)) = find_path(&graph, start_random_coord, goal_random_coord) {
+    if let Some((result, cost)) = path {
         println!("Path found with cost {}", cost);
         path_result = result;
     } else {
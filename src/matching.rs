@@ -0,0 +1,385 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use crate::graph::{find_path, haversine_meters, COST_PER_METER};
+use crate::types::{coord::Coord, edge::Edge};
+
+/// Standard deviation of GPS noise in meters.
+const GPS_SIGMA: f64 = 10.0;
+/// How quickly a transition becomes unlikely as the distance along the roads
+/// departs from the distance between fixes, in meters.
+const TRANSITION_BETA: f64 = 5.0;
+/// How far from a fix a road may be to be a candidate, in meters.
+const SEARCH_RADIUS: f64 = 50.0;
+const MAX_CANDIDATES: usize = 8;
+/// Size of an index cell in degrees.
+const CELL_DEGREES: f64 = 0.002;
+const METERS_PER_DEGREE_LAT: f64 = 110_574.0;
+
+/// A graph edge, stored once for both directions.
+#[derive(Debug, Clone, Copy)]
+struct Segment {
+    a: Coord,
+    b: Coord,
+    way: i64,
+}
+
+impl Segment {
+    fn length(&self) -> f64 {
+        haversine_meters(self.a.lon, self.a.lat, self.b.lon, self.b.lat)
+    }
+}
+
+/// Grid of the edges of a graph for finding those near a point.
+pub struct EdgeIndex {
+    cells: HashMap<(i64, i64), Vec<Segment>>,
+}
+
+fn cell(coord: Coord) -> (i64, i64) {
+    (
+        (coord.lon / CELL_DEGREES).floor() as i64,
+        (coord.lat / CELL_DEGREES).floor() as i64,
+    )
+}
+
+impl EdgeIndex {
+    pub fn new(graph: &HashMap<Coord, Vec<Edge>>) -> Self {
+        let mut cells: HashMap<(i64, i64), Vec<Segment>> = HashMap::new();
+        let mut seen = HashSet::new();
+        for (&a, edges) in graph {
+            for edge in edges {
+                if !seen.insert((a.min(edge.target), a.max(edge.target))) {
+                    continue;
+                }
+                let segment = Segment {
+                    a,
+                    b: edge.target,
+                    way: edge.way,
+                };
+                let ((x0, y0), (x1, y1)) = (cell(a), cell(edge.target));
+                for x in x0.min(x1)..=x0.max(x1) {
+                    for y in y0.min(y1)..=y0.max(y1) {
+                        cells.entry((x, y)).or_default().push(segment);
+                    }
+                }
+            }
+        }
+        EdgeIndex { cells }
+    }
+
+    /// The closest points on the edges within `radius` meters of `point`,
+    /// nearest first.
+    fn candidates(&self, point: Coord, radius: f64) -> Vec<Candidate> {
+        let lat_radius = radius / METERS_PER_DEGREE_LAT;
+        let lon_radius = lat_radius / point.lat.to_radians().cos();
+        let (x0, y0) = cell(Coord::new(point.lon - lon_radius, point.lat - lat_radius));
+        let (x1, y1) = cell(Coord::new(point.lon + lon_radius, point.lat + lat_radius));
+
+        let mut seen = HashSet::new();
+        let mut candidates = Vec::new();
+        for x in x0..=x1 {
+            for y in y0..=y1 {
+                for segment in self.cells.get(&(x, y)).into_iter().flatten() {
+                    if !seen.insert((segment.a, segment.b)) {
+                        continue;
+                    }
+                    let candidate = Candidate::project(point, *segment);
+                    if candidate.distance <= radius {
+                        candidates.push(candidate);
+                    }
+                }
+            }
+        }
+        candidates.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        candidates.truncate(MAX_CANDIDATES);
+        candidates
+    }
+}
+
+/// A possible road position for a GPS fix.
+#[derive(Debug, Clone, Copy)]
+struct Candidate {
+    segment: Segment,
+    /// How far along the segment the position is, from 0 at `a` to 1 at `b`.
+    t: f64,
+    point: Coord,
+    /// Distance from the fix in meters.
+    distance: f64,
+}
+
+impl Candidate {
+    fn project(point: Coord, segment: Segment) -> Self {
+        // Flat approximation in meters around the fix
+        let scale_x = METERS_PER_DEGREE_LAT * point.lat.to_radians().cos();
+        let local = |c: Coord| {
+            (
+                (c.lon - point.lon) * scale_x,
+                (c.lat - point.lat) * METERS_PER_DEGREE_LAT,
+            )
+        };
+        let ((ax, ay), (bx, by)) = (local(segment.a), local(segment.b));
+        let (dx, dy) = (bx - ax, by - ay);
+        let length_squared = dx * dx + dy * dy;
+        let t = if length_squared > 0.0 {
+            (-(ax * dx + ay * dy) / length_squared).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        Candidate {
+            segment,
+            t,
+            point: Coord::new(
+                segment.a.lon + t * (segment.b.lon - segment.a.lon),
+                segment.a.lat + t * (segment.b.lat - segment.a.lat),
+            ),
+            distance: (ax + t * dx).hypot(ay + t * dy),
+        }
+    }
+
+    fn log_emission(&self) -> f64 {
+        -0.5 * (self.distance / GPS_SIGMA).powi(2)
+    }
+
+    /// The segment's end nodes with the distance to each along the segment.
+    fn ends(&self) -> [(Coord, f64); 2] {
+        let length = self.segment.length();
+        [
+            (self.segment.a, self.t * length),
+            (self.segment.b, (1.0 - self.t) * length),
+        ]
+    }
+}
+
+/// A GPS track snapped onto the road graph.
+#[derive(Debug, Clone)]
+pub struct MatchedRoute {
+    /// The route along the roads, starting and ending at the snapped first and
+    /// last fixes.
+    pub path: Vec<Coord>,
+    /// OSM ids of the ways driven, in order.
+    pub ways: Vec<i64>,
+    /// The position on the road of each fix that could be matched, with the
+    /// fix's index in the track. Fixes closer than twice `GPS_SIGMA` to the
+    /// previous one kept are thinned out before matching and have no entry.
+    pub snapped: Vec<(usize, Coord)>,
+    /// From 0 to 1, the share of fixes matched times the geometric mean of
+    /// their likelihood relative to a fix lying exactly on a road that was
+    /// followed exactly.
+    pub confidence: f64,
+}
+
+/// Matches a GPS track with a hidden Markov model solved by Viterbi. Each fix
+/// may be any nearby road position, likelier the closer it is, and moving
+/// between positions of consecutive fixes is likelier the closer the
+/// distance along the roads is to the distance between the fixes. Where no
+/// road route joins two fixes the match restarts, leaving a straight gap.
+/// Travel direction and turn restrictions are not enforced.
+pub fn match_track(
+    graph: &HashMap<Coord, Vec<Edge>>,
+    index: &EdgeIndex,
+    track: &[Coord],
+) -> Option<MatchedRoute> {
+    // Fixes closer together than the noise only add noise
+    let mut fixes: Vec<(usize, Coord)> = Vec::new();
+    for (i, &fix) in track.iter().enumerate() {
+        if fixes
+            .last()
+            .is_none_or(|&(_, last)| distance(last, fix) >= 2.0 * GPS_SIGMA)
+        {
+            fixes.push((i, fix));
+        }
+    }
+    // Track indices of the fixes with candidates, alongside them
+    let (track_indices, steps): (Vec<usize>, Vec<(Coord, Vec<Candidate>)>) = fixes
+        .iter()
+        .map(|&(i, fix)| (i, (fix, index.candidates(fix, SEARCH_RADIUS))))
+        .filter(|(_, (_, candidates))| !candidates.is_empty())
+        .unzip();
+    if steps.is_empty() {
+        return None;
+    }
+
+    // For every step, the best previous candidate of each candidate and the
+    // segment end nodes the route between them leaves and enters by
+    let mut back: Vec<Vec<Option<Link>>> = vec![vec![None; steps[0].1.len()]];
+    let mut scores: Vec<Vec<f64>> = vec![steps[0].1.iter().map(Candidate::log_emission).collect()];
+    let mut total_score = 0.0;
+    for i in 1..steps.len() {
+        let ((previous_fix, previous), (fix, current)) = (&steps[i - 1], &steps[i]);
+        let straight = distance(*previous_fix, *fix);
+        let limit = straight * 3.0 + 4.0 * SEARCH_RADIUS;
+        let reach: HashMap<Coord, HashMap<Coord, f64>> = previous
+            .iter()
+            .flat_map(Candidate::ends)
+            .map(|(node, _)| (node, bounded_dijkstra(graph, node, limit)))
+            .collect();
+
+        let mut links = Vec::with_capacity(current.len());
+        let mut next_scores = Vec::with_capacity(current.len());
+        for candidate in current {
+            let best = previous
+                .iter()
+                .enumerate()
+                .filter_map(|(j, from)| {
+                    let (route, via) = route_distance(from, candidate, &reach)?;
+                    let log_transition = -(route - straight).abs() / TRANSITION_BETA;
+                    Some((scores[i - 1][j] + log_transition, Link { previous: j, via }))
+                })
+                .max_by(|a, b| a.0.total_cmp(&b.0));
+            match best {
+                Some((score, link)) => {
+                    next_scores.push(score + candidate.log_emission());
+                    links.push(Some(link));
+                }
+                None => {
+                    next_scores.push(f64::NEG_INFINITY);
+                    links.push(None);
+                }
+            }
+        }
+
+        if next_scores.iter().all(|score| *score == f64::NEG_INFINITY) {
+            // No road route from the last fix: close the chain and start over
+            total_score += scores[i - 1][best_index(&scores[i - 1])];
+            next_scores = current.iter().map(Candidate::log_emission).collect();
+        }
+        back.push(links);
+        scores.push(next_scores);
+    }
+    let last = &scores[steps.len() - 1];
+    let mut chosen = best_index(last);
+    total_score += last[chosen];
+
+    // Walk the links back from the best final candidate. A step without a
+    // link started over, so the chain before it ends at its own best.
+    let mut chain = vec![(chosen, back[steps.len() - 1][chosen])];
+    for i in (1..steps.len()).rev() {
+        chosen = match back[i][chosen] {
+            Some(link) => link.previous,
+            None => best_index(&scores[i - 1]),
+        };
+        chain.push((chosen, back[i - 1][chosen]));
+    }
+    chain.reverse();
+
+    let no_restrictions = HashMap::new();
+    let mut path = Vec::new();
+    let mut ways = Vec::new();
+    let mut snapped = Vec::new();
+    for (i, &(chosen, link)) in chain.iter().enumerate() {
+        let candidate = &steps[i].1[chosen];
+        if let Some(Link {
+            via: Some((exit, entry)),
+            ..
+        }) = link
+        {
+            if let Some((nodes, _)) = find_path(graph, &no_restrictions, exit, entry) {
+                for w in nodes.windows(2) {
+                    ways.extend(edge_way(graph, w[0], w[1]));
+                }
+                path.extend(nodes);
+            }
+        }
+        ways.push(candidate.segment.way);
+        path.push(candidate.point);
+        snapped.push((track_indices[i], candidate.point));
+    }
+    path.dedup();
+    ways.dedup();
+
+    let matched_share = steps.len() as f64 / fixes.len() as f64;
+    Some(MatchedRoute {
+        path,
+        ways,
+        snapped,
+        confidence: matched_share * (total_score / steps.len() as f64).exp(),
+    })
+}
+
+/// How a candidate was reached from one of the previous step.
+#[derive(Debug, Clone, Copy)]
+struct Link {
+    previous: usize,
+    /// End nodes of the previous and current segments the route passes
+    /// through, or `None` when both lie on the same segment.
+    via: Option<(Coord, Coord)>,
+}
+
+/// Distance along the roads between two candidates and the segment ends the
+/// shortest way uses.
+fn route_distance(
+    from: &Candidate,
+    to: &Candidate,
+    reach: &HashMap<Coord, HashMap<Coord, f64>>,
+) -> Option<(f64, Option<(Coord, Coord)>)> {
+    let same_segment =
+        |a: &Segment, b: &Segment| (a.a == b.a && a.b == b.b) || (a.a == b.b && a.b == b.a);
+    if same_segment(&from.segment, &to.segment) {
+        let t = if from.segment.a == to.segment.a {
+            to.t
+        } else {
+            1.0 - to.t
+        };
+        return Some(((t - from.t).abs() * from.segment.length(), None));
+    }
+
+    let mut best: Option<(f64, Option<(Coord, Coord)>)> = None;
+    for (exit, to_exit) in from.ends() {
+        for (entry, from_entry) in to.ends() {
+            let Some(between) = reach.get(&exit).and_then(|costs| costs.get(&entry)) else {
+                continue;
+            };
+            let total = to_exit + between + from_entry;
+            if best.is_none_or(|(distance, _)| total < distance) {
+                best = Some((total, Some((exit, entry))));
+            }
+        }
+    }
+    best
+}
+
+fn best_index(scores: &[f64]) -> usize {
+    (0..scores.len())
+        .max_by(|&a, &b| scores[a].total_cmp(&scores[b]))
+        .unwrap_or(0)
+}
+
+/// Distances in meters to every node within `limit` meters of `origin`.
+fn bounded_dijkstra(
+    graph: &HashMap<Coord, Vec<Edge>>,
+    origin: Coord,
+    limit: f64,
+) -> HashMap<Coord, f64> {
+    let limit = (limit * COST_PER_METER) as i64;
+    let mut costs: HashMap<Coord, i64> = HashMap::from([(origin, 0)]);
+    let mut queue = BinaryHeap::from([Reverse((0, origin))]);
+    while let Some(Reverse((cost, node))) = queue.pop() {
+        if costs.get(&node).is_some_and(|&best| cost > best) {
+            continue;
+        }
+        for edge in graph.get(&node).into_iter().flatten() {
+            let next = cost + edge.cost;
+            if next <= limit && costs.get(&edge.target).is_none_or(|&best| next < best) {
+                costs.insert(edge.target, next);
+                queue.push(Reverse((next, edge.target)));
+            }
+        }
+    }
+    costs
+        .into_iter()
+        .map(|(node, cost)| (node, cost as f64 / COST_PER_METER))
+        .collect()
+}
+
+fn edge_way(graph: &HashMap<Coord, Vec<Edge>>, from: Coord, to: Coord) -> Option<i64> {
+    graph
+        .get(&from)?
+        .iter()
+        .filter(|edge| edge.target == to)
+        .min_by_key(|edge| edge.cost)
+        .map(|edge| edge.way)
+}
+
+fn distance(a: Coord, b: Coord) -> f64 {
+    haversine_meters(a.lon, a.lat, b.lon, b.lat)
+}
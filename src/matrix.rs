@@ -0,0 +1,96 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+
+use crate::graph::turn_allowed;
+use crate::types::restriction::TurnRestriction;
+use crate::types::{coord::Coord, edge::Edge};
+
+/// A node and the way the search arrived by, so turn restrictions can be
+/// checked when leaving it.
+type State = (Coord, Option<i64>);
+
+/// Dijkstra state for one-to-many searches. Each origin is one search that
+/// settles all of the targets, and keeps the allocations of the previous
+/// origin on the same thread.
+#[derive(Default)]
+struct SearchState {
+    costs: HashMap<State, i64>,
+    /// Cost of the first state settled at each node, the cheapest way there.
+    reached: HashMap<Coord, i64>,
+    queue: BinaryHeap<Reverse<(i64, State)>>,
+}
+
+impl SearchState {
+    /// Costs from `origin` to each of `targets`, in the same order. The search
+    /// stops as soon as every reachable target has been settled.
+    fn one_to_many(
+        &mut self,
+        graph: &HashMap<Coord, Vec<Edge>>,
+        restrictions: &HashMap<Coord, Vec<TurnRestriction>>,
+        origin: Coord,
+        targets: &[Coord],
+    ) -> Vec<Option<i64>> {
+        self.costs.clear();
+        self.reached.clear();
+        self.queue.clear();
+
+        let mut remaining: HashMap<Coord, usize> = HashMap::new();
+        for target in targets {
+            *remaining.entry(*target).or_default() += 1;
+        }
+        let mut left = targets.len();
+
+        self.costs.insert((origin, None), 0);
+        self.queue.push(Reverse((0, (origin, None))));
+        while let Some(Reverse((cost, state))) = self.queue.pop() {
+            if self.costs.get(&state).is_some_and(|&best| cost > best) {
+                continue;
+            }
+            let (node, incoming) = state;
+            self.reached.entry(node).or_insert(cost);
+            if let Some(count) = remaining.remove(&node) {
+                left -= count;
+                if left == 0 {
+                    break;
+                }
+            }
+            for edge in graph.get(&node).into_iter().flatten() {
+                if !turn_allowed(restrictions, node, incoming, edge.way) {
+                    continue;
+                }
+                let next = (edge.target, Some(edge.way));
+                let next_cost = cost + edge.cost;
+                if self.costs.get(&next).is_none_or(|&best| next_cost < best) {
+                    self.costs.insert(next, next_cost);
+                    self.queue.push(Reverse((next_cost, next)));
+                }
+            }
+        }
+
+        targets
+            .iter()
+            .map(|target| self.reached.get(target).copied())
+            .collect()
+    }
+}
+
+/// Travel costs between every origin and destination, in the same units as
+/// edge costs. Row `i` holds the costs from `origins[i]`; unreachable pairs are
+/// `None`. Each row is a single Dijkstra search from its origin rather than
+/// one per pair, and rows run in parallel. Turn restrictions are obeyed, so
+/// costs match those of `graph::find_path`.
+pub fn distance_matrix(
+    graph: &HashMap<Coord, Vec<Edge>>,
+    restrictions: &HashMap<Coord, Vec<TurnRestriction>>,
+    origins: &[Coord],
+    destinations: &[Coord],
+) -> Vec<Vec<Option<i64>>> {
+    origins
+        .par_iter()
+        .map_init(SearchState::default, |state, &origin| {
+            state.one_to_many(graph, restrictions, origin, destinations)
+        })
+        .collect()
+}
@@ -0,0 +1,83 @@
+use std::collections::HashMap;
+
+use crate::types::area_record::AreaRecord;
+use crate::types::cached_data::{Polygon, WayCoords};
+use crate::types::way_record::WayRecord;
+
+/// Assembles a multipolygon relation into polygons. Member ways are joined
+/// end to end into closed rings and every inner ring becomes a hole of the
+/// outer ring it lies in. Rings that cannot be closed, for example because a
+/// member is outside the extract, are left out.
+pub fn assemble(
+    area: &AreaRecord,
+    ways: &HashMap<i64, WayRecord>,
+    nodes: &HashMap<i64, (f64, f64)>,
+) -> Vec<Polygon> {
+    let rings = |members: &[i64]| -> Vec<WayCoords> {
+        let members = members
+            .iter()
+            .filter_map(|id| Some(ways.get(id)?.nodes.clone()))
+            .collect();
+        join_rings(members)
+            .into_iter()
+            .map(|ring| {
+                ring.iter()
+                    .filter_map(|id| nodes.get(id))
+                    .copied()
+                    .collect()
+            })
+            .filter(|ring: &WayCoords| ring.len() >= 4)
+            .collect()
+    };
+
+    let mut polygons: Vec<Polygon> = rings(&area.outer)
+        .into_iter()
+        .map(|ring| vec![ring])
+        .collect();
+    for hole in rings(&area.inner) {
+        if let Some(polygon) = polygons
+            .iter_mut()
+            .find(|polygon| contains(&polygon[0], hole[0]))
+        {
+            polygon.push(hole);
+        }
+    }
+    polygons
+}
+
+/// Joins node id sequences that share end nodes into closed rings.
+fn join_rings(mut open: Vec<Vec<i64>>) -> Vec<Vec<i64>> {
+    let mut rings = Vec::new();
+    while let Some(mut ring) = open.pop() {
+        while ring.len() > 1 && ring.first() != ring.last() {
+            let end = *ring.last().unwrap();
+            let Some(next) = open
+                .iter()
+                .position(|way| way.first() == Some(&end) || way.last() == Some(&end))
+            else {
+                break;
+            };
+            let mut way = open.swap_remove(next);
+            if way.first() != Some(&end) {
+                way.reverse();
+            }
+            ring.extend(way.into_iter().skip(1));
+        }
+        if ring.len() > 1 && ring.first() == ring.last() {
+            rings.push(ring);
+        }
+    }
+    rings
+}
+
+/// Point in polygon test by ray casting.
+pub fn contains(ring: &[(f64, f64)], (x, y): (f64, f64)) -> bool {
+    let mut inside = false;
+    for edge in ring.windows(2) {
+        let ((x1, y1), (x2, y2)) = (edge[0], edge[1]);
+        if (y1 > y) != (y2 > y) && x < x1 + (y - y1) / (y2 - y1) * (x2 - x1) {
+            inside = !inside;
+        }
+    }
+    inside
+}
@@ -0,0 +1,493 @@
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+use std::f64::consts::PI;
+use std::fs;
+use std::path::Path;
+
+use crate::drawing::clip_segment;
+use crate::export::{extract_features, Feature, Geometry};
+use crate::simplify::{douglas_peucker, visvalingam};
+use crate::types::cached_data::CachedData;
+
+/// Tile coordinates run from 0 to `EXTENT` across a tile.
+const EXTENT: u32 = 4096;
+/// Geometry is kept this far past the tile edge so lines and outlines join
+/// up without seams.
+const BUFFER: f64 = 64.0;
+/// Simplification tolerance in tile units, a quarter of a pixel on a 256px tile.
+const TOLERANCE: f64 = 4.0;
+/// Web Mercator stops short of the poles.
+const MAX_LATITUDE: f64 = 85.051_128_78;
+
+const POINT: u32 = 1;
+const LINE_STRING: u32 = 2;
+const POLYGON: u32 = 3;
+
+type Point = (f64, f64);
+/// Features of one tile by layer name.
+type TileLayers<'a> = BTreeMap<&'a str, Vec<(&'a Feature, u32, Vec<u32>)>>;
+
+/// Writes Mapbox Vector Tiles of every extracted feature for each zoom level
+/// in `zooms` to `dir/{z}/{x}/{y}.mvt`, with one layer per feature category
+/// and the OSM tags as properties. Geometry is simplified for each zoom, so
+/// small features drop out at low zooms. A `metadata.json` lists the layers.
+/// Returns the number of tiles written.
+pub fn write_vector_tiles(
+    data: &CachedData,
+    zooms: std::ops::RangeInclusive<u32>,
+    dir: impl AsRef<Path>,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let features = extract_features(data);
+    let mut count = 0;
+    for zoom in zooms.clone() {
+        let mut tiles: HashMap<(u32, u32), TileLayers> = HashMap::new();
+        for feature in &features {
+            for (tile, geometry_type, geometry) in tile_feature(&feature.geometry, zoom) {
+                tiles
+                    .entry(tile)
+                    .or_default()
+                    .entry(feature.layer)
+                    .or_default()
+                    .push((feature, geometry_type, geometry));
+            }
+        }
+        for ((x, y), layers) in tiles {
+            let tile_dir = dir.as_ref().join(zoom.to_string()).join(x.to_string());
+            fs::create_dir_all(&tile_dir)?;
+            fs::write(tile_dir.join(format!("{}.mvt", y)), encode_tile(&layers))?;
+            count += 1;
+        }
+    }
+
+    let mut layers: Vec<&str> = features.iter().map(|feature| feature.layer).collect();
+    layers.sort();
+    layers.dedup();
+    let metadata = json!({
+        "format": "pbf",
+        "minzoom": zooms.start(),
+        "maxzoom": zooms.end(),
+        "tiles": ["{z}/{x}/{y}.mvt"],
+        "vector_layers": layers.iter().map(|layer| json!({"id": layer})).collect::<Vec<_>>(),
+    });
+    fs::create_dir_all(dir.as_ref())?;
+    fs::write(dir.as_ref().join("metadata.json"), metadata.to_string())?;
+    Ok(count)
+}
+
+/// Web Mercator position in tile units at `zoom`, with y growing southwards.
+fn project((lon, lat): Point, zoom: u32) -> Point {
+    let size = (EXTENT as u64 * (1 << zoom)) as f64;
+    let lat = lat.clamp(-MAX_LATITUDE, MAX_LATITUDE).to_radians();
+    let x = (lon + 180.0) / 360.0 * size;
+    let y = (1.0 - (lat.tan() + 1.0 / lat.cos()).ln() / PI) / 2.0 * size;
+    (x, y)
+}
+
+/// Projects and simplifies a geometry for `zoom` and cuts it into the tiles it
+/// touches, giving each tile's geometry type and encoded commands. Tiles are
+/// found by splitting from the whole world down, one quarter at a time.
+fn tile_feature(geometry: &Geometry, zoom: u32) -> Vec<((u32, u32), u32, Vec<u32>)> {
+    let project_all =
+        |line: &[Point]| -> Vec<Point> { line.iter().map(|&point| project(point, zoom)).collect() };
+    let line = |line: &[Point]| douglas_peucker(&project_all(line), TOLERANCE);
+    let ring = |ring: &Vec<Point>| visvalingam(&project_all(ring), TOLERANCE * TOLERANCE);
+    let (geometry_type, parts): (u32, Vec<Vec<Vec<Point>>>) = match geometry {
+        Geometry::Point(point) => (POINT, vec![vec![vec![project(*point, zoom)]]]),
+        Geometry::LineString(coords) => (LINE_STRING, vec![vec![line(coords)]]),
+        Geometry::Polygon(rings) => (POLYGON, vec![rings.iter().map(ring).collect()]),
+        Geometry::MultiPolygon(polygons) => (
+            POLYGON,
+            polygons
+                .iter()
+                .map(|rings| rings.iter().map(ring).collect())
+                .collect(),
+        ),
+    };
+
+    let mut tiles = Vec::new();
+    let parts = clip_parts(geometry_type, &parts, tile_bounds((0, 0), 0, zoom));
+    split(geometry_type, parts, (0, 0), 0, zoom, &mut tiles);
+    tiles
+}
+
+/// A geometry in tile units by its type: one point, pieces of a line, or
+/// polygons as outer ring followed by holes.
+type Parts = Vec<Vec<Vec<Point>>>;
+/// A box as (min_x, min_y, max_x, max_y).
+type Bounds = (f64, f64, f64, f64);
+
+/// The buffered edges of tile `(x, y)` at `level`, in tile units at `zoom`.
+fn tile_bounds((x, y): (u32, u32), level: u32, zoom: u32) -> Bounds {
+    let size = EXTENT as f64 * (1u64 << (zoom - level)) as f64;
+    (
+        x as f64 * size - BUFFER,
+        y as f64 * size - BUFFER,
+        (x as f64 + 1.0) * size + BUFFER,
+        (y as f64 + 1.0) * size + BUFFER,
+    )
+}
+
+/// Splits parts already clipped to tile `(x, y)` at `level` into its four
+/// quarters down to `zoom`, so each tile only clips what its parent kept
+/// rather than the whole geometry.
+fn split(
+    geometry_type: u32,
+    parts: Parts,
+    (x, y): (u32, u32),
+    level: u32,
+    zoom: u32,
+    tiles: &mut Vec<((u32, u32), u32, Vec<u32>)>,
+) {
+    if parts.iter().flatten().flatten().next().is_none() {
+        return;
+    }
+    if level < zoom {
+        for tile in [
+            (2 * x, 2 * y),
+            (2 * x + 1, 2 * y),
+            (2 * x, 2 * y + 1),
+            (2 * x + 1, 2 * y + 1),
+        ] {
+            let quarter = clip_parts(geometry_type, &parts, tile_bounds(tile, level + 1, zoom));
+            split(geometry_type, quarter, tile, level + 1, zoom, tiles);
+        }
+        return;
+    }
+
+    let origin = (x as f64 * EXTENT as f64, y as f64 * EXTENT as f64);
+    let local = |part: &[Point]| -> Vec<Point> {
+        part.iter()
+            .map(|&(px, py)| (px - origin.0, py - origin.1))
+            .collect()
+    };
+    let commands = match geometry_type {
+        POINT => {
+            let (px, py) = local(&parts[0][0])[0];
+            let inside = (0.0..EXTENT as f64).contains(&px) && (0.0..EXTENT as f64).contains(&py);
+            if inside {
+                encode_points(&[(px, py)])
+            } else {
+                Vec::new()
+            }
+        }
+        LINE_STRING => {
+            let pieces: Vec<Vec<(i32, i32)>> = parts[0]
+                .iter()
+                .map(|piece| round(&local(piece)))
+                .filter(|piece| piece.len() >= 2)
+                .collect();
+            encode_lines(&pieces)
+        }
+        _ => {
+            let mut rings = Vec::new();
+            for polygon in &parts {
+                for (i, ring) in polygon.iter().enumerate() {
+                    let ring = round(&local(ring));
+                    if ring.len() >= 3 && area(&ring) != 0 {
+                        rings.push((i == 0, ring));
+                    } else if i == 0 {
+                        // Holes of a polygon outside the tile go too
+                        break;
+                    }
+                }
+            }
+            encode_rings(&mut rings)
+        }
+    };
+    if !commands.is_empty() {
+        tiles.push(((x, y), geometry_type, commands));
+    }
+}
+
+/// Clips parts to `bounds`, copying them when they are all inside.
+fn clip_parts(geometry_type: u32, parts: &Parts, bounds: Bounds) -> Parts {
+    let (min_x, min_y, max_x, max_y) = bounds;
+    let (low_x, low_y, high_x, high_y) = parts.iter().flatten().flatten().fold(
+        (f64::MAX, f64::MAX, f64::MIN, f64::MIN),
+        |(low_x, low_y, high_x, high_y), &(x, y)| {
+            (low_x.min(x), low_y.min(y), high_x.max(x), high_y.max(y))
+        },
+    );
+    if high_x < min_x || low_x > max_x || high_y < min_y || low_y > max_y {
+        return Vec::new();
+    }
+    if low_x >= min_x && high_x <= max_x && low_y >= min_y && high_y <= max_y {
+        return parts.clone();
+    }
+    match geometry_type {
+        // A single point is either inside or outside
+        POINT => Vec::new(),
+        LINE_STRING => vec![parts[0]
+            .iter()
+            .flat_map(|piece| clip_line(piece, bounds))
+            .collect()],
+        _ => parts
+            .iter()
+            .filter_map(|polygon| {
+                let mut rings = polygon.iter().map(|ring| clip_ring(ring, bounds));
+                let outer = rings.next().filter(|outer| outer.len() >= 3)?;
+                Some(
+                    std::iter::once(outer)
+                        .chain(rings.filter(|hole| hole.len() >= 3))
+                        .collect(),
+                )
+            })
+            .collect(),
+    }
+}
+
+/// Cuts a line into the pieces inside `bounds`.
+fn clip_line(line: &[Point], bounds: Bounds) -> Vec<Vec<Point>> {
+    let mut pieces: Vec<Vec<Point>> = Vec::new();
+    let mut current: Vec<Point> = Vec::new();
+    for segment in line.windows(2) {
+        match clip_segment(segment[0], segment[1], bounds) {
+            Some((a, b)) => {
+                if current.last() != Some(&a) {
+                    if current.len() > 1 {
+                        pieces.push(std::mem::take(&mut current));
+                    }
+                    current = vec![a];
+                }
+                current.push(b);
+                // The segment left the tile, so the piece ends here
+                if b != segment[1] {
+                    pieces.push(std::mem::take(&mut current));
+                }
+            }
+            None => {
+                if current.len() > 1 {
+                    pieces.push(std::mem::take(&mut current));
+                }
+                current.clear();
+            }
+        }
+    }
+    if current.len() > 1 {
+        pieces.push(current);
+    }
+    pieces
+}
+
+/// Sutherland–Hodgman clipping of a closed ring to `bounds`.
+fn clip_ring(ring: &[Point], (min_x, min_y, max_x, max_y): Bounds) -> Vec<Point> {
+    let mut points: Vec<Point> = ring.to_vec();
+    let edges = [
+        (0, min_x, true),
+        (0, max_x, false),
+        (1, min_y, true),
+        (1, max_y, false),
+    ];
+    for (axis, edge, is_min) in edges {
+        let axis = |p: Point| if axis == 0 { p.0 } else { p.1 };
+        let inside = |p: Point| {
+            if is_min {
+                axis(p) >= edge
+            } else {
+                axis(p) <= edge
+            }
+        };
+        let mut clipped = Vec::new();
+        for i in 0..points.len() {
+            let (a, b) = (points[i], points[(i + 1) % points.len()]);
+            if inside(a) {
+                clipped.push(a);
+            }
+            if inside(a) != inside(b) {
+                let t = (edge - axis(a)) / (axis(b) - axis(a));
+                clipped.push((a.0 + t * (b.0 - a.0), a.1 + t * (b.1 - a.1)));
+            }
+        }
+        points = clipped;
+    }
+    points
+}
+
+/// Rounds to whole tile units, dropping repeated points.
+fn round(points: &[Point]) -> Vec<(i32, i32)> {
+    let mut rounded: Vec<(i32, i32)> = Vec::with_capacity(points.len());
+    for &(x, y) in points {
+        let point = (x.round() as i32, y.round() as i32);
+        if rounded.last() != Some(&point) {
+            rounded.push(point);
+        }
+    }
+    rounded
+}
+
+/// Twice the signed area of a ring, positive when clockwise with y down.
+fn area(ring: &[(i32, i32)]) -> i64 {
+    let n = ring.len();
+    (0..n)
+        .map(|i| {
+            let (a, b) = (ring[i], ring[(i + 1) % n]);
+            a.0 as i64 * b.1 as i64 - b.0 as i64 * a.1 as i64
+        })
+        .sum()
+}
+
+/// Geometry commands are a command id and repeat count, followed by zigzag
+/// encoded moves relative to the previous point.
+struct Commands {
+    commands: Vec<u32>,
+    cursor: (i32, i32),
+}
+
+impl Commands {
+    fn new() -> Self {
+        Commands {
+            commands: Vec::new(),
+            cursor: (0, 0),
+        }
+    }
+
+    fn command(&mut self, id: u32, count: usize) {
+        self.commands.push(id | (count as u32) << 3);
+    }
+
+    fn point(&mut self, (x, y): (i32, i32)) {
+        let zigzag = |n: i32| ((n << 1) ^ (n >> 31)) as u32;
+        self.commands.push(zigzag(x - self.cursor.0));
+        self.commands.push(zigzag(y - self.cursor.1));
+        self.cursor = (x, y);
+    }
+}
+
+const MOVE_TO: u32 = 1;
+const LINE_TO: u32 = 2;
+const CLOSE_PATH: u32 = 7;
+
+fn encode_points(points: &[Point]) -> Vec<u32> {
+    let mut commands = Commands::new();
+    commands.command(MOVE_TO, points.len());
+    for point in round(points) {
+        commands.point(point);
+    }
+    commands.commands
+}
+
+fn encode_lines(lines: &[Vec<(i32, i32)>]) -> Vec<u32> {
+    let mut commands = Commands::new();
+    for line in lines {
+        commands.command(MOVE_TO, 1);
+        commands.point(line[0]);
+        commands.command(LINE_TO, line.len() - 1);
+        for &point in &line[1..] {
+            commands.point(point);
+        }
+    }
+    commands.commands
+}
+
+/// Encodes rings, each flagged as an outer ring or a hole. Outer rings are
+/// turned clockwise and holes anticlockwise as the format requires.
+fn encode_rings(rings: &mut [(bool, Vec<(i32, i32)>)]) -> Vec<u32> {
+    let mut commands = Commands::new();
+    for (outer, ring) in rings.iter_mut() {
+        if ring.first() == ring.last() {
+            ring.pop();
+        }
+        if ring.len() < 3 {
+            continue;
+        }
+        if (area(ring) > 0) != *outer {
+            ring.reverse();
+        }
+        commands.command(MOVE_TO, 1);
+        commands.point(ring[0]);
+        commands.command(LINE_TO, ring.len() - 1);
+        for &point in &ring[1..] {
+            commands.point(point);
+        }
+        commands.command(CLOSE_PATH, 1);
+    }
+    commands.commands
+}
+
+/// Encodes a tile as the protobuf message of the vector tile spec.
+fn encode_tile(layers: &TileLayers) -> Vec<u8> {
+    let mut tile = Protobuf::default();
+    for (&name, features) in layers {
+        let mut keys: Vec<&str> = Vec::new();
+        let mut values: Vec<&str> = Vec::new();
+        let mut key_index: HashMap<&str, u32> = HashMap::new();
+        let mut value_index: HashMap<&str, u32> = HashMap::new();
+
+        let mut layer = Protobuf::default();
+        layer.uint(15, 2);
+        layer.string(1, name);
+        for (feature, geometry_type, geometry) in features {
+            let mut tags = Vec::new();
+            for (key, value) in &feature.tags {
+                tags.push(*key_index.entry(key).or_insert_with(|| {
+                    keys.push(key);
+                    keys.len() as u32 - 1
+                }));
+                tags.push(*value_index.entry(value).or_insert_with(|| {
+                    values.push(value);
+                    values.len() as u32 - 1
+                }));
+            }
+            let mut message = Protobuf::default();
+            if feature.id > 0 {
+                message.uint(1, feature.id as u64);
+            }
+            message.packed(2, &tags);
+            message.uint(3, *geometry_type as u64);
+            message.packed(4, geometry);
+            layer.message(2, &message);
+        }
+        for key in keys {
+            layer.string(3, key);
+        }
+        for value in values {
+            let mut message = Protobuf::default();
+            message.string(1, value);
+            layer.message(4, &message);
+        }
+        layer.uint(5, EXTENT as u64);
+        tile.message(3, &layer);
+    }
+    tile.0
+}
+
+/// Just enough of the protobuf wire format for vector tiles.
+#[derive(Default)]
+struct Protobuf(Vec<u8>);
+
+impl Protobuf {
+    fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.0.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.0.push(value as u8);
+    }
+
+    fn uint(&mut self, field: u64, value: u64) {
+        self.varint(field << 3);
+        self.varint(value);
+    }
+
+    fn bytes(&mut self, field: u64, bytes: &[u8]) {
+        self.varint(field << 3 | 2);
+        self.varint(bytes.len() as u64);
+        self.0.extend_from_slice(bytes);
+    }
+
+    fn string(&mut self, field: u64, value: &str) {
+        self.bytes(field, value.as_bytes());
+    }
+
+    fn message(&mut self, field: u64, message: &Protobuf) {
+        self.bytes(field, &message.0);
+    }
+
+    fn packed(&mut self, field: u64, values: &[u32]) {
+        let mut packed = Protobuf::default();
+        for &value in values {
+            packed.varint(value as u64);
+        }
+        self.bytes(field, &packed.0);
+    }
+}
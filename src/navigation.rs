@@ -0,0 +1,215 @@
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::graph::haversine_meters;
+use crate::types::{coord::Coord, edge::Edge, highway::Highway};
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Maneuver {
+    Depart,
+    Continue,
+    SlightLeft,
+    SlightRight,
+    Left,
+    Right,
+    SharpLeft,
+    SharpRight,
+    UTurn,
+    Arrive,
+}
+
+impl Maneuver {
+    /// Classifies a change of heading in degrees, positive being clockwise.
+    pub fn from_turn_angle(angle: f64) -> Self {
+        let magnitude = angle.abs();
+        let right = angle > 0.0;
+        match magnitude {
+            m if m < 20.0 => Maneuver::Continue,
+            m if m < 60.0 => pick(right, Maneuver::SlightRight, Maneuver::SlightLeft),
+            m if m < 120.0 => pick(right, Maneuver::Right, Maneuver::Left),
+            m if m < 170.0 => pick(right, Maneuver::SharpRight, Maneuver::SharpLeft),
+            _ => Maneuver::UTurn,
+        }
+    }
+
+    fn verb(&self) -> &'static str {
+        match self {
+            Maneuver::Depart => "Head",
+            Maneuver::Continue => "Continue",
+            Maneuver::SlightLeft => "Bear left",
+            Maneuver::SlightRight => "Bear right",
+            Maneuver::Left => "Turn left",
+            Maneuver::Right => "Turn right",
+            Maneuver::SharpLeft => "Turn sharp left",
+            Maneuver::SharpRight => "Turn sharp right",
+            Maneuver::UTurn => "Make a U-turn",
+            Maneuver::Arrive => "Arrive at your destination",
+        }
+    }
+}
+
+fn pick(right: bool, if_right: Maneuver, if_left: Maneuver) -> Maneuver {
+    if right {
+        if_right
+    } else {
+        if_left
+    }
+}
+
+/// One step of a route. `distance_m` is how far the maneuver is from the
+/// previous one.
+#[derive(Serialize, Debug, Clone)]
+pub struct Instruction {
+    pub maneuver: Maneuver,
+    /// Name of the street being entered, or the one departed on.
+    pub street: Option<String>,
+    pub distance_m: f64,
+    /// Heading in degrees clockwise from north after the maneuver.
+    pub bearing: f64,
+    pub location: Coord,
+}
+
+impl Instruction {
+    pub fn text(&self) -> String {
+        let distance = format_distance(self.distance_m);
+        match self.maneuver {
+            Maneuver::Depart => {
+                let direction = compass_direction(self.bearing);
+                match &self.street {
+                    Some(street) => format!("Head {} on {}", direction, street),
+                    None => format!("Head {}", direction),
+                }
+            }
+            Maneuver::Arrive => format!("{} in {}", self.maneuver.verb(), distance),
+            maneuver => match &self.street {
+                Some(street) => format!("{} onto {} in {}", maneuver.verb(), street, distance),
+                None => format!("{} in {}", maneuver.verb(), distance),
+            },
+        }
+    }
+}
+
+/// Smallest change of heading, in degrees, announced at a junction when the
+/// route stays on the same street.
+const JUNCTION_TURN_ANGLE: f64 = 40.0;
+
+/// Turns a path from `find_path` into maneuvers. A maneuver is emitted
+/// wherever the route moves onto a different street; unnamed ways count as
+/// different streets when their way id changes. At junctions, nodes with
+/// more than two edges, a sharp enough bend is announced even when the
+/// street stays the same.
+pub fn instructions(
+    path: &[Coord],
+    graph: &HashMap<Coord, Vec<Edge>>,
+    highways: &[Highway],
+) -> Vec<Instruction> {
+    if path.len() < 2 {
+        return Vec::new();
+    }
+    let names: HashMap<i64, Option<&str>> = highways
+        .iter()
+        .map(|highway| (highway.id, highway.name.as_deref()))
+        .collect();
+
+    // The way each segment of the path runs along
+    let ways: Vec<Option<i64>> = path
+        .windows(2)
+        .map(|w| {
+            graph
+                .get(&w[0])?
+                .iter()
+                .filter(|edge| edge.target == w[1])
+                .min_by_key(|edge| edge.cost)
+                .map(|edge| edge.way)
+        })
+        .collect();
+    let street_of = |way: Option<i64>| way.and_then(|way| names.get(&way).copied().flatten());
+    let same_street = |a: Option<i64>, b: Option<i64>| match (street_of(a), street_of(b)) {
+        (Some(a), Some(b)) => a == b,
+        (None, None) => a == b,
+        _ => false,
+    };
+
+    let mut result = vec![Instruction {
+        maneuver: Maneuver::Depart,
+        street: street_of(ways[0]).map(str::to_string),
+        distance_m: 0.0,
+        bearing: bearing(path[0], path[1]),
+        location: path[0],
+    }];
+
+    let mut distance = 0.0;
+    for i in 1..path.len() - 1 {
+        distance += haversine_meters(path[i - 1].lon, path[i - 1].lat, path[i].lon, path[i].lat);
+        let incoming = bearing(path[i - 1], path[i]);
+        let outgoing = bearing(path[i], path[i + 1]);
+        let angle = normalize_angle(outgoing - incoming);
+        let junction = graph.get(&path[i]).is_some_and(|edges| edges.len() > 2);
+        if same_street(ways[i - 1], ways[i]) && !(junction && angle.abs() >= JUNCTION_TURN_ANGLE) {
+            continue;
+        }
+
+        result.push(Instruction {
+            maneuver: Maneuver::from_turn_angle(angle),
+            street: street_of(ways[i]).map(str::to_string),
+            distance_m: distance,
+            bearing: outgoing,
+            location: path[i],
+        });
+        distance = 0.0;
+    }
+
+    let (previous, last) = (path[path.len() - 2], path[path.len() - 1]);
+    distance += haversine_meters(previous.lon, previous.lat, last.lon, last.lat);
+    result.push(Instruction {
+        maneuver: Maneuver::Arrive,
+        street: None,
+        distance_m: distance,
+        bearing: bearing(previous, last),
+        location: last,
+    });
+    result
+}
+
+/// Initial great circle bearing from `a` to `b` in degrees clockwise from north.
+pub fn bearing(a: Coord, b: Coord) -> f64 {
+    let (phi1, phi2) = (a.lat.to_radians(), b.lat.to_radians());
+    let delta_lambda = (b.lon - a.lon).to_radians();
+    let y = delta_lambda.sin() * phi2.cos();
+    let x = phi1.cos() * phi2.sin() - phi1.sin() * phi2.cos() * delta_lambda.cos();
+    (y.atan2(x).to_degrees() + 360.0) % 360.0
+}
+
+/// Wraps an angle into (-180, 180].
+fn normalize_angle(angle: f64) -> f64 {
+    let angle = angle % 360.0;
+    if angle > 180.0 {
+        angle - 360.0
+    } else if angle <= -180.0 {
+        angle + 360.0
+    } else {
+        angle
+    }
+}
+
+fn compass_direction(bearing: f64) -> &'static str {
+    const DIRECTIONS: [&str; 8] = [
+        "north",
+        "northeast",
+        "east",
+        "southeast",
+        "south",
+        "southwest",
+        "west",
+        "northwest",
+    ];
+    DIRECTIONS[((bearing + 22.5) / 45.0) as usize % 8]
+}
+
+fn format_distance(meters: f64) -> String {
+    if meters >= 1000.0 {
+        format!("{:.1} km", meters / 1000.0)
+    } else {
+        format!("{} m", ((meters / 10.0).round() * 10.0) as i64)
+    }
+}
@@ -1,58 +1,133 @@
-use std::{collections::HashMap, ffi::OsStr};
-
-use osmpbfreader::{OsmId, OsmObj, OsmPbfReader, Relation, Way};
-
-use crate::types::cached_data::WayCoords;
-
-pub fn read_osm_data(
-    filename: &OsStr,
-) -> (
-    HashMap<i64, (f64, f64)>,
-    Vec<WayCoords>,
-    Vec<WayCoords>,
-    Vec<WayCoords>,
-    Vec<WayCoords>,
-    Vec<WayCoords>,
-    Vec<WayCoords>,
-    Vec<Vec<WayCoords>>, // Multipolygons
-) {
-    // Check if the file exists
-    let r = std::fs::File::open(std::path::Path::new(filename)).unwrap();
-    let mut pbf = OsmPbfReader::new(r);
-
-    let mut nodes: HashMap<i64, (f64, f64)> = HashMap::new();
-    let mut highways: Vec<WayCoords> = Vec::new();
-    let mut waterways: Vec<WayCoords> = Vec::new();
-    let mut railways: Vec<WayCoords> = Vec::new();
-    let mut buildings: Vec<WayCoords> = Vec::new();
-    let mut naturals: Vec<WayCoords> = Vec::new();
-    let mut aeroways: Vec<WayCoords> = Vec::new();
-    let mut multipolygons: Vec<Vec<WayCoords>> = Vec::new();
-
-    let mut ways: HashMap<i64, Way> = HashMap::new();
+use std::collections::{HashMap, HashSet};
+use std::{ffi::OsStr, path::Path};
+
+use osmpbfreader::{OsmId, OsmObj, OsmPbfReader, Relation, Tags};
+
+use crate::clip::Clip;
+use crate::multipolygon::assemble;
+use crate::osm_xml::OsmXmlReader;
+use crate::poi::poi_kinds;
+use crate::types::area_record::AreaRecord;
+use crate::types::cached_data::{CachedData, WayCoords};
+use crate::types::category::Category;
+use crate::types::coord::Coord;
+use crate::types::highway::Highway;
+use crate::types::place::Place;
+use crate::types::poi::Poi;
+use crate::types::restriction::{RestrictionKind, TurnRestriction};
+use crate::types::way_record::WayRecord;
+
+/// A clip along with the member ways of every multipolygon relation. Ways
+/// come before relations, so these are found in a first pass to know which
+/// ways outside the clip to hold on to until their relations are read.
+pub struct Clipping<'a> {
+    pub clip: &'a Clip,
+    pub relation_ways: HashSet<i64>,
+}
+
+/// Parses an OSM file into categorized ways. Files ending in `.osm` or `.xml`
+/// are read as OSM XML and anything else as PBF. The routing graph is left
+/// empty and is built separately from the highways. With a `clip`, the file
+/// is read twice and only what touches the clipped area is kept, see
+/// [`read_osm_objects`].
+pub fn read_osm_data(filename: &OsStr, clip: Option<&Clip>) -> CachedData {
+    let path = Path::new(filename);
+    match path.extension().and_then(OsStr::to_str) {
+        Some("osm" | "xml") => {
+            let objects = || OsmXmlReader::from_file(path).unwrap().map(Result::unwrap);
+            let clipping = clip.map(|clip| Clipping {
+                clip,
+                relation_ways: relation_ways(objects()),
+            });
+            read_osm_objects(objects(), clipping.as_ref())
+        }
+        _ => {
+            let r = std::fs::File::open(path).unwrap();
+            let mut pbf = OsmPbfReader::new(r);
+            let clipping = clip.map(|clip| {
+                let relation_ways = relation_ways(pbf.par_iter().map(Result::unwrap));
+                pbf.rewind().unwrap();
+                Clipping {
+                    clip,
+                    relation_ways,
+                }
+            });
+            read_osm_objects(pbf.par_iter().map(Result::unwrap), clipping.as_ref())
+        }
+    }
+}
+
+/// Ids of the member ways of multipolygon relations.
+pub fn relation_ways(objects: impl IntoIterator<Item = OsmObj>) -> HashSet<i64> {
+    objects
+        .into_iter()
+        .filter_map(|obj| match obj {
+            OsmObj::Relation(relation) => Some(relation),
+            _ => None,
+        })
+        .filter(|relation| relation.tags.contains("type", "multipolygon"))
+        .flat_map(|relation| {
+            relation
+                .refs
+                .iter()
+                .filter_map(|member| member.member.way())
+                .map(|id| id.0)
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Categorizes OSM objects, which must come as nodes, then ways, then
+/// relations like they do in OSM files.
+///
+/// With a clip, nodes inside it, ways running through it or around it and
+/// relations with such a member or enclosing the clip are kept. Kept ways and
+/// relations stay whole, so roads and areas crossing the edge keep their
+/// nodes and member ways outside it.
+pub fn read_osm_objects(
+    objects: impl IntoIterator<Item = OsmObj>,
+    clipping: Option<&Clipping>,
+) -> CachedData {
+    let clip = clipping.map(|clipping| clipping.clip);
+    let mut data = CachedData::default();
     let mut relations: Vec<Relation> = Vec::new();
+    // Relation members outside the clip, until it is known whether their
+    // relations are kept
+    let mut outside: HashMap<i64, WayRecord> = HashMap::new();
 
-    for obj in pbf.par_iter().map(Result::unwrap) {
+    for obj in objects {
         match obj {
             OsmObj::Node(node) => {
-                nodes.insert(node.id.0, (node.lon(), node.lat()));
+                let (lon, lat) = (node.lon(), node.lat());
+                data.nodes.insert(node.id.0, (lon, lat));
+                if clip.is_none_or(|clip| clip.contains((lon, lat))) {
+                    add_node(&mut data, node.id.0, &node.tags, lon, lat);
+                }
             }
             OsmObj::Way(way) => {
-                ways.insert(way.id.0, way.clone());
-                let way_nodes = extract_way_nodes(&way, &nodes);
-                if way.tags.get("highway").is_some() {
-                    highways.push(way_nodes);
-                } else if way.tags.get("waterway").is_some() {
-                    waterways.push(way_nodes);
-                } else if way.tags.get("railway").is_some() {
-                    railways.push(way_nodes);
-                } else if way.tags.get("building").is_some() {
-                    buildings.push(way_nodes);
-                } else if way.tags.get("natural").is_some() {
-                    naturals.push(way_nodes);
-                } else if way.tags.get("aeroway").is_some() {
-                    aeroways.push(way_nodes);
+                let nodes = way.nodes.iter().map(|node_id| node_id.0).collect();
+                let record = WayRecord {
+                    nodes,
+                    tags: way.tags,
+                    member_only: false,
+                };
+                let coords = record.coords(&data.nodes);
+                if let Some(clipping) = clipping {
+                    if !clipping.clip.intersects(&coords, record.is_closed()) {
+                        if clipping.relation_ways.contains(&way.id.0) {
+                            outside.insert(way.id.0, record);
+                        }
+                        continue;
+                    }
                 }
+                add_way(
+                    &mut data,
+                    way.id.0,
+                    &record.tags,
+                    coords,
+                    record.is_closed(),
+                );
+                data.ways.insert(way.id.0, record);
             }
             OsmObj::Relation(relation) => {
                 relations.push(relation);
@@ -61,35 +136,274 @@ pub fn read_osm_data(
     }
 
     for relation in relations {
+        if let Some(clip) = clip {
+            let touches = relation.refs.iter().any(|member| match member.member {
+                OsmId::Node(id) => data
+                    .nodes
+                    .get(&id.0)
+                    .is_some_and(|&node| clip.contains(node)),
+                OsmId::Way(id) => data.ways.get(&id.0).is_some_and(|way| !way.member_only),
+                OsmId::Relation(_) => false,
+            }) || encloses(&relation, clip, &outside, &data.nodes);
+            if !touches {
+                continue;
+            }
+            for id in relation
+                .refs
+                .iter()
+                .filter_map(|member| member.member.way())
+            {
+                if let Some(mut way) = outside.remove(&id.0) {
+                    way.member_only = true;
+                    data.ways.insert(id.0, way);
+                }
+            }
+        }
+        if let Some(restriction) = extract_restriction(&relation, &data.nodes) {
+            data.restrictions
+                .entry(restriction.via)
+                .or_default()
+                .push(restriction);
+            continue;
+        }
         if relation.tags.get("type") == Some(&smartstring::alias::String::from("multipolygon")) {
+            // Area relations are assembled with their holes once every
+            // relation is read. Anything else is kept as a generic
+            // multipolygon.
+            let tags = &relation.tags;
+            let category = area_category(tags);
+
+            let mut area = AreaRecord {
+                id: relation.id.0,
+                tags: tags.clone(),
+                outer: Vec::new(),
+                inner: Vec::new(),
+            };
             let mut multipolygon_ways: Vec<WayCoords> = Vec::new();
             for member in &relation.refs {
                 if let OsmId::Way(id) = member.member {
-                    if let Some(way) = ways.get(&id.0) {
-                        multipolygon_ways.push(extract_way_nodes(way, &nodes));
+                    if member.role == "inner" {
+                        area.inner.push(id.0);
+                    } else {
+                        area.outer.push(id.0);
+                    }
+                    if let Some(way) = data.ways.get(&id.0) {
+                        multipolygon_ways.push(way.coords(&data.nodes));
                     }
                 }
             }
-            multipolygons.push(multipolygon_ways);
+            if category.is_none() {
+                data.multipolygons.push(multipolygon_ways);
+            }
+            data.areas.push(area);
         }
     }
+    assemble_area_polygons(&mut data);
+
+    if let Some(clip) = clip {
+        let used: HashSet<i64> = data
+            .ways
+            .values()
+            .flat_map(|way| way.nodes.iter().copied())
+            .collect();
+        data.nodes
+            .retain(|id, &mut node| used.contains(id) || clip.contains(node));
+    }
+    data
+}
 
-    (
-        nodes,
-        highways,
-        waterways,
-        railways,
-        buildings,
-        naturals,
-        aeroways,
-        multipolygons,
-    )
+/// Whether a multipolygon relation with all its members outside the clip is
+/// an area around it.
+fn encloses(
+    relation: &Relation,
+    clip: &Clip,
+    outside: &HashMap<i64, WayRecord>,
+    nodes: &HashMap<i64, (f64, f64)>,
+) -> bool {
+    if !relation.tags.contains("type", "multipolygon") {
+        return false;
+    }
+    let area = AreaRecord {
+        id: relation.id.0,
+        tags: relation.tags.clone(),
+        outer: relation
+            .refs
+            .iter()
+            .filter(|member| member.role != "inner")
+            .filter_map(|member| member.member.way())
+            .map(|id| id.0)
+            .collect(),
+        inner: Vec::new(),
+    };
+    assemble(&area, outside, nodes).iter().any(|polygon| {
+        polygon
+            .first()
+            .is_some_and(|outer| clip.intersects(outer, true))
+    })
 }
 
-pub fn extract_way_nodes(way: &Way, nodes: &HashMap<i64, (f64, f64)>) -> WayCoords {
-    way.nodes
+/// Assembles the multipolygon relations that have a layer into polygons with
+/// holes, replacing any assembled before.
+pub fn assemble_area_polygons(data: &mut CachedData) {
+    data.polygons = data
+        .areas
         .iter()
-        .filter_map(|node_id| nodes.get(&node_id.0))
-        .map(|&(lon, lat)| (lon, lat))
-        .collect()
+        .filter_map(|area| Some((area_category(&area.tags)?, area)))
+        .flat_map(|(category, area)| {
+            assemble(area, &data.ways, &data.nodes)
+                .into_iter()
+                .map(move |polygon| (category, polygon))
+        })
+        .collect();
+}
+
+/// Adds a tagged node to the places and POIs it belongs to.
+pub fn add_node(data: &mut CachedData, id: i64, tags: &Tags, lon: f64, lat: f64) {
+    let all_tags = || {
+        tags.iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    };
+    if let (Some(kind), Some(name)) = (tags.get("place"), tags.get("name")) {
+        data.places.push(Place {
+            id,
+            name: name.to_string(),
+            kind: kind.to_string(),
+            tags: all_tags(),
+            lon,
+            lat,
+        });
+    }
+    let kinds = poi_kinds(tags);
+    if !kinds.is_empty() {
+        data.pois.push(Poi {
+            id,
+            kinds,
+            name: tags.get("name").map(|name| name.to_string()),
+            tags: all_tags(),
+            lon,
+            lat,
+        });
+    }
+}
+
+/// Adds a way to the first category its tags match. `closed` ways whose
+/// first and last node are the same may be areas.
+pub fn add_way(data: &mut CachedData, id: i64, tags: &Tags, coords: WayCoords, closed: bool) {
+    match way_category(tags, closed) {
+        Some(Category::Highway) => data.highways.push(Highway {
+            id,
+            highway: tags.get("highway").unwrap().to_string(),
+            name: tags.get("name").map(|name| name.to_string()),
+            coords,
+        }),
+        Some(category) => data.layer_mut(category).unwrap().push(coords),
+        None => {}
+    }
+}
+
+/// The category of a way, if it is drawn at all.
+pub fn way_category(tags: &Tags, closed: bool) -> Option<Category> {
+    if tags.get("highway").is_some() {
+        Some(Category::Highway)
+    } else if closed && is_water_area(tags) {
+        Some(Category::Water)
+    } else if tags.get("waterway").is_some() {
+        Some(Category::Waterway)
+    } else if tags.get("railway").is_some() {
+        Some(Category::Railway)
+    } else if tags.get("building").is_some() {
+        Some(Category::Building)
+    } else if is_park(tags) {
+        Some(Category::Park)
+    } else if tags.get("natural").is_some() {
+        Some(Category::Natural)
+    } else if tags.get("landuse").is_some() {
+        Some(Category::Landuse)
+    } else if tags.get("aeroway").is_some() {
+        Some(Category::Aeroway)
+    } else {
+        None
+    }
+}
+
+/// The category of a multipolygon relation, if it has a layer of its own.
+pub fn area_category(tags: &Tags) -> Option<Category> {
+    if is_water_area(tags) {
+        Some(Category::Water)
+    } else if tags.get("building").is_some() {
+        Some(Category::Building)
+    } else if is_park(tags) {
+        Some(Category::Park)
+    } else if tags.get("natural").is_some() {
+        Some(Category::Natural)
+    } else if tags.get("landuse").is_some() {
+        Some(Category::Landuse)
+    } else {
+        None
+    }
+}
+
+/// Reads a `type=restriction` relation with a from way, via node and to way.
+/// Restrictions with a via way are not supported and are skipped.
+fn extract_restriction(
+    relation: &Relation,
+    nodes: &HashMap<i64, (f64, f64)>,
+) -> Option<TurnRestriction> {
+    if !relation.tags.contains("type", "restriction") {
+        return None;
+    }
+    let kind = RestrictionKind::from_tag(relation.tags.get("restriction")?)?;
+
+    let (mut from_way, mut via, mut to_way) = (None, None, None);
+    for member in &relation.refs {
+        match (member.role.as_str(), member.member) {
+            ("from", OsmId::Way(id)) => from_way = Some(id.0),
+            ("to", OsmId::Way(id)) => to_way = Some(id.0),
+            ("via", OsmId::Node(id)) => {
+                let &(lon, lat) = nodes.get(&id.0)?;
+                via = Some(Coord::new(lon, lat));
+            }
+            _ => {}
+        }
+    }
+
+    Some(TurnRestriction {
+        from_way: from_way?,
+        via: via?,
+        to_way: to_way?,
+        kind,
+    })
+}
+
+/// Closed water bodies, as opposed to waterways drawn as lines.
+fn is_water_area(tags: &Tags) -> bool {
+    tags.contains("natural", "water")
+        || tags.get("water").is_some()
+        || tags.contains("waterway", "riverbank")
+        || tags.contains("waterway", "dock")
+        || tags.contains("landuse", "reservoir")
+        || tags.contains("landuse", "basin")
+}
+
+/// Parks and other green spaces.
+fn is_park(tags: &Tags) -> bool {
+    let leisure = [
+        "park",
+        "garden",
+        "nature_reserve",
+        "recreation_ground",
+        "common",
+    ];
+    let landuse = [
+        "forest",
+        "grass",
+        "meadow",
+        "village_green",
+        "recreation_ground",
+    ];
+    let natural = ["wood", "scrub", "grassland", "heath"];
+    leisure.iter().any(|value| tags.contains("leisure", value))
+        || landuse.iter().any(|value| tags.contains("landuse", value))
+        || natural.iter().any(|value| tags.contains("natural", value))
 }
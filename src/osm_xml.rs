@@ -0,0 +1,197 @@
+use osmpbfreader::{Node, NodeId, OsmId, OsmObj, Ref, Relation, RelationId, Tags, Way, WayId};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use crate::import::attribute;
+
+/// What a change file does with an object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Create,
+    Modify,
+    Delete,
+}
+
+impl Action {
+    fn from_name(name: &[u8]) -> Option<Self> {
+        match name {
+            b"create" => Some(Action::Create),
+            b"modify" => Some(Action::Modify),
+            b"delete" => Some(Action::Delete),
+            _ => None,
+        }
+    }
+}
+
+/// Streams the nodes, ways and relations of an OSM XML file, such as a `.osm`
+/// extract or a JOSM export, as the same objects the PBF reader produces.
+/// Objects JOSM marks as deleted are skipped. The same reader reads `.osc`
+/// change files through [`OsmXmlReader::changes`].
+pub struct OsmXmlReader<R: BufRead> {
+    reader: Reader<R>,
+    buf: Vec<u8>,
+    /// The `<create>`, `<modify>` or `<delete>` block of a change file being read.
+    section: Action,
+    /// The object whose child elements are being read.
+    current: Option<(Action, OsmObj)>,
+}
+
+impl OsmXmlReader<BufReader<File>> {
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        Ok(Self::new(BufReader::new(File::open(path)?)))
+    }
+}
+
+impl<R: BufRead> OsmXmlReader<R> {
+    pub fn new(reader: R) -> Self {
+        OsmXmlReader {
+            reader: Reader::from_reader(reader),
+            buf: Vec::new(),
+            section: Action::Create,
+            current: None,
+        }
+    }
+
+    /// Reads the file as changes, each object with what is done to it.
+    pub fn changes(mut self) -> impl Iterator<Item = Result<(Action, OsmObj), Box<dyn Error>>> {
+        std::iter::from_fn(move || self.next_change())
+    }
+
+    fn next_change(&mut self) -> Option<Result<(Action, OsmObj), Box<dyn Error>>> {
+        loop {
+            self.buf.clear();
+            let event = match self.reader.read_event_into(&mut self.buf) {
+                Ok(event) => event.into_owned(),
+                Err(e) => return Some(Err(e.into())),
+            };
+            let change = match event {
+                Event::Start(e) => self.element(&e, false),
+                Event::Empty(e) => self.element(&e, true),
+                Event::End(e) => match e.local_name().as_ref() {
+                    b"node" | b"way" | b"relation" => Ok(self.current.take()),
+                    name if Action::from_name(name).is_some() => {
+                        self.section = Action::Create;
+                        Ok(None)
+                    }
+                    _ => Ok(None),
+                },
+                Event::Eof => return None,
+                _ => Ok(None),
+            };
+            match change {
+                Ok(Some(change)) => return Some(Ok(change)),
+                Ok(None) => {}
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+
+    /// Handles one element, returning an object once it is complete.
+    fn element(
+        &mut self,
+        e: &BytesStart,
+        empty: bool,
+    ) -> Result<Option<(Action, OsmObj)>, Box<dyn Error>> {
+        if let Some(section) = Action::from_name(e.local_name().as_ref()) {
+            self.section = section;
+            return Ok(None);
+        }
+        // JOSM marks edited objects with an action attribute instead
+        let action = match e.try_get_attribute(b"action")? {
+            Some(action) => Action::from_name(action.value.as_ref()).unwrap_or(self.section),
+            None => self.section,
+        };
+
+        let obj = match e.local_name().as_ref() {
+            // Deleted nodes in change files need not have a position
+            b"node" if action == Action::Delete => OsmObj::Node(Node {
+                id: NodeId(attribute(e, b"id")?),
+                tags: Tags::new(),
+                decimicro_lat: 0,
+                decimicro_lon: 0,
+            }),
+            b"node" => OsmObj::Node(Node {
+                id: NodeId(attribute(e, b"id")?),
+                tags: Tags::new(),
+                decimicro_lat: decimicro(attribute(e, b"lat")?),
+                decimicro_lon: decimicro(attribute(e, b"lon")?),
+            }),
+            b"way" => OsmObj::Way(Way {
+                id: WayId(attribute(e, b"id")?),
+                tags: Tags::new(),
+                nodes: Vec::new(),
+            }),
+            b"relation" => OsmObj::Relation(Relation {
+                id: RelationId(attribute(e, b"id")?),
+                tags: Tags::new(),
+                refs: Vec::new(),
+            }),
+            b"tag" => {
+                let tags = match &mut self.current {
+                    Some((_, OsmObj::Node(node))) => &mut node.tags,
+                    Some((_, OsmObj::Way(way))) => &mut way.tags,
+                    Some((_, OsmObj::Relation(relation))) => &mut relation.tags,
+                    None => return Ok(None),
+                };
+                let key: String = attribute(e, b"k")?;
+                let value: String = attribute(e, b"v")?;
+                tags.insert(key.into(), value.into());
+                return Ok(None);
+            }
+            b"nd" => {
+                if let Some((_, OsmObj::Way(way))) = &mut self.current {
+                    way.nodes.push(NodeId(attribute(e, b"ref")?));
+                }
+                return Ok(None);
+            }
+            b"member" => {
+                if let Some((_, OsmObj::Relation(relation))) = &mut self.current {
+                    let id = attribute(e, b"ref")?;
+                    let member = match attribute::<String>(e, b"type")?.as_str() {
+                        "node" => OsmId::Node(NodeId(id)),
+                        "way" => OsmId::Way(WayId(id)),
+                        "relation" => OsmId::Relation(RelationId(id)),
+                        other => return Err(format!("Unknown member type {}", other).into()),
+                    };
+                    let role: String = attribute(e, b"role")?;
+                    relation.refs.push(Ref {
+                        member,
+                        role: role.into(),
+                    });
+                }
+                return Ok(None);
+            }
+            _ => return Ok(None),
+        };
+
+        if empty {
+            Ok(Some((action, obj)))
+        } else {
+            self.current = Some((action, obj));
+            Ok(None)
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for OsmXmlReader<R> {
+    type Item = Result<OsmObj, Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.next_change()? {
+                Ok((Action::Delete, _)) => {}
+                Ok((_, obj)) => return Some(Ok(obj)),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+/// Degrees in the 10⁻⁷ degree units the PBF format stores.
+fn decimicro(degrees: f64) -> i32 {
+    (degrees * 1e7).round() as i32
+}
@@ -0,0 +1,408 @@
+use ab_glyph::{FontRef, PxScale};
+use image::{Rgba, RgbaImage};
+use std::collections::BTreeSet;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::drawing::{data_bounding_box, draw_layers, DrawTarget, Projection, RenderOptions};
+use crate::labels::{LabelTarget, Labeler, PlacedGlyph};
+use crate::types::cached_data::CachedData;
+
+const POINTS_PER_MM: f64 = 72.0 / 25.4;
+const MARGIN_MM: f64 = 10.0;
+const METERS_PER_DEGREE_LAT: f64 = 110_574.0;
+const METERS_PER_DEGREE_LON_AT_EQUATOR: f64 = 111_320.0;
+// Line widths and text sizes in the layer drawing are tuned for 4096px raster images.
+const STROKE_SCALE: f64 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaperSize {
+    A4,
+    A3,
+    Letter,
+}
+
+impl PaperSize {
+    /// Portrait width and height in millimetres.
+    pub fn dimensions_mm(&self) -> (f64, f64) {
+        match self {
+            PaperSize::A4 => (210.0, 297.0),
+            PaperSize::A3 => (297.0, 420.0),
+            PaperSize::Letter => (215.9, 279.4),
+        }
+    }
+}
+
+impl FromStr for PaperSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "a4" => Ok(PaperSize::A4),
+            "a3" => Ok(PaperSize::A3),
+            "letter" => Ok(PaperSize::Letter),
+            _ => Err(format!("Unknown paper size: {}", s)),
+        }
+    }
+}
+
+/// Parses a map scale written as `1:10000` (or just `10000`) into its denominator.
+pub fn parse_scale(scale: &str) -> Option<f64> {
+    let denominator = scale.strip_prefix("1:").unwrap_or(scale);
+    denominator.parse().ok().filter(|&d: &f64| d > 0.0)
+}
+
+/// Writes a single page PDF of the map at the given paper size and scale,
+/// centered on the data. The map is drawn as vector paths with the same
+/// layers and labels as `draw_map`, plus a scale bar and a north arrow.
+pub fn write_pdf(
+    data: &CachedData,
+    path: &[(f64, f64)],
+    options: &RenderOptions,
+    paper: PaperSize,
+    scale: f64,
+    output_file: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (page_w_mm, page_h_mm) = paper.dimensions_mm();
+    let (frame_w_mm, frame_h_mm) = (page_w_mm - 2.0 * MARGIN_MM, page_h_mm - 2.0 * MARGIN_MM);
+
+    let (min_lon, min_lat, max_lon, max_lat) = data_bounding_box(data);
+    let (center_lon, center_lat) = ((min_lon + max_lon) / 2.0, (min_lat + max_lat) / 2.0);
+
+    // Ground size of the map frame in meters at the requested scale
+    let ground_w = frame_w_mm / 1000.0 * scale;
+    let ground_h = frame_h_mm / 1000.0 * scale;
+    let lon_span = ground_w / (METERS_PER_DEGREE_LON_AT_EQUATOR * center_lat.to_radians().cos());
+    let lat_span = ground_h / METERS_PER_DEGREE_LAT;
+
+    let frame = Frame {
+        x: MARGIN_MM * POINTS_PER_MM,
+        y: MARGIN_MM * POINTS_PER_MM,
+        width: frame_w_mm * POINTS_PER_MM,
+        height: frame_h_mm * POINTS_PER_MM,
+    };
+    let projection = Projection::new(
+        (
+            center_lon - lon_span / 2.0,
+            center_lat - lat_span / 2.0,
+            center_lon + lon_span / 2.0,
+            center_lat + lat_span / 2.0,
+        ),
+        frame.width,
+        frame.height,
+    );
+
+    let mut canvas = PdfCanvas::new(frame);
+    canvas.begin_map();
+    draw_layers(&mut canvas, data, &projection, path, options);
+    if options.labels {
+        Labeler::with_scale(STROKE_SCALE).draw_labels(&mut canvas, data, &projection);
+    }
+    canvas.end_map();
+    canvas.scale_bar(scale);
+    canvas.north_arrow();
+
+    let page = (page_w_mm * POINTS_PER_MM, page_h_mm * POINTS_PER_MM);
+    let fout = &mut BufWriter::new(File::create(Path::new(output_file))?);
+    canvas.write_document(fout, page)?;
+    Ok(())
+}
+
+/// Map frame on the page, in PDF points from the bottom left corner.
+#[derive(Debug, Clone, Copy)]
+struct Frame {
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+}
+
+struct PdfCanvas {
+    frame: Frame,
+    content: String,
+    alphas: BTreeSet<u8>,
+}
+
+impl PdfCanvas {
+    fn new(frame: Frame) -> Self {
+        PdfCanvas {
+            frame,
+            content: String::new(),
+            alphas: BTreeSet::new(),
+        }
+    }
+
+    /// Converts surface coordinates (origin top left of the frame) to page coordinates.
+    fn to_page(&self, x: f64, y: f64) -> (f64, f64) {
+        (self.frame.x + x, self.frame.y + self.frame.height - y)
+    }
+
+    fn set_fill(&mut self, color: Rgba<u8>) {
+        self.alphas.insert(color[3]);
+        let _ = writeln!(
+            self.content,
+            "/GS{} gs {:.3} {:.3} {:.3} rg",
+            color[3],
+            color[0] as f64 / 255.0,
+            color[1] as f64 / 255.0,
+            color[2] as f64 / 255.0
+        );
+    }
+
+    fn set_stroke(&mut self, color: Rgba<u8>, width: f64) {
+        self.alphas.insert(color[3]);
+        let _ = writeln!(
+            self.content,
+            "/GS{} gs {:.3} {:.3} {:.3} RG {:.2} w",
+            color[3],
+            color[0] as f64 / 255.0,
+            color[1] as f64 / 255.0,
+            color[2] as f64 / 255.0,
+            width
+        );
+    }
+
+    fn path(&mut self, points: &[(f64, f64)]) {
+        for (i, &(x, y)) in points.iter().enumerate() {
+            let op = if i == 0 { "m" } else { "l" };
+            let _ = writeln!(self.content, "{:.2} {:.2} {}", x, y, op);
+        }
+    }
+
+    fn rect(&mut self, x: f64, y: f64, w: f64, h: f64, op: &str) {
+        let _ = writeln!(
+            self.content,
+            "{:.2} {:.2} {:.2} {:.2} re {}",
+            x, y, w, h, op
+        );
+    }
+
+    fn text(&mut self, x: f64, y: f64, size: f64, text: &str) {
+        let escaped = text
+            .replace('\\', "\\\\")
+            .replace('(', "\\(")
+            .replace(')', "\\)");
+        let _ = writeln!(
+            self.content,
+            "BT /F1 {:.1} Tf {:.2} {:.2} Td ({}) Tj ET",
+            size, x, y, escaped
+        );
+    }
+
+    /// Paints the map background and clips everything drawn until `end_map` to the frame.
+    fn begin_map(&mut self) {
+        let Frame {
+            x,
+            y,
+            width,
+            height,
+        } = self.frame;
+        self.content.push_str("q 1 J 1 j\n");
+        self.rect(x, y, width, height, "W n");
+        self.set_fill(Rgba([242, 239, 233, 255]));
+        self.rect(x, y, width, height, "f");
+    }
+
+    fn end_map(&mut self) {
+        let Frame {
+            x,
+            y,
+            width,
+            height,
+        } = self.frame;
+        self.content.push_str("Q\n");
+        self.set_stroke(Rgba([0, 0, 0, 255]), 0.8);
+        self.rect(x, y, width, height, "S");
+    }
+
+    /// Draws a four segment scale bar with a round length in the bottom left of the frame.
+    fn scale_bar(&mut self, scale: f64) {
+        let meters_per_point = scale / POINTS_PER_MM / 1000.0;
+        let target = self.frame.width / 4.0 * meters_per_point;
+        let magnitude = 10f64.powf(target.log10().floor());
+        let length_m = [5.0, 2.0, 1.0]
+            .iter()
+            .map(|step| step * magnitude)
+            .find(|&length| length <= target)
+            .unwrap_or(magnitude);
+        let length_pt = length_m / meters_per_point;
+
+        let x = self.frame.x + 6.0 * POINTS_PER_MM;
+        let y = self.frame.y + 8.0 * POINTS_PER_MM;
+        let bar_h = 4.0;
+
+        self.set_fill(Rgba([255, 255, 255, 200]));
+        self.rect(x - 6.0, y - 16.0, length_pt + 42.0, 36.0, "f");
+
+        let segment = length_pt / 4.0;
+        for i in 0..4 {
+            let color = if i % 2 == 0 { 0 } else { 255 };
+            self.set_fill(Rgba([color, color, color, 255]));
+            self.rect(x + i as f64 * segment, y, segment, bar_h, "f");
+        }
+        self.set_stroke(Rgba([0, 0, 0, 255]), 0.5);
+        self.rect(x, y, length_pt, bar_h, "S");
+
+        let label = if length_m >= 1000.0 {
+            format!("{} km", length_m / 1000.0)
+        } else {
+            format!("{} m", length_m)
+        };
+        self.set_fill(Rgba([0, 0, 0, 255]));
+        self.text(x - 2.0, y + bar_h + 3.0, 7.0, "0");
+        self.text(x + length_pt - 4.0, y + bar_h + 3.0, 7.0, &label);
+        self.text(x, y - 10.0, 7.0, &format!("Scale 1:{}", scale));
+    }
+
+    /// Draws a north arrow in the top right of the frame. The map is always north up.
+    fn north_arrow(&mut self) {
+        let cx = self.frame.x + self.frame.width - 12.0 * POINTS_PER_MM;
+        let top = self.frame.y + self.frame.height - 8.0 * POINTS_PER_MM;
+        let (half_w, h) = (7.0, 24.0);
+
+        self.set_fill(Rgba([255, 255, 255, 200]));
+        self.rect(cx - 12.0, top - h - 6.0, 24.0, h + 20.0, "f");
+
+        self.set_fill(Rgba([0, 0, 0, 255]));
+        self.path(&[(cx, top), (cx - half_w, top - h), (cx, top - h * 0.7)]);
+        self.content.push_str("h f\n");
+        self.set_stroke(Rgba([0, 0, 0, 255]), 0.8);
+        self.path(&[
+            (cx, top),
+            (cx + half_w, top - h),
+            (cx, top - h * 0.7),
+            (cx - half_w, top - h),
+        ]);
+        self.content.push_str("h S\n");
+        self.text(cx - 3.5, top + 3.0, 10.0, "N");
+    }
+
+    fn write_document(
+        &self,
+        out: &mut impl Write,
+        (page_w, page_h): (f64, f64),
+    ) -> std::io::Result<()> {
+        let ext_g_states: String = self
+            .alphas
+            .iter()
+            .map(|&a| {
+                let alpha = a as f64 / 255.0;
+                format!("/GS{} << /ca {:.3} /CA {:.3} >> ", a, alpha, alpha)
+            })
+            .collect();
+
+        let objects = [
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+            format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {:.2} {:.2}] \
+                 /Resources << /Font << /F1 5 0 R >> /ExtGState << {}>> >> /Contents 4 0 R >>",
+                page_w, page_h, ext_g_states
+            ),
+            format!(
+                "<< /Length {} >>\nstream\n{}endstream",
+                self.content.len(),
+                self.content
+            ),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>".to_string(),
+        ];
+
+        let mut offset = 0;
+        let mut offsets = Vec::with_capacity(objects.len());
+        let header = "%PDF-1.4\n";
+        out.write_all(header.as_bytes())?;
+        offset += header.len();
+
+        for (i, object) in objects.iter().enumerate() {
+            offsets.push(offset);
+            let body = format!("{} 0 obj\n{}\nendobj\n", i + 1, object);
+            out.write_all(body.as_bytes())?;
+            offset += body.len();
+        }
+
+        write!(out, "xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1)?;
+        for offset in offsets {
+            writeln!(out, "{:010} 00000 n ", offset)?;
+        }
+        write!(
+            out,
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 1,
+            offset
+        )?;
+        out.flush()
+    }
+}
+
+impl DrawTarget for PdfCanvas {
+    fn fill_polygon(&mut self, points: &[(f64, f64)], color: Rgba<u8>) {
+        if points.len() < 3 {
+            return;
+        }
+        let page_points: Vec<_> = points.iter().map(|&(x, y)| self.to_page(x, y)).collect();
+        self.set_fill(color);
+        self.path(&page_points);
+        self.content.push_str("h f\n");
+    }
+
+    fn stroke_line(&mut self, points: &[(f64, f64)], color: Rgba<u8>, width: f64) {
+        if points.len() < 2 {
+            return;
+        }
+        let page_points: Vec<_> = points.iter().map(|&(x, y)| self.to_page(x, y)).collect();
+        self.set_stroke(color, width * STROKE_SCALE);
+        self.path(&page_points);
+        self.content.push_str("S\n");
+    }
+
+    fn fill_rings(&mut self, rings: &[Vec<(f64, f64)>], color: Rgba<u8>) {
+        self.set_fill(color);
+        for ring in rings.iter().filter(|ring| ring.len() >= 3) {
+            let page_points: Vec<_> = ring.iter().map(|&(x, y)| self.to_page(x, y)).collect();
+            self.path(&page_points);
+            self.content.push_str("h\n");
+        }
+        // Even-odd, so holes stay open whichever way their rings wind
+        self.content.push_str("f*\n");
+    }
+
+    // Rasters are left out so the PDF stays vector only. Contours still show
+    // the terrain.
+    fn blend_image(&mut self, _image: &RgbaImage) {}
+}
+
+impl LabelTarget for PdfCanvas {
+    fn size(&self) -> (f64, f64) {
+        (self.frame.width, self.frame.height)
+    }
+
+    // Glyphs are drawn as their outlines, so text stays sharp at any zoom
+    fn draw_glyph(
+        &mut self,
+        font: &FontRef,
+        scale: PxScale,
+        glyph: &PlacedGlyph,
+        color: Rgba<u8>,
+        halo: f64,
+    ) {
+        let contours = glyph.contours(font, scale);
+        if contours.is_empty() {
+            return;
+        }
+        if halo > 0.0 {
+            self.set_stroke(color, halo * 2.0);
+        } else {
+            self.set_fill(color);
+        }
+        for contour in contours {
+            let page_points: Vec<_> = contour.iter().map(|&(x, y)| self.to_page(x, y)).collect();
+            self.path(&page_points);
+            self.content.push_str("h\n");
+        }
+        self.content
+            .push_str(if halo > 0.0 { "S\n" } else { "f\n" });
+    }
+}
@@ -0,0 +1,178 @@
+use image::Rgba;
+use osmpbfreader::Tags;
+
+use crate::types::poi::Poi;
+
+/// Tag keys that turn a node into a POI, in order of precedence.
+pub const POI_KEYS: [&str; 8] = [
+    "amenity",
+    "shop",
+    "tourism",
+    "leisure",
+    "historic",
+    "railway",
+    "public_transport",
+    "highway",
+];
+
+/// Returns the tags a node should be classified by, empty if it is not a POI
+/// at all. `railway` and `highway` are only taken for point features like
+/// stations and traffic signals since those keys are mostly used on ways.
+pub fn poi_kinds(tags: &Tags) -> Vec<(String, String)> {
+    POI_KEYS
+        .iter()
+        .filter_map(|&key| {
+            let value = tags.get(key)?;
+            let is_point = match key {
+                "railway" => matches!(value.as_str(), "station" | "halt" | "tram_stop"),
+                "highway" => matches!(
+                    value.as_str(),
+                    "traffic_signals" | "bus_stop" | "crossing" | "stop"
+                ),
+                _ => true,
+            };
+            is_point.then(|| (key.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// Returns the POIs tagged `key=value` inside the bounding box, whichever of
+/// their kinds it is. A `value` of `None` matches any value, so
+/// `("amenity", None)` gives every amenity.
+pub fn pois_in_bbox<'a>(
+    pois: &'a [Poi],
+    key: &str,
+    value: Option<&str>,
+    (min_lon, min_lat, max_lon, max_lat): (f64, f64, f64, f64),
+) -> Vec<&'a Poi> {
+    pois.iter()
+        .filter(|poi| poi.has_kind(key, value))
+        .filter(|poi| {
+            poi.lon >= min_lon && poi.lon <= max_lon && poi.lat >= min_lat && poi.lat <= max_lat
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolShape {
+    Circle,
+    Square,
+    Triangle,
+    Diamond,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Symbol {
+    pub shape: SymbolShape,
+    pub color: Rgba<u8>,
+    /// Radius in pixels of the rendered image.
+    pub size: f64,
+}
+
+impl Symbol {
+    /// Outline of the symbol centered on `(x, y)`.
+    pub fn outline(&self, x: f64, y: f64) -> Vec<(f64, f64)> {
+        let r = self.size;
+        match self.shape {
+            SymbolShape::Circle => (0..16)
+                .map(|i| {
+                    let a = i as f64 * std::f64::consts::TAU / 16.0;
+                    (x + r * a.cos(), y + r * a.sin())
+                })
+                .collect(),
+            SymbolShape::Square => vec![
+                (x - r, y - r),
+                (x + r, y - r),
+                (x + r, y + r),
+                (x - r, y + r),
+            ],
+            SymbolShape::Triangle => vec![(x, y - r), (x + r, y + r), (x - r, y + r)],
+            SymbolShape::Diamond => vec![(x, y - r), (x + r, y), (x, y + r), (x - r, y)],
+        }
+    }
+}
+
+/// Which symbol each kind of POI is drawn with. Rules are matched in order
+/// against all of a POI's kinds, and a rule without a value matches every
+/// value of its key. POIs that match no rule are drawn with `fallback`, or
+/// not at all if it is `None`.
+#[derive(Debug, Clone)]
+pub struct PoiStyles {
+    pub rules: Vec<(String, Option<String>, Symbol)>,
+    pub fallback: Option<Symbol>,
+}
+
+impl PoiStyles {
+    pub fn symbol_for(&self, poi: &Poi) -> Option<Symbol> {
+        self.rules
+            .iter()
+            .find(|(key, value, _)| poi.has_kind(key, value.as_deref()))
+            .map(|&(_, _, symbol)| symbol)
+            .or(self.fallback)
+    }
+}
+
+impl Default for PoiStyles {
+    fn default() -> Self {
+        let rule = |key: &str, value: Option<&str>, shape, color| {
+            (
+                key.to_string(),
+                value.map(str::to_string),
+                Symbol {
+                    shape,
+                    color: Rgba(color),
+                    size: 6.0,
+                },
+            )
+        };
+        PoiStyles {
+            rules: vec![
+                rule(
+                    "amenity",
+                    Some("hospital"),
+                    SymbolShape::Square,
+                    [220, 0, 0, 255],
+                ),
+                rule(
+                    "amenity",
+                    Some("pharmacy"),
+                    SymbolShape::Square,
+                    [0, 160, 0, 255],
+                ),
+                rule(
+                    "amenity",
+                    Some("school"),
+                    SymbolShape::Triangle,
+                    [200, 140, 0, 255],
+                ),
+                rule("amenity", None, SymbolShape::Circle, [150, 80, 200, 255]),
+                rule("shop", None, SymbolShape::Circle, [170, 90, 160, 255]),
+                rule("tourism", None, SymbolShape::Diamond, [0, 120, 220, 255]),
+                rule("railway", None, SymbolShape::Square, [255, 0, 0, 255]),
+                rule(
+                    "public_transport",
+                    None,
+                    SymbolShape::Square,
+                    [0, 90, 200, 255],
+                ),
+                rule(
+                    "highway",
+                    Some("traffic_signals"),
+                    SymbolShape::Circle,
+                    [255, 200, 0, 255],
+                ),
+                rule(
+                    "highway",
+                    Some("bus_stop"),
+                    SymbolShape::Square,
+                    [0, 90, 200, 255],
+                ),
+            ],
+            fallback: Some(Symbol {
+                shape: SymbolShape::Circle,
+                color: Rgba([120, 120, 120, 255]),
+                size: 4.0,
+            }),
+        }
+    }
+}
@@ -0,0 +1,262 @@
+use std::cell::Cell;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use crate::graph::{astar_path, reverse_graph, straight_line_cost, turn_allowed};
+use crate::landmarks::Landmarks;
+use crate::types::restriction::TurnRestriction;
+use crate::types::{coord::Coord, edge::Edge};
+
+/// How a `Router` searches for a path. All strategies find the same cost.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    /// A* towards the goal with the straight line distance as heuristic.
+    AStar,
+    /// A* from both ends at once, meeting in the middle. Uses the landmark
+    /// bounds as well when the router has them.
+    Bidirectional,
+    /// A* with landmark lower bounds, falling back to the straight line
+    /// where they are weaker.
+    Alt,
+}
+
+impl Strategy {
+    pub const ALL: [Strategy; 3] = [Strategy::AStar, Strategy::Bidirectional, Strategy::Alt];
+}
+
+impl FromStr for Strategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "astar" => Ok(Strategy::AStar),
+            "bidirectional" => Ok(Strategy::Bidirectional),
+            "alt" => Ok(Strategy::Alt),
+            _ => Err(format!(
+                "Unknown strategy {}, expected astar, bidirectional or alt.",
+                s
+            )),
+        }
+    }
+}
+
+/// Answers path queries over one graph with any `Strategy`. Turn
+/// restrictions are obeyed by every strategy.
+pub struct Router<'a> {
+    graph: &'a HashMap<Coord, Vec<Edge>>,
+    restrictions: &'a HashMap<Coord, Vec<TurnRestriction>>,
+    /// Built on the first bidirectional search, the others do not need it.
+    reverse: OnceLock<HashMap<Coord, Vec<Edge>>>,
+    landmarks: Option<&'a Landmarks>,
+}
+
+/// A search state: a node and the way the route arrived by (forward) or
+/// leaves by (backward).
+type State = (Coord, Option<i64>);
+
+impl<'a> Router<'a> {
+    pub fn new(
+        graph: &'a HashMap<Coord, Vec<Edge>>,
+        restrictions: &'a HashMap<Coord, Vec<TurnRestriction>>,
+        landmarks: Option<&'a Landmarks>,
+    ) -> Self {
+        Router {
+            graph,
+            restrictions,
+            reverse: OnceLock::new(),
+            landmarks,
+        }
+    }
+
+    /// Finds the cheapest path and its cost, along with the number of search
+    /// states expanded on the way.
+    pub fn find_path(
+        &self,
+        start: Coord,
+        goal: Coord,
+        strategy: Strategy,
+    ) -> (Option<(Vec<Coord>, i64)>, usize) {
+        let expanded = Cell::new(0);
+        let path = match strategy {
+            Strategy::AStar => {
+                let heuristic = |node| straight_line_cost(node, goal);
+                astar_path(
+                    self.graph,
+                    self.restrictions,
+                    start,
+                    goal,
+                    heuristic,
+                    |_, edge: &Edge| edge.cost,
+                    &expanded,
+                )
+            }
+            Strategy::Alt => {
+                let heuristic = |node| self.lower_bound(node, goal);
+                astar_path(
+                    self.graph,
+                    self.restrictions,
+                    start,
+                    goal,
+                    heuristic,
+                    |_, edge: &Edge| edge.cost,
+                    &expanded,
+                )
+            }
+            Strategy::Bidirectional => self.bidirectional(start, goal, &expanded),
+        };
+        (path, expanded.get())
+    }
+
+    fn lower_bound(&self, a: Coord, b: Coord) -> i64 {
+        let landmarks = self
+            .landmarks
+            .map_or(0, |landmarks| landmarks.lower_bound(a, b));
+        straight_line_cost(a, b).max(landmarks)
+    }
+
+    /// Bidirectional A* with the average of the forward and backward
+    /// potentials, which keeps both searches consistent so they can stop as
+    /// soon as their frontiers together cannot beat the best meeting found.
+    fn bidirectional(
+        &self,
+        start: Coord,
+        goal: Coord,
+        expanded: &Cell<usize>,
+    ) -> Option<(Vec<Coord>, i64)> {
+        if start == goal {
+            return Some((vec![start], 0));
+        }
+        let reverse = self.reverse.get_or_init(|| reverse_graph(self.graph));
+        // Doubled so that halving the potentials stays in integers
+        let potential = |node| self.lower_bound(node, goal) - self.lower_bound(start, node);
+        let mut forward = Frontier::new((start, None), potential(start));
+        let mut backward = Frontier::new((goal, None), -potential(goal));
+        // Best cost found so far, with the states where the searches met
+        let mut best: Option<(i64, State, State)> = None;
+
+        while let (Some(forward_key), Some(backward_key)) = (forward.top_key(), backward.top_key())
+        {
+            if best.is_some_and(|(cost, _, _)| forward_key + backward_key >= 2 * cost) {
+                break;
+            }
+            if forward_key <= backward_key {
+                let Some(((node, incoming), cost)) = forward.pop() else {
+                    continue;
+                };
+                expanded.set(expanded.get() + 1);
+                for edge in self.graph.get(&node).into_iter().flatten() {
+                    if !turn_allowed(self.restrictions, node, incoming, edge.way) {
+                        continue;
+                    }
+                    let next = (edge.target, Some(edge.way));
+                    let next_cost = cost + edge.cost;
+                    if !forward.relax(next, next_cost, (node, incoming), potential(edge.target)) {
+                        continue;
+                    }
+                    // Meet any backward state leaving this node by an allowed way
+                    let leaving = self.graph.get(&edge.target).into_iter().flatten();
+                    for outgoing in leaving.map(|edge| Some(edge.way)).chain([None]) {
+                        let Some(&rest) = backward.costs.get(&(edge.target, outgoing)) else {
+                            continue;
+                        };
+                        let allowed = outgoing.is_none_or(|outgoing| {
+                            turn_allowed(self.restrictions, edge.target, next.1, outgoing)
+                        });
+                        if allowed && best.is_none_or(|(cost, _, _)| next_cost + rest < cost) {
+                            best = Some((next_cost + rest, next, (edge.target, outgoing)));
+                        }
+                    }
+                }
+            } else {
+                let Some(((node, outgoing), cost)) = backward.pop() else {
+                    continue;
+                };
+                expanded.set(expanded.get() + 1);
+                // Each reverse edge is a real edge from `edge.target` to `node`
+                for edge in reverse.get(&node).into_iter().flatten() {
+                    let allowed = outgoing.is_none_or(|outgoing| {
+                        turn_allowed(self.restrictions, node, Some(edge.way), outgoing)
+                    });
+                    if !allowed {
+                        continue;
+                    }
+                    let next = (edge.target, Some(edge.way));
+                    let next_cost = cost + edge.cost;
+                    if !backward.relax(next, next_cost, (node, outgoing), -potential(edge.target)) {
+                        continue;
+                    }
+                    // Meet any forward state that arrived here and may turn onto this way
+                    let arriving = reverse.get(&edge.target).into_iter().flatten();
+                    for incoming in arriving.map(|edge| Some(edge.way)).chain([None]) {
+                        let Some(&before) = forward.costs.get(&(edge.target, incoming)) else {
+                            continue;
+                        };
+                        let allowed =
+                            turn_allowed(self.restrictions, edge.target, incoming, edge.way);
+                        if allowed && best.is_none_or(|(cost, _, _)| before + next_cost < cost) {
+                            best = Some((before + next_cost, (edge.target, incoming), next));
+                        }
+                    }
+                }
+            }
+        }
+
+        let (cost, forward_meeting, backward_meeting) = best?;
+        let mut path = forward.trace(forward_meeting);
+        path.reverse();
+        path.extend(backward.trace(backward_meeting).into_iter().skip(1));
+        Some((path, cost))
+    }
+}
+
+/// One side of a bidirectional search. Queue keys are twice the cost so far
+/// plus the potential of the node.
+struct Frontier {
+    costs: HashMap<State, i64>,
+    parents: HashMap<State, State>,
+    queue: BinaryHeap<Reverse<(i64, i64, State)>>,
+}
+
+impl Frontier {
+    fn new(origin: State, potential: i64) -> Self {
+        Frontier {
+            costs: HashMap::from([(origin, 0)]),
+            parents: HashMap::new(),
+            queue: BinaryHeap::from([Reverse((potential, 0, origin))]),
+        }
+    }
+
+    fn top_key(&self) -> Option<i64> {
+        self.queue.peek().map(|Reverse((key, _, _))| *key)
+    }
+
+    /// Takes the state with the lowest key, or `None` if it was outdated.
+    fn pop(&mut self) -> Option<(State, i64)> {
+        let Reverse((_, cost, state)) = self.queue.pop()?;
+        (self.costs.get(&state) == Some(&cost)).then_some((state, cost))
+    }
+
+    /// Records a cheaper way to reach `state`, returning whether it was one.
+    fn relax(&mut self, state: State, cost: i64, parent: State, potential: i64) -> bool {
+        if self.costs.get(&state).is_some_and(|&best| best <= cost) {
+            return false;
+        }
+        self.costs.insert(state, cost);
+        self.parents.insert(state, parent);
+        self.queue
+            .push(Reverse((2 * cost + potential, cost, state)));
+        true
+    }
+
+    /// Nodes from `state` back to the origin of this side.
+    fn trace(&self, mut state: State) -> Vec<Coord> {
+        let mut nodes = vec![state.0];
+        while let Some(&parent) = self.parents.get(&state) {
+            nodes.push(parent.0);
+            state = parent;
+        }
+        nodes
+    }
+}
@@ -0,0 +1,405 @@
+use image::{ImageFormat, RgbaImage};
+use lru::LruCache;
+use std::f64::consts::PI;
+use std::io::Cursor;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use threadpool::ThreadPool;
+use tiny_http::{Header, Request, Response, Server};
+
+use crate::drawing::{calculate_bounding_box, render_image, Projection, RenderOptions};
+use crate::export::route_to_geojson;
+use crate::graph::{find_path, nearest_node_in};
+use crate::types::cached_data::{CachedData, WayCoords};
+use crate::utils::{parse_bbox, parse_coord};
+
+const TILE_SIZE: u32 = 256;
+/// Rendered tiles kept in memory.
+pub const TILE_CACHE_SIZE: usize = 1024;
+const MAX_ZOOM: u32 = 22;
+const MAX_IMAGE_SIZE: u32 = 4096;
+/// Share of a tile's size around it whose features are still drawn, so that
+/// lines, symbols and labels crossing the tile edge are not cut off.
+const TILE_MARGIN: f64 = 0.25;
+
+/// `(min_lon, min_lat, max_lon, max_lat)`
+type Bounds = (f64, f64, f64, f64);
+/// Encoded PNGs by zoom, x and y.
+type TileCache = LruCache<(u32, u32, u32), Vec<u8>>;
+
+/// An answer to a request, before it is sent.
+#[derive(Debug)]
+pub struct Reply {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: Vec<u8>,
+}
+
+/// Answers requests for tiles, region images and routes from loaded data,
+/// keeping the most recently used tiles.
+///
+/// - `/tiles/{z}/{x}/{y}.png` renders a 256px Web Mercator map tile.
+/// - `/bbox.png?bbox=min_lon,min_lat,max_lon,max_lat&width=1024` renders a region.
+/// - `/route?from=lon,lat&to=lon,lat` returns the route as a GeoJSON Feature.
+pub struct MapService {
+    data: CachedData,
+    options: RenderOptions,
+    extents: Extents,
+    tiles: Mutex<TileCache>,
+}
+
+impl MapService {
+    pub fn new(data: CachedData, options: RenderOptions, cache_size: usize) -> Self {
+        MapService {
+            extents: Extents::new(&data),
+            data,
+            options,
+            tiles: Mutex::new(LruCache::new(
+                NonZeroUsize::new(cache_size).unwrap_or(NonZeroUsize::MIN),
+            )),
+        }
+    }
+
+    /// Answers a request for `url`, a path with an optional query string.
+    pub fn get(&self, url: &str) -> Reply {
+        let (path, query) = url.split_once('?').unwrap_or((url, ""));
+        let params: Vec<(String, String)> = query
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .map(|(key, value)| (percent_decode(key), percent_decode(value)))
+            .collect();
+        let param = |name: &str| {
+            params
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.as_str())
+        };
+
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+        match segments.as_slice() {
+            ["tiles", z, x, y] => {
+                match (z.parse(), x.parse(), y.strip_suffix(".png").map(str::parse)) {
+                    (Ok(z), Ok(x), Some(Ok(y))) => self.tile(z, x, y),
+                    _ => error(400, "Tiles are /tiles/{z}/{x}/{y}.png"),
+                }
+            }
+            ["bbox.png"] => self.bbox(param("bbox"), param("width")),
+            ["route"] => self.route(param("from"), param("to")),
+            _ => error(404, "Not found"),
+        }
+    }
+
+    /// Whether a tile is cached, without counting as a use of it.
+    pub fn is_cached(&self, z: u32, x: u32, y: u32) -> bool {
+        self.tiles.lock().unwrap().contains(&(z, x, y))
+    }
+
+    fn tile(&self, z: u32, x: u32, y: u32) -> Reply {
+        if z > MAX_ZOOM || x >= 1 << z || y >= 1 << z {
+            return error(404, "No such tile");
+        }
+        if let Some(png) = self.tiles.lock().unwrap().get(&(z, x, y)) {
+            return png_reply(png.clone());
+        }
+
+        // Rendered without the lock held so tiles render in parallel
+        let bounds = tile_bounds(z, x, y);
+        let projection = Projection::mercator(bounds, TILE_SIZE as f64, TILE_SIZE as f64);
+        let png = match encode_png(&self.render(&projection, bounds)) {
+            Ok(png) => png,
+            Err(e) => return error(500, &e.to_string()),
+        };
+        self.tiles.lock().unwrap().put((z, x, y), png.clone());
+        png_reply(png)
+    }
+
+    fn bbox(&self, bbox: Option<&str>, width: Option<&str>) -> Reply {
+        let Some((min_lon, min_lat, max_lon, max_lat)) = bbox.and_then(parse_bbox) else {
+            return error(400, "bbox must look like min_lon,min_lat,max_lon,max_lat");
+        };
+        if min_lon >= max_lon || min_lat >= max_lat {
+            return error(400, "bbox is empty");
+        }
+        let Some(width) = width.map_or(Some(1024), |width| width.parse::<u32>().ok()) else {
+            return error(400, "width must be a number of pixels");
+        };
+        let width = width.clamp(1, MAX_IMAGE_SIZE);
+
+        // Keep the ground aspect ratio of the region
+        let mid_lat = ((min_lat + max_lat) / 2.0).to_radians();
+        let aspect = (max_lat - min_lat) / ((max_lon - min_lon) * mid_lat.cos());
+        let height = ((width as f64 * aspect).round() as u32).clamp(1, MAX_IMAGE_SIZE);
+
+        let bounds = (min_lon, min_lat, max_lon, max_lat);
+        let projection = Projection::new(bounds, width as f64, height as f64);
+        match encode_png(&self.render(&projection, bounds)) {
+            Ok(png) => png_reply(png),
+            Err(e) => error(500, &e.to_string()),
+        }
+    }
+
+    /// Renders only the features near `bounds`, rather than projecting every
+    /// feature of the data for each image.
+    fn render(&self, projection: &Projection, bounds: Bounds) -> RgbaImage {
+        let (min_lon, min_lat, max_lon, max_lat) = bounds;
+        let (margin_lon, margin_lat) = (
+            (max_lon - min_lon) * TILE_MARGIN,
+            (max_lat - min_lat) * TILE_MARGIN,
+        );
+        let view = (
+            min_lon - margin_lon,
+            min_lat - margin_lat,
+            max_lon + margin_lon,
+            max_lat + margin_lat,
+        );
+        if contains(view, self.extents.all) {
+            return render_image(&self.data, projection, &[], &self.options);
+        }
+        let near = self.extents.data_in(&self.data, view);
+        render_image(&near, projection, &[], &self.options)
+    }
+
+    fn route(&self, from: Option<&str>, to: Option<&str>) -> Reply {
+        let (Some(from), Some(to)) = (from.and_then(parse_coord), to.and_then(parse_coord)) else {
+            return error(400, "from and to must look like lon,lat");
+        };
+        let (Some(start), Some(goal)) = (
+            nearest_node_in(&self.data.component, from),
+            nearest_node_in(&self.data.component, to),
+        ) else {
+            return error(404, "No roads loaded");
+        };
+        match find_path(&self.data.graph, &self.data.restrictions, start, goal) {
+            Some((path, _)) => Reply {
+                status: 200,
+                content_type: "application/geo+json",
+                body: route_to_geojson(&path).to_string().into_bytes(),
+            },
+            None => error(404, "No path found"),
+        }
+    }
+}
+
+/// Serves a [`MapService`] over HTTP on `addr` until the process is stopped.
+/// Requests are handled on a thread pool sharing `data`.
+pub fn serve(
+    data: CachedData,
+    options: RenderOptions,
+    addr: &str,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let server = Server::http(addr)?;
+    let service = Arc::new(MapService::new(data, options, TILE_CACHE_SIZE));
+    let threads = std::thread::available_parallelism().map_or(4, |threads| threads.get());
+    let pool = ThreadPool::new(threads);
+    println!("Listening on http://{}", addr);
+
+    for request in server.incoming_requests() {
+        let service = Arc::clone(&service);
+        pool.execute(move || handle(&service, request));
+    }
+    Ok(())
+}
+
+fn handle(service: &MapService, request: Request) {
+    let start_time = Instant::now();
+    let url = request.url().to_string();
+    let reply = service.get(&url);
+
+    println!(
+        "{} {} {} in {:?}",
+        request.method(),
+        url,
+        reply.status,
+        start_time.elapsed()
+    );
+    let response = Response::from_data(reply.body)
+        .with_status_code(reply.status)
+        .with_header(Header::from_bytes("Content-Type", reply.content_type).unwrap());
+    if let Err(e) = request.respond(response) {
+        println!("Failed to respond: {}", e);
+    }
+}
+
+/// Bounding boxes of everything drawn, in the order of the lists in the
+/// data, so an image only draws what lies near it.
+struct Extents {
+    all: Bounds,
+    highways: Vec<Bounds>,
+    waterways: Vec<Bounds>,
+    railways: Vec<Bounds>,
+    buildings: Vec<Bounds>,
+    naturals: Vec<Bounds>,
+    aeroways: Vec<Bounds>,
+    landuse: Vec<Bounds>,
+    water: Vec<Bounds>,
+    parks: Vec<Bounds>,
+    multipolygons: Vec<Bounds>,
+    polygons: Vec<Bounds>,
+}
+
+impl Extents {
+    fn new(data: &CachedData) -> Self {
+        let ways = |ways: &[WayCoords]| -> Vec<Bounds> {
+            ways.iter()
+                .map(|way| calculate_bounding_box([way.as_slice()]))
+                .collect()
+        };
+        let rings = |rings: &[WayCoords]| calculate_bounding_box(rings.iter().map(Vec::as_slice));
+        let points = data
+            .places
+            .iter()
+            .map(|place| (place.lon, place.lat))
+            .chain(data.pois.iter().map(|poi| (poi.lon, poi.lat)))
+            .collect::<Vec<_>>();
+
+        let mut extents = Extents {
+            all: calculate_bounding_box([points.as_slice()]),
+            highways: data
+                .highways
+                .iter()
+                .map(|highway| calculate_bounding_box([highway.coords.as_slice()]))
+                .collect(),
+            waterways: ways(&data.waterways),
+            railways: ways(&data.railways),
+            buildings: ways(&data.buildings),
+            naturals: ways(&data.naturals),
+            aeroways: ways(&data.aeroways),
+            landuse: ways(&data.landuse),
+            water: ways(&data.water),
+            parks: ways(&data.parks),
+            multipolygons: data.multipolygons.iter().map(|ways| rings(ways)).collect(),
+            polygons: data
+                .polygons
+                .iter()
+                .map(|(_, polygon)| rings(polygon))
+                .collect(),
+        };
+        extents.all = [
+            &extents.highways,
+            &extents.waterways,
+            &extents.railways,
+            &extents.buildings,
+            &extents.naturals,
+            &extents.aeroways,
+            &extents.landuse,
+            &extents.water,
+            &extents.parks,
+            &extents.multipolygons,
+            &extents.polygons,
+        ]
+        .into_iter()
+        .flatten()
+        .fold(extents.all, |all, bounds| {
+            (
+                all.0.min(bounds.0),
+                all.1.min(bounds.1),
+                all.2.max(bounds.2),
+                all.3.max(bounds.3),
+            )
+        });
+        extents
+    }
+
+    /// A copy of the drawn features of `data` that overlap `view`.
+    fn data_in(&self, data: &CachedData, view: Bounds) -> CachedData {
+        fn keep<T: Clone>(items: &[T], bounds: &[Bounds], view: Bounds) -> Vec<T> {
+            items
+                .iter()
+                .zip(bounds)
+                .filter(|(_, &bounds)| overlaps(bounds, view))
+                .map(|(item, _)| item.clone())
+                .collect()
+        }
+        let inside = |lon: f64, lat: f64| contains(view, (lon, lat, lon, lat));
+        CachedData {
+            highways: keep(&data.highways, &self.highways, view),
+            waterways: keep(&data.waterways, &self.waterways, view),
+            railways: keep(&data.railways, &self.railways, view),
+            buildings: keep(&data.buildings, &self.buildings, view),
+            naturals: keep(&data.naturals, &self.naturals, view),
+            aeroways: keep(&data.aeroways, &self.aeroways, view),
+            landuse: keep(&data.landuse, &self.landuse, view),
+            water: keep(&data.water, &self.water, view),
+            parks: keep(&data.parks, &self.parks, view),
+            multipolygons: keep(&data.multipolygons, &self.multipolygons, view),
+            polygons: keep(&data.polygons, &self.polygons, view),
+            places: data
+                .places
+                .iter()
+                .filter(|place| inside(place.lon, place.lat))
+                .cloned()
+                .collect(),
+            pois: data
+                .pois
+                .iter()
+                .filter(|poi| inside(poi.lon, poi.lat))
+                .cloned()
+                .collect(),
+            ..Default::default()
+        }
+    }
+}
+
+fn overlaps(a: Bounds, b: Bounds) -> bool {
+    a.0 <= b.2 && b.0 <= a.2 && a.1 <= b.3 && b.1 <= a.3
+}
+
+/// Whether `outer` contains all of `inner`.
+fn contains(outer: Bounds, inner: Bounds) -> bool {
+    outer.0 <= inner.0 && outer.1 <= inner.1 && inner.2 <= outer.2 && inner.3 <= outer.3
+}
+
+/// Bounds of a Web Mercator tile.
+fn tile_bounds(z: u32, x: u32, y: u32) -> Bounds {
+    let n = (1u64 << z) as f64;
+    let lon = |x: u32| x as f64 / n * 360.0 - 180.0;
+    let lat = |y: u32| (PI * (1.0 - 2.0 * y as f64 / n)).sinh().atan().to_degrees();
+    (lon(x), lat(y + 1), lon(x + 1), lat(y))
+}
+
+fn encode_png(img: &RgbaImage) -> Result<Vec<u8>, image::ImageError> {
+    let mut png = Vec::new();
+    img.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+    Ok(png)
+}
+
+fn png_reply(png: Vec<u8>) -> Reply {
+    Reply {
+        status: 200,
+        content_type: "image/png",
+        body: png,
+    }
+}
+
+fn error(status: u16, message: &str) -> Reply {
+    Reply {
+        status,
+        content_type: "text/plain",
+        body: message.as_bytes().to_vec(),
+    }
+}
+
+/// Decodes `%XX` escapes and `+` in a query string component.
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match (escaped, bytes[i]) {
+            (Some(byte), _) => {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+            (None, b'+') => decoded.push(b' '),
+            (None, byte) => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
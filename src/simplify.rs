@@ -0,0 +1,110 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+/// Simplifies a line with the Douglas–Peucker algorithm, keeping the points
+/// that stray more than `tolerance` from the simplified line. The ends are
+/// always kept, so closed rings stay closed.
+pub fn douglas_peucker(points: &[(f64, f64)], tolerance: f64) -> Vec<(f64, f64)> {
+    if points.len() < 3 {
+        return points.to_vec();
+    }
+    let mut keep = vec![false; points.len()];
+    keep[0] = true;
+    keep[points.len() - 1] = true;
+
+    let mut stack = vec![(0, points.len() - 1)];
+    while let Some((first, last)) = stack.pop() {
+        let farthest = (first + 1..last)
+            .map(|i| (i, segment_distance(points[i], points[first], points[last])))
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        if let Some((i, distance)) = farthest {
+            if distance > tolerance {
+                keep[i] = true;
+                stack.push((first, i));
+                stack.push((i, last));
+            }
+        }
+    }
+    points
+        .iter()
+        .zip(keep)
+        .filter(|&(_, keep)| keep)
+        .map(|(&point, _)| point)
+        .collect()
+}
+
+/// Distance from `p` to the segment from `a` to `b`.
+fn segment_distance(p: (f64, f64), a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let length_squared = dx * dx + dy * dy;
+    let t = if length_squared > 0.0 {
+        (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / length_squared).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let (x, y) = (a.0 + t * dx, a.1 + t * dy);
+    ((p.0 - x).powi(2) + (p.1 - y).powi(2)).sqrt()
+}
+
+/// Simplifies a line with the Visvalingam–Whyatt algorithm, repeatedly
+/// dropping the point whose triangle with its neighbours has the smallest
+/// area until every remaining triangle is at least `min_area`. This keeps the
+/// overall shape of outlines better than Douglas–Peucker. The ends are always
+/// kept, and a closed ring keeps at least three distinct points.
+pub fn visvalingam(points: &[(f64, f64)], min_area: f64) -> Vec<(f64, f64)> {
+    let closed = points.len() > 3 && points.first() == points.last();
+    let min_len = if closed { 4 } else { 2 };
+    if points.len() <= min_len {
+        return points.to_vec();
+    }
+    let n = points.len();
+    let mut previous: Vec<usize> = (0..n).map(|i| i.saturating_sub(1)).collect();
+    let mut next: Vec<usize> = (1..=n).collect();
+    let mut areas = vec![f64::INFINITY; n];
+    // Areas are never negative, so their bits sort like the areas themselves
+    let mut heap = BinaryHeap::new();
+    for i in 1..n - 1 {
+        areas[i] = triangle_area(points[i - 1], points[i], points[i + 1]);
+        heap.push(Reverse((areas[i].to_bits(), i)));
+    }
+
+    let mut len = n;
+    while let Some(Reverse((bits, i))) = heap.pop() {
+        let area = f64::from_bits(bits);
+        if area != areas[i] {
+            // Stale entry from before a neighbour was removed
+            continue;
+        }
+        if area >= min_area || len <= min_len {
+            break;
+        }
+        areas[i] = f64::NAN;
+        len -= 1;
+        let (before, after) = (previous[i], next[i]);
+        next[before] = after;
+        previous[after] = before;
+        for neighbour in [before, after] {
+            if neighbour == 0 || neighbour == n - 1 {
+                continue;
+            }
+            // A neighbour never drops out before the point removed here did
+            let triangle = triangle_area(
+                points[previous[neighbour]],
+                points[neighbour],
+                points[next[neighbour]],
+            );
+            areas[neighbour] = triangle.max(area);
+            heap.push(Reverse((areas[neighbour].to_bits(), neighbour)));
+        }
+    }
+    points
+        .iter()
+        .zip(&areas)
+        .filter(|(_, area)| !area.is_nan())
+        .map(|(&point, _)| point)
+        .collect()
+}
+
+fn triangle_area(a: (f64, f64), b: (f64, f64), c: (f64, f64)) -> f64 {
+    ((b.0 - a.0) * (c.1 - a.1) - (c.0 - a.0) * (b.1 - a.1)).abs() / 2.0
+}
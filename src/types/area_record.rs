@@ -0,0 +1,12 @@
+use osmpbfreader::Tags;
+use serde::{Deserialize, Serialize};
+
+/// A multipolygon relation by the ids of its member ways, kept so the area
+/// can be assembled into rings for export.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AreaRecord {
+    pub id: i64,
+    pub tags: Tags,
+    pub outer: Vec<i64>,
+    pub inner: Vec<i64>,
+}
@@ -1,19 +1,76 @@
+use super::area_record::AreaRecord;
+use super::category::Category;
 use super::coord::Coord;
 use super::edge::Edge;
+use super::highway::Highway;
+use super::place::Place;
+use super::poi::Poi;
+use super::restriction::TurnRestriction;
+use super::way_record::WayRecord;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Default)]
 pub struct CachedData {
     pub nodes: HashMap<i64, (f64, f64)>,
-    pub highways: Vec<WayCoords>,
+    pub highways: Vec<Highway>,
     pub waterways: Vec<WayCoords>,
     pub railways: Vec<WayCoords>,
     pub buildings: Vec<WayCoords>,
     pub naturals: Vec<WayCoords>,
     pub aeroways: Vec<WayCoords>,
+    pub landuse: Vec<WayCoords>,
+    pub water: Vec<WayCoords>,
+    pub parks: Vec<WayCoords>,
     pub multipolygons: Vec<Vec<WayCoords>>,
+    /// Multipolygon relations with a layer, assembled into polygons with
+    /// holes and drawn in the layer of their category.
+    pub polygons: Vec<(Category, Polygon)>,
+    pub places: Vec<Place>,
+    pub pois: Vec<Poi>,
     pub graph: HashMap<Coord, Vec<Edge>>,
+    /// Nodes of the largest strongly connected component of the graph, kept
+    /// with it so route endpoints can be snapped without finding components
+    /// on every run.
+    pub component: Vec<Coord>,
+    /// Components with fewer nodes were pruned from the graph, and are pruned
+    /// again when changes are applied.
+    pub min_component_size: Option<usize>,
+    /// Turn restrictions keyed by their `via` node.
+    pub restrictions: HashMap<Coord, Vec<TurnRestriction>>,
+    /// Every OSM way by id, for applying change files.
+    pub ways: HashMap<i64, WayRecord>,
+    /// Multipolygon relations, for exporting them as assembled areas.
+    pub areas: Vec<AreaRecord>,
+}
+
+impl CachedData {
+    /// The coordinate layer of a category. Highways are kept with their
+    /// names instead and have none.
+    pub fn layer_mut(&mut self, category: Category) -> Option<&mut Vec<WayCoords>> {
+        match category {
+            Category::Highway => None,
+            Category::Water => Some(&mut self.water),
+            Category::Waterway => Some(&mut self.waterways),
+            Category::Railway => Some(&mut self.railways),
+            Category::Building => Some(&mut self.buildings),
+            Category::Park => Some(&mut self.parks),
+            Category::Natural => Some(&mut self.naturals),
+            Category::Landuse => Some(&mut self.landuse),
+            Category::Aeroway => Some(&mut self.aeroways),
+        }
+    }
+
+    /// The assembled polygons drawn in a category's layer.
+    pub fn polygons_in(&self, category: Category) -> impl Iterator<Item = &Polygon> {
+        self.polygons
+            .iter()
+            .filter(move |(polygon_category, _)| *polygon_category == category)
+            .map(|(_, polygon)| polygon)
+    }
 }
 
 pub type WayCoords = Vec<(f64, f64)>;
+
+/// A polygon as its outer ring followed by its holes.
+pub type Polygon = Vec<WayCoords>;
@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+
+/// The layers ways and areas are sorted into.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Category {
+    Highway,
+    Water,
+    Waterway,
+    Railway,
+    Building,
+    Park,
+    Natural,
+    Landuse,
+    Aeroway,
+}
+
+impl Category {
+    pub fn name(self) -> &'static str {
+        match self {
+            Category::Highway => "highway",
+            Category::Water => "water",
+            Category::Waterway => "waterway",
+            Category::Railway => "railway",
+            Category::Building => "building",
+            Category::Park => "park",
+            Category::Natural => "natural",
+            Category::Landuse => "landuse",
+            Category::Aeroway => "aeroway",
+        }
+    }
+
+    /// Whether a closed way in this category outlines an area rather than
+    /// being a loop of a line.
+    pub fn is_area(self) -> bool {
+        !matches!(
+            self,
+            Category::Highway | Category::Waterway | Category::Railway
+        )
+    }
+}
@@ -13,14 +13,31 @@ impl Coord {
     }
 }
 
+// Equality, ordering and hashing all compare the bits of the floats, so
+// they agree with each other even for -0.0 and NaN.
 impl PartialEq for Coord {
     fn eq(&self, other: &Self) -> bool {
-        self.lat == other.lat && self.lon == other.lon
+        self.lat.to_bits() == other.lat.to_bits() && self.lon.to_bits() == other.lon.to_bits()
     }
 }
 
 impl Eq for Coord {}
 
+// Total order so coordinates can be used in search queues.
+impl Ord for Coord {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.lat
+            .total_cmp(&other.lat)
+            .then(self.lon.total_cmp(&other.lon))
+    }
+}
+
+impl PartialOrd for Coord {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 impl Hash for Coord {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.lat.to_bits().hash(state);
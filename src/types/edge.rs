@@ -5,4 +5,6 @@ use serde::{Deserialize, Serialize};
 pub struct Edge {
     pub target: Coord, // (lat, lon)
     pub cost: i64,
+    /// OSM id of the highway the edge belongs to.
+    pub way: i64,
 }
@@ -0,0 +1,64 @@
+use super::cached_data::WayCoords;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Highway {
+    /// OSM way id.
+    pub id: i64,
+    /// Value of the `highway` tag, e.g. `primary` or `footway`.
+    pub highway: String,
+    pub name: Option<String>,
+    pub coords: WayCoords,
+}
+
+impl Highway {
+    pub fn class(&self) -> HighwayClass {
+        HighwayClass::from_tag(&self.highway)
+    }
+}
+
+impl AsRef<[(f64, f64)]> for Highway {
+    fn as_ref(&self) -> &[(f64, f64)] {
+        &self.coords
+    }
+}
+
+/// Road classes ordered from least to most important.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum HighwayClass {
+    Path,
+    Service,
+    Residential,
+    Tertiary,
+    Secondary,
+    Primary,
+    Trunk,
+    Motorway,
+}
+
+impl HighwayClass {
+    pub const ALL: [HighwayClass; 8] = [
+        HighwayClass::Path,
+        HighwayClass::Service,
+        HighwayClass::Residential,
+        HighwayClass::Tertiary,
+        HighwayClass::Secondary,
+        HighwayClass::Primary,
+        HighwayClass::Trunk,
+        HighwayClass::Motorway,
+    ];
+
+    pub fn from_tag(highway: &str) -> Self {
+        match highway.strip_suffix("_link").unwrap_or(highway) {
+            "motorway" => HighwayClass::Motorway,
+            "trunk" => HighwayClass::Trunk,
+            "primary" => HighwayClass::Primary,
+            "secondary" => HighwayClass::Secondary,
+            "tertiary" => HighwayClass::Tertiary,
+            "service" => HighwayClass::Service,
+            "footway" | "path" | "cycleway" | "bridleway" | "steps" | "pedestrian" | "track"
+            | "corridor" => HighwayClass::Path,
+            _ => HighwayClass::Residential,
+        }
+    }
+}
@@ -1,3 +1,10 @@
+pub mod area_record;
 pub mod cached_data;
+pub mod category;
 pub mod coord;
 pub mod edge;
+pub mod highway;
+pub mod place;
+pub mod poi;
+pub mod restriction;
+pub mod way_record;
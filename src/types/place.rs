@@ -0,0 +1,14 @@
+use serde::{Deserialize, Serialize};
+
+/// A named `place=*` node such as a city, suburb or village.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Place {
+    /// OSM id of the node.
+    pub id: i64,
+    pub name: String,
+    pub kind: String,
+    /// Every tag of the node, for exporting it.
+    pub tags: Vec<(String, String)>,
+    pub lon: f64,
+    pub lat: f64,
+}
@@ -0,0 +1,25 @@
+use serde::{Deserialize, Serialize};
+
+/// A tagged node such as a shop, amenity or station. `kinds` are the tags it
+/// was classified by in order of precedence, e.g. `amenity=cafe` and
+/// `shop=bakery` for a bakery with a café.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Poi {
+    pub id: i64,
+    pub kinds: Vec<(String, String)>,
+    pub name: Option<String>,
+    /// Every tag of the node, for exporting it.
+    pub tags: Vec<(String, String)>,
+    pub lon: f64,
+    pub lat: f64,
+}
+
+impl Poi {
+    /// Whether the POI is tagged `key=value`, or with any value of `key` if
+    /// `value` is `None`.
+    pub fn has_kind(&self, key: &str, value: Option<&str>) -> bool {
+        self.kinds
+            .iter()
+            .any(|(k, v)| k == key && value.is_none_or(|value| v == value))
+    }
+}
@@ -0,0 +1,33 @@
+use serde::{Deserialize, Serialize};
+
+use super::coord::Coord;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestrictionKind {
+    /// `no_*` restrictions: the turn from `from_way` onto `to_way` is forbidden.
+    No,
+    /// `only_*` restrictions: coming from `from_way`, `to_way` is the only way out.
+    Only,
+}
+
+/// A `type=restriction` relation with a node as its `via` member.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TurnRestriction {
+    pub from_way: i64,
+    pub via: Coord,
+    pub to_way: i64,
+    pub kind: RestrictionKind,
+}
+
+impl RestrictionKind {
+    /// Parses the value of a `restriction` tag such as `no_left_turn` or `only_straight_on`.
+    pub fn from_tag(restriction: &str) -> Option<Self> {
+        if restriction.starts_with("no_") {
+            Some(RestrictionKind::No)
+        } else if restriction.starts_with("only_") {
+            Some(RestrictionKind::Only)
+        } else {
+            None
+        }
+    }
+}
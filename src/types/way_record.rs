@@ -0,0 +1,30 @@
+use super::cached_data::WayCoords;
+use osmpbfreader::Tags;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Node ids and tags of an OSM way, kept so a change file can rebuild the
+/// way after its nodes move or its tags change.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WayRecord {
+    pub nodes: Vec<i64>,
+    pub tags: Tags,
+    /// Kept outside a clip only as a member of a relation inside it, so it
+    /// is in no layer and not exported or drawn on its own.
+    pub member_only: bool,
+}
+
+impl WayRecord {
+    /// Coordinates of the way's nodes, skipping any that are missing.
+    pub fn coords(&self, nodes: &HashMap<i64, (f64, f64)>) -> WayCoords {
+        self.nodes
+            .iter()
+            .filter_map(|id| nodes.get(id))
+            .copied()
+            .collect()
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.nodes.len() > 1 && self.nodes.first() == self.nodes.last()
+    }
+}
@@ -0,0 +1,207 @@
+use osmpbfreader::{OsmObj, Tags};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::graph::{build_graph, largest_component, prune_islands};
+use crate::osm::{add_node, add_way, assemble_area_polygons, way_category};
+use crate::osm_xml::Action;
+use crate::types::cached_data::{CachedData, WayCoords};
+use crate::types::category::Category;
+use crate::types::coord::Coord;
+use crate::types::way_record::WayRecord;
+
+/// Counts of what a change file touched.
+#[derive(Debug, Default)]
+pub struct ChangeStats {
+    pub nodes: usize,
+    pub ways: usize,
+    /// Ways rebuilt because they changed or one of their nodes moved.
+    pub rebuilt_ways: usize,
+    /// Relations are not applied, new restrictions and multipolygons need a
+    /// fresh import.
+    pub skipped_relations: usize,
+}
+
+/// Applies the changes of an `.osc` file to cached data. Nodes and ways are
+/// created, moved, retagged or deleted, and every way they affect is taken out
+/// of its layer and the routing graph and added back with its new shape.
+pub fn apply_changes(
+    data: &mut CachedData,
+    changes: impl IntoIterator<Item = (Action, OsmObj)>,
+) -> ChangeStats {
+    let mut stats = ChangeStats::default();
+    // Positions before the change of every node it touches, None for new nodes
+    let mut old_nodes: HashMap<i64, Option<(f64, f64)>> = HashMap::new();
+    // Latest version of every changed node, None once deleted
+    let mut node_changes: BTreeMap<i64, Option<(Tags, f64, f64)>> = BTreeMap::new();
+    let mut way_changes: Vec<(Action, i64, WayRecord)> = Vec::new();
+
+    for (action, obj) in changes {
+        match obj {
+            OsmObj::Node(node) => {
+                stats.nodes += 1;
+                let id = node.id.0;
+                let old = data.nodes.get(&id).copied();
+                old_nodes.entry(id).or_insert(old);
+                if action == Action::Delete {
+                    data.nodes.remove(&id);
+                    node_changes.insert(id, None);
+                } else {
+                    let (lon, lat) = (node.lon(), node.lat());
+                    data.nodes.insert(id, (lon, lat));
+                    node_changes.insert(id, Some((node.tags, lon, lat)));
+                }
+            }
+            OsmObj::Way(way) => {
+                stats.ways += 1;
+                let nodes = way.nodes.iter().map(|node_id| node_id.0).collect();
+                let tags = way.tags;
+                let member_only = data.ways.get(&way.id.0).is_some_and(|way| way.member_only);
+                let record = WayRecord {
+                    nodes,
+                    tags,
+                    member_only,
+                };
+                way_changes.push((action, way.id.0, record));
+            }
+            OsmObj::Relation(_) => stats.skipped_relations += 1,
+        }
+    }
+
+    // POIs and places are replaced in one pass over each
+    data.pois.retain(|poi| !node_changes.contains_key(&poi.id));
+    data.places
+        .retain(|place| !node_changes.contains_key(&place.id));
+    for (id, node) in node_changes {
+        if let Some((tags, lon, lat)) = node {
+            add_node(data, id, &tags, lon, lat);
+        }
+    }
+
+    let moved: HashSet<i64> = old_nodes
+        .iter()
+        .filter(|&(id, &old)| old != data.nodes.get(id).copied())
+        .map(|(&id, _)| id)
+        .collect();
+    let mut affected: HashSet<i64> = data
+        .ways
+        .iter()
+        .filter(|(_, way)| way.nodes.iter().any(|id| moved.contains(id)))
+        .map(|(&id, _)| id)
+        .collect();
+    affected.extend(way_changes.iter().map(|&(_, id, _)| id));
+
+    // Take the old shapes out before the records change
+    let old_position = |id: &i64| match old_nodes.get(id) {
+        Some(&old) => old,
+        None => data.nodes.get(id).copied(),
+    };
+    let old_ways: Vec<(i64, Tags, WayCoords, bool)> = affected
+        .iter()
+        .filter_map(|id| {
+            let way = data.ways.get(id).filter(|way| !way.member_only)?;
+            let coords = way.nodes.iter().filter_map(old_position).collect();
+            Some((*id, way.tags.clone(), coords, way.is_closed()))
+        })
+        .collect();
+    // Old shapes to take out of each layer, by their coordinates' bits
+    let mut removed: HashMap<Category, HashMap<Vec<(u64, u64)>, usize>> = HashMap::new();
+    for (id, tags, coords, closed) in old_ways {
+        match way_category(&tags, closed) {
+            Some(Category::Highway) => remove_edges(data, id, &coords),
+            Some(category) => {
+                *removed
+                    .entry(category)
+                    .or_default()
+                    .entry(coord_bits(&coords))
+                    .or_default() += 1;
+            }
+            None => {}
+        }
+    }
+    for (category, mut shapes) in removed {
+        let layer = data.layer_mut(category).unwrap();
+        layer.retain(|way| match shapes.get_mut(&coord_bits(way)) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                false
+            }
+            _ => true,
+        });
+    }
+    data.highways
+        .retain(|highway| !affected.contains(&highway.id));
+
+    for (action, id, way) in way_changes {
+        if action == Action::Delete {
+            data.ways.remove(&id);
+        } else {
+            data.ways.insert(id, way);
+        }
+    }
+
+    let first_new = data.highways.len();
+    for &id in &affected {
+        // Member-only ways of a clipped cache stay out of the layers
+        if let Some(way) = data.ways.get(&id).filter(|way| !way.member_only) {
+            let (tags, closed) = (way.tags.clone(), way.is_closed());
+            let coords = way.coords(&data.nodes);
+            add_way(data, id, &tags, coords, closed);
+            stats.rebuilt_ways += 1;
+        }
+    }
+    for (node, edges) in build_graph(&data.highways[first_new..]) {
+        data.graph.entry(node).or_default().extend(edges);
+    }
+    // Areas may have lost or reshaped a member way, and roads may have
+    // joined or split components. Rebuilt roads come back whole, so islands
+    // pruned before are pruned again.
+    if !affected.is_empty() {
+        assemble_area_polygons(data);
+        if let Some(min_size) = data.min_component_size {
+            prune_islands(&mut data.graph, min_size);
+        }
+        data.component = largest_component(&data.graph);
+    }
+
+    // Restrictions follow their via node
+    for &id in &moved {
+        let Some((lon, lat)) = old_nodes[&id] else {
+            continue;
+        };
+        let Some(mut restrictions) = data.restrictions.remove(&Coord::new(lon, lat)) else {
+            continue;
+        };
+        if let Some(&(lon, lat)) = data.nodes.get(&id) {
+            let via = Coord::new(lon, lat);
+            for restriction in &mut restrictions {
+                restriction.via = via;
+            }
+            data.restrictions
+                .entry(via)
+                .or_default()
+                .extend(restrictions);
+        }
+    }
+
+    stats
+}
+
+fn coord_bits(coords: &[(f64, f64)]) -> Vec<(u64, u64)> {
+    coords
+        .iter()
+        .map(|&(lon, lat)| (lon.to_bits(), lat.to_bits()))
+        .collect()
+}
+
+/// Removes the edges of way `id` from the graph, given the way's nodes.
+fn remove_edges(data: &mut CachedData, id: i64, coords: &[(f64, f64)]) {
+    for &(lon, lat) in coords {
+        let node = Coord::new(lon, lat);
+        if let Some(edges) = data.graph.get_mut(&node) {
+            edges.retain(|edge| edge.way != id);
+            if edges.is_empty() {
+                data.graph.remove(&node);
+            }
+        }
+    }
+}
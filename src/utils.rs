@@ -1,8 +1,29 @@
 use rand::seq::IteratorRandom;
 
-pub fn get_random_node(highways: &[Vec<(f64, f64)>]) -> (f64, f64) {
+use crate::types::coord::Coord;
+
+pub fn get_random_node(nodes: &[Coord]) -> Coord {
     let mut rng = rand::thread_rng();
-    let random_highway = highways.iter().choose(&mut rng);
-    let random_node = random_highway.unwrap().iter().choose(&mut rng);
-    *random_node.unwrap()
+    *nodes.iter().choose(&mut rng).unwrap()
+}
+
+/// Parses a `lon,lat` pair.
+pub fn parse_coord(coord: &str) -> Option<Coord> {
+    let (lon, lat) = coord.split_once(',')?;
+    Some(Coord::new(
+        lon.trim().parse().ok()?,
+        lat.trim().parse().ok()?,
+    ))
+}
+
+/// Parses a `min_lon,min_lat,max_lon,max_lat` bounding box.
+pub fn parse_bbox(bbox: &str) -> Option<(f64, f64, f64, f64)> {
+    let parts: Vec<f64> = bbox
+        .split(',')
+        .map(|part| part.trim().parse().ok())
+        .collect::<Option<_>>()?;
+    match parts[..] {
+        [min_lon, min_lat, max_lon, max_lat] => Some((min_lon, min_lat, max_lon, max_lat)),
+        _ => None,
+    }
 }
@@ -0,0 +1,84 @@
+use std::collections::HashMap;
+
+use crate::graph::find_path;
+use crate::matrix::distance_matrix;
+use crate::types::restriction::TurnRestriction;
+use crate::types::{coord::Coord, edge::Edge};
+
+/// Routes through `waypoints` in order by joining the shortest path of each
+/// leg. Turn restrictions apply within legs but not across a waypoint.
+pub fn route_via(
+    graph: &HashMap<Coord, Vec<Edge>>,
+    restrictions: &HashMap<Coord, Vec<TurnRestriction>>,
+    waypoints: &[Coord],
+) -> Option<(Vec<Coord>, i64)> {
+    let mut path = vec![*waypoints.first()?];
+    let mut cost = 0;
+    for leg in waypoints.windows(2) {
+        let (leg_path, leg_cost) = find_path(graph, restrictions, leg[0], leg[1])?;
+        path.extend(leg_path.into_iter().skip(1));
+        cost += leg_cost;
+    }
+    Some((path, cost))
+}
+
+/// Reorders the waypoints to make the route through them short, with a
+/// nearest neighbour tour improved by 2-opt. The first waypoint stays first.
+/// A round trip comes back to it at the end; otherwise the last waypoint
+/// stays last. Legs are priced with turn restrictions like `route_via`
+/// routes them.
+pub fn optimise_order(
+    graph: &HashMap<Coord, Vec<Edge>>,
+    restrictions: &HashMap<Coord, Vec<TurnRestriction>>,
+    waypoints: &[Coord],
+    round_trip: bool,
+) -> Vec<Coord> {
+    if waypoints.len() < 3 {
+        let mut order = waypoints.to_vec();
+        if round_trip {
+            order.extend(waypoints.first());
+        }
+        return order;
+    }
+    let matrix = distance_matrix(graph, restrictions, waypoints, waypoints);
+    let cost = |from: usize, to: usize| matrix[from][to].unwrap_or(i64::MAX);
+
+    // Both ends of the tour are fixed, a round trip ends where it started
+    let n = waypoints.len();
+    let end = if round_trip { 0 } else { n - 1 };
+    let mut free: Vec<usize> = (1..n).filter(|&i| i != end).collect();
+    let mut tour = vec![0];
+    while !free.is_empty() {
+        let last = tour[tour.len() - 1];
+        let nearest = (0..free.len())
+            .min_by_key(|&i| cost(last, free[i]))
+            .unwrap();
+        tour.push(free.swap_remove(nearest));
+    }
+    tour.push(end);
+
+    let tour_cost = |tour: &[usize]| {
+        tour.windows(2)
+            .fold(0i64, |total, w| total.saturating_add(cost(w[0], w[1])))
+    };
+    let mut best = tour_cost(&tour);
+    let mut improved = true;
+    while improved {
+        improved = false;
+        for i in 1..tour.len() - 2 {
+            for j in i + 1..tour.len() - 1 {
+                tour[i..=j].reverse();
+                // The matrix need not be symmetric, so price the whole tour
+                let candidate = tour_cost(&tour);
+                if candidate < best {
+                    best = candidate;
+                    improved = true;
+                } else {
+                    tour[i..=j].reverse();
+                }
+            }
+        }
+    }
+
+    tour.into_iter().map(|i| waypoints[i]).collect()
+}
@@ -0,0 +1,81 @@
+use maps::alternatives::{alternative_routes, similarity};
+use maps::graph::{build_graph, find_path};
+use maps::types::coord::Coord;
+use maps::types::highway::Highway;
+use std::collections::HashMap;
+
+mod common;
+use common::highway;
+
+// A straight road from lon 0 to 0.01 with a node every 0.001 degrees
+fn main_road() -> Highway {
+    let coords: Vec<(f64, f64)> = (0..=10).map(|i| (i as f64 * 0.001, 0.0)).collect();
+    highway(1, &coords)
+}
+
+/// A detour leaving the main road at `from` and rejoining it at `to`, through
+/// a node `height` degrees to the north halfway along.
+fn detour(id: i64, from: f64, to: f64, height: f64) -> Highway {
+    highway(id, &[(from, 0.0), ((from + to) / 2.0, height), (to, 0.0)])
+}
+
+/// Routes along the main road and returns its alternatives.
+fn alternatives(highways: &[Highway]) -> Vec<Vec<Coord>> {
+    let graph = build_graph(highways);
+    let (main, _) = find_path(
+        &graph,
+        &HashMap::new(),
+        Coord::new(0.0, 0.0),
+        Coord::new(0.01, 0.0),
+    )
+    .unwrap();
+    alternative_routes(&graph, &HashMap::new(), &main, 3)
+        .into_iter()
+        .map(|(path, _)| path)
+        .collect()
+}
+
+#[test]
+fn alternatives_sharing_too_much_are_dropped() {
+    // The short detour leaves 80% of its length on the main road
+    let main = main_road();
+    let short = alternatives(&[main.clone(), detour(2, 0.004, 0.006, 0.0005)]);
+    assert!(short.is_empty());
+
+    // The long one shares 40%
+    let long = alternatives(&[main.clone(), detour(2, 0.002, 0.008, 0.0005)]);
+    assert_eq!(long.len(), 1);
+    let straight: Vec<Coord> = main
+        .coords
+        .iter()
+        .map(|&(lon, lat)| Coord::new(lon, lat))
+        .collect();
+    let shared = similarity(&long[0], &straight);
+    assert!(shared > 0.3 && shared <= 0.7);
+}
+
+#[test]
+fn alternatives_longer_than_the_stretch_are_dropped() {
+    // Detours over the whole length that are 1.4 and 1.6 times as long
+    let main = main_road();
+    assert_eq!(
+        alternatives(&[main.clone(), detour(2, 0.0, 0.01, 0.0049)]).len(),
+        1
+    );
+    assert!(alternatives(&[main, detour(2, 0.0, 0.01, 0.00624)]).is_empty());
+}
+
+#[test]
+fn alternatives_are_found_for_the_main_route_given() {
+    // With the detour as the main route, the straight road is its alternative
+    let highways = [main_road(), detour(2, 0.0, 0.01, 0.0049)];
+    let graph = build_graph(&highways);
+    let main: Vec<Coord> = highways[1]
+        .coords
+        .iter()
+        .map(|&(lon, lat)| Coord::new(lon, lat))
+        .collect();
+    let routes = alternative_routes(&graph, &HashMap::new(), &main, 3);
+    assert_eq!(routes.len(), 1);
+    assert_eq!(routes[0].0.len(), 11);
+}
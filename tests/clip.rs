@@ -0,0 +1,195 @@
+use maps::clip::Clip;
+use maps::export::{extract_features, Geometry};
+use maps::osm::{read_osm_objects, relation_ways, Clipping};
+use maps::osm_xml::OsmXmlReader;
+use maps::types::cached_data::CachedData;
+use maps::update::apply_changes;
+
+// The clip is the box from (0, 0) to (1, 1). Way 1 crosses its east edge,
+// way 2 is entirely east of it, and the lake relation has one member way
+// inside and one outside. Way 30 runs through the clip without a node in
+// it, the farmland and the wood relation surround it, and the pond relation
+// is far away. Way 51 of the wood is tagged as a railway, which only runs
+// outside the clip.
+const FIXTURE: &str = r#"<osm version="0.6">
+  <node id="1" lat="0.5" lon="0.5"/>
+  <node id="2" lat="0.5" lon="1.5"/>
+  <node id="3" lat="0.5" lon="2.0"/>
+  <node id="4" lat="0.6" lon="2.0">
+    <tag k="amenity" v="cafe"/>
+  </node>
+  <node id="5" lat="0.6" lon="0.6">
+    <tag k="amenity" v="cafe"/>
+  </node>
+  <node id="10" lat="0.2" lon="0.8"/>
+  <node id="11" lat="0.2" lon="1.2"/>
+  <node id="12" lat="0.4" lon="1.2"/>
+  <node id="13" lat="0.4" lon="0.8"/>
+  <way id="1">
+    <nd ref="1"/>
+    <nd ref="2"/>
+    <tag k="highway" v="primary"/>
+  </way>
+  <way id="2">
+    <nd ref="2"/>
+    <nd ref="3"/>
+    <tag k="highway" v="primary"/>
+  </way>
+  <node id="30" lat="0.8" lon="-0.5"/>
+  <node id="31" lat="0.8" lon="1.5"/>
+  <node id="40" lat="-1.0" lon="-1.0"/>
+  <node id="41" lat="-1.0" lon="2.0"/>
+  <node id="42" lat="2.0" lon="2.0"/>
+  <node id="43" lat="2.0" lon="-1.0"/>
+  <node id="50" lat="-3.0" lon="-3.0"/>
+  <node id="51" lat="-3.0" lon="3.0"/>
+  <node id="52" lat="3.0" lon="3.0"/>
+  <node id="53" lat="3.0" lon="-3.0"/>
+  <node id="60" lat="5.0" lon="5.0"/>
+  <node id="61" lat="5.0" lon="6.0"/>
+  <node id="62" lat="6.0" lon="5.0"/>
+  <way id="30">
+    <nd ref="30"/>
+    <nd ref="31"/>
+    <tag k="highway" v="residential"/>
+  </way>
+  <way id="40">
+    <nd ref="40"/>
+    <nd ref="41"/>
+    <nd ref="42"/>
+    <nd ref="43"/>
+    <nd ref="40"/>
+    <tag k="landuse" v="farmland"/>
+  </way>
+  <way id="50"><nd ref="50"/><nd ref="51"/><nd ref="52"/></way>
+  <way id="51">
+    <nd ref="52"/>
+    <nd ref="53"/>
+    <nd ref="50"/>
+    <tag k="railway" v="rail"/>
+  </way>
+  <way id="60"><nd ref="60"/><nd ref="61"/><nd ref="62"/><nd ref="60"/></way>
+  <way id="10"><nd ref="13"/><nd ref="10"/><nd ref="11"/></way>
+  <way id="11"><nd ref="11"/><nd ref="12"/><nd ref="13"/></way>
+  <relation id="1">
+    <member type="way" ref="10" role="outer"/>
+    <member type="way" ref="11" role="outer"/>
+    <tag k="type" v="multipolygon"/>
+    <tag k="natural" v="water"/>
+  </relation>
+  <relation id="2">
+    <member type="way" ref="50" role="outer"/>
+    <member type="way" ref="51" role="outer"/>
+    <tag k="type" v="multipolygon"/>
+    <tag k="natural" v="wood"/>
+  </relation>
+  <relation id="3">
+    <member type="way" ref="60" role="outer"/>
+    <tag k="type" v="multipolygon"/>
+    <tag k="natural" v="water"/>
+  </relation>
+</osm>
+"#;
+
+fn clipped() -> CachedData {
+    let objects = || OsmXmlReader::new(FIXTURE.as_bytes()).map(Result::unwrap);
+    let clipping = Clipping {
+        clip: &Clip::BBox(0.0, 0.0, 1.0, 1.0),
+        relation_ways: relation_ways(objects()),
+    };
+    read_osm_objects(objects(), Some(&clipping))
+}
+
+#[test]
+fn keeps_roads_crossing_the_edge_whole() {
+    let data = clipped();
+    let ids: Vec<i64> = data.highways.iter().map(|highway| highway.id).collect();
+    assert_eq!(ids, [1, 30]);
+    assert_eq!(data.highways[0].coords.len(), 2);
+    assert!(data.nodes.contains_key(&2));
+    assert!(!data.nodes.contains_key(&3));
+}
+
+#[test]
+fn drops_nodes_outside() {
+    let data = clipped();
+    assert_eq!(data.pois.len(), 1);
+    assert_eq!(data.pois[0].id, 5);
+}
+
+#[test]
+fn keeps_member_ways_of_relations_crossing_the_edge() {
+    let data = clipped();
+    assert!(data.ways.contains_key(&11));
+    let features = extract_features(&data);
+    let lake = features
+        .iter()
+        .find(|feature| (feature.id, feature.osm_type) == (1, "relation"))
+        .unwrap();
+    let Geometry::Polygon(rings) = &lake.geometry else {
+        panic!("lake should be a polygon");
+    };
+    assert_eq!(rings[0].len(), 5);
+}
+
+#[test]
+fn keeps_areas_around_the_clip() {
+    let data = clipped();
+    assert!(data.ways.contains_key(&40));
+    assert_eq!(data.landuse.len(), 1);
+
+    // Neither wood member touches the clip, but together they surround it
+    assert!(data.ways.contains_key(&50) && data.ways.contains_key(&51));
+    let ids: Vec<i64> = data.areas.iter().map(|area| area.id).collect();
+    assert_eq!(ids, [1, 2]);
+    assert!(!data.ways.contains_key(&60));
+    assert!(!data.nodes.contains_key(&60));
+}
+
+#[test]
+fn polygon_clip_checks_segments_and_rings() {
+    let clip = Clip::Polygons {
+        polygons: vec![vec![vec![(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (0.0, 0.0)]]],
+        bounds: (0.0, 0.0, 1.0, 1.0),
+    };
+    assert!(clip.intersects(&[(-0.5, 0.8), (1.5, 0.8)], false));
+    // Inside the bounding box but past the long edge
+    assert!(!clip.intersects(&[(0.8, 0.8), (1.5, 1.5)], false));
+    let around = [
+        (-1.0, -1.0),
+        (2.0, -1.0),
+        (2.0, 2.0),
+        (-1.0, 2.0),
+        (-1.0, -1.0),
+    ];
+    assert!(clip.intersects(&around, true));
+    assert!(!clip.intersects(&around, false));
+}
+
+#[test]
+fn member_ways_outside_stay_out_of_layers() {
+    let mut data = clipped();
+    assert!(data.ways[&51].member_only);
+    assert!(data.railways.is_empty());
+    let features = extract_features(&data);
+    assert!(!features
+        .iter()
+        .any(|feature| (feature.id, feature.osm_type) == (51, "way")));
+
+    let osc = r#"<osmChange version="0.6">
+      <modify>
+        <way id="50">
+          <nd ref="50"/>
+          <nd ref="51"/>
+          <nd ref="52"/>
+          <tag k="railway" v="rail"/>
+        </way>
+      </modify>
+    </osmChange>"#;
+    let changes = OsmXmlReader::new(osc.as_bytes())
+        .changes()
+        .map(Result::unwrap);
+    apply_changes(&mut data, changes);
+    assert!(data.ways[&50].member_only);
+    assert!(data.railways.is_empty());
+}
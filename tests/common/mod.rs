@@ -0,0 +1,31 @@
+//! Helpers shared by the integration tests. Each test crate uses only some.
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+
+use maps::types::highway::Highway;
+
+/// An unnamed residential road through `coords`.
+pub fn highway(id: i64, coords: &[(f64, f64)]) -> Highway {
+    Highway {
+        id,
+        highway: "residential".to_string(),
+        name: None,
+        coords: coords.to_vec(),
+    }
+}
+
+/// A residential road with a name.
+pub fn named_highway(id: i64, name: &str, coords: &[(f64, f64)]) -> Highway {
+    Highway {
+        name: Some(name.to_string()),
+        ..highway(id, coords)
+    }
+}
+
+/// A fresh directory under the system temp dir, unique to this process.
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("maps-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
@@ -0,0 +1,68 @@
+use std::collections::HashMap;
+
+use maps::graph::{build_graph, largest_component, prune_islands, strongly_connected_components};
+use maps::types::coord::Coord;
+use maps::types::edge::Edge;
+
+mod common;
+use common::highway;
+
+// A block of four nodes, a oneway dead end leaving one of its corners, and a
+// separate road of two nodes.
+fn graph() -> HashMap<Coord, Vec<Edge>> {
+    let mut graph = build_graph(&[
+        highway(
+            1,
+            &[
+                (0.0, 0.0),
+                (0.001, 0.0),
+                (0.001, 0.001),
+                (0.0, 0.001),
+                (0.0, 0.0),
+            ],
+        ),
+        highway(2, &[(0.01, 0.01), (0.011, 0.01)]),
+    ]);
+    let dead_end = Coord::new(0.002, 0.0);
+    graph.get_mut(&Coord::new(0.001, 0.0)).unwrap().push(Edge {
+        target: dead_end,
+        cost: 1,
+        way: 3,
+    });
+    graph.insert(dead_end, Vec::new());
+    graph
+}
+
+#[test]
+fn components_are_found_largest_first() {
+    let components = strongly_connected_components(&graph());
+    let sizes: Vec<usize> = components.iter().map(Vec::len).collect();
+    assert_eq!(sizes, [4, 2, 1]);
+    assert_eq!(components[2], [Coord::new(0.002, 0.0)]);
+
+    let mut largest = largest_component(&graph());
+    largest.sort();
+    let mut block = vec![
+        Coord::new(0.0, 0.0),
+        Coord::new(0.001, 0.0),
+        Coord::new(0.001, 0.001),
+        Coord::new(0.0, 0.001),
+    ];
+    block.sort();
+    assert_eq!(largest, block);
+}
+
+#[test]
+fn pruning_removes_small_components_and_edges_into_them() {
+    let mut graph = graph();
+    assert_eq!(prune_islands(&mut graph, 3), 3);
+    assert_eq!(graph.len(), 4);
+    assert!(graph
+        .values()
+        .flatten()
+        .all(|edge| graph.contains_key(&edge.target)));
+    assert_eq!(graph[&Coord::new(0.001, 0.0)].len(), 2);
+
+    // Nothing else is small enough
+    assert_eq!(prune_islands(&mut graph, 3), 0);
+}
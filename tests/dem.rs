@@ -0,0 +1,126 @@
+use maps::dem::{add_climb_costs, Dem};
+use maps::graph::COST_PER_METER;
+use maps::types::{coord::Coord, edge::Edge};
+use std::collections::HashMap;
+use tiff::encoder::{colortype::Gray32Float, TiffEncoder};
+use tiff::tags::Tag;
+
+mod common;
+use common::temp_dir;
+
+// A 3 arc-second SRTM tile at the origin, rising a meter per sample eastwards.
+fn slope_hgt() -> Dem {
+    let dir = temp_dir("dem-hgt");
+    let path = dir.join("N00E000.hgt");
+    let bytes: Vec<u8> = (0..1201 * 1201)
+        .flat_map(|i| ((i % 1201) as i16).to_be_bytes())
+        .collect();
+    std::fs::write(&path, bytes).unwrap();
+    let dem = Dem::open(&path).unwrap();
+    std::fs::remove_dir_all(dir).unwrap();
+    dem
+}
+
+#[test]
+fn reads_hgt_tiles() {
+    let dem = slope_hgt();
+    assert_eq!(dem.elevation(0.5, 0.5), Some(600.0));
+    assert_eq!(dem.elevation(0.0, 1.0), Some(0.0));
+    assert_eq!(dem.elevation(1.0, 0.0), Some(1200.0));
+    assert_eq!(dem.elevation(1.5, 0.5), None);
+
+    // Contours run north-south, every 100 m
+    let contours = dem.contours((0.1, 0.1, 0.2, 0.11), 100.0);
+    assert!(!contours.is_empty());
+    for (level, [a, b]) in contours {
+        assert_eq!(level, 200.0);
+        assert!((a.0 - 1.0 / 6.0).abs() < 1e-9 && (b.0 - 1.0 / 6.0).abs() < 1e-9);
+    }
+}
+
+/// GeoTIFF keys of a geographic model in EPSG:4326, as written by `georef`.
+const WGS84_KEYS: [u16; 12] = [1, 1, 0, 2, 1024, 0, 1, 2, 2048, 0, 1, 4326];
+
+// A 3x3 grid of heights 10 m apart with its last sample void, half a degree
+// per sample from 10E 50N.
+fn write_geotiff(path: &std::path::Path, keys: &[u16], planar: Option<u16>) {
+    let mut heights: Vec<f32> = (0..9).map(|i| i as f32 * 10.0).collect();
+    heights[8] = -9999.0;
+    let mut encoder = TiffEncoder::new(std::fs::File::create(path).unwrap()).unwrap();
+    let mut image = encoder.new_image::<Gray32Float>(3, 3).unwrap();
+    let tags = image.encoder();
+    tags.write_tag(Tag::ModelPixelScaleTag, &[0.5, 0.5, 0.0][..])
+        .unwrap();
+    tags.write_tag(Tag::ModelTiepointTag, &[0.0, 0.0, 0.0, 10.0, 50.0, 0.0][..])
+        .unwrap();
+    if !keys.is_empty() {
+        tags.write_tag(Tag::GeoKeyDirectoryTag, keys).unwrap();
+    }
+    if let Some(planar) = planar {
+        tags.write_tag(Tag::PlanarConfiguration, planar).unwrap();
+    }
+    tags.write_tag(Tag::GdalNodata, "-9999").unwrap();
+    image.write_data(&heights).unwrap();
+}
+
+#[test]
+fn reads_geotiff_with_no_data() {
+    let dir = temp_dir("dem-tif");
+    let path = dir.join("dem.tif");
+    write_geotiff(&path, &WGS84_KEYS, None);
+    let dem = Dem::open(&path).unwrap();
+    std::fs::remove_dir_all(dir).unwrap();
+
+    // The tie point is the corner of the first pixel, whose centre is the sample
+    assert_eq!(dem.elevation(10.25, 49.75), Some(0.0));
+    assert_eq!(dem.elevation(10.5, 49.75), Some(5.0));
+    assert_eq!(dem.elevation(10.25, 49.5), Some(15.0));
+    assert_eq!(dem.elevation(11.0, 49.0), None);
+}
+
+#[test]
+fn rejects_geotiffs_it_cannot_place() {
+    let dir = temp_dir("dem-tif-rejected");
+    let path = dir.join("dem.tif");
+    let projected = [1, 1, 0, 2, 1024, 0, 1, 1, 2048, 0, 1, 4326];
+    let nad83 = [1, 1, 0, 2, 1024, 0, 1, 2, 2048, 0, 1, 4269];
+    for (keys, planar) in [
+        (&[][..], None),
+        (&projected[..], None),
+        (&nad83[..], None),
+        (&WGS84_KEYS[..], Some(2)),
+    ] {
+        write_geotiff(&path, keys, planar);
+        assert!(Dem::open(&path).is_err(), "{:?} {:?}", keys, planar);
+    }
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn climbing_costs_extra() {
+    let dem = slope_hgt();
+    let (west, east) = (Coord::new(0.5, 0.5), Coord::new(0.5 + 1.0 / 1200.0, 0.5));
+    let mut graph = HashMap::new();
+    graph.insert(
+        west,
+        vec![Edge {
+            target: east,
+            cost: 100,
+            way: 1,
+        }],
+    );
+    graph.insert(
+        east,
+        vec![Edge {
+            target: west,
+            cost: 100,
+            way: 1,
+        }],
+    );
+    assert_eq!(add_climb_costs(&mut graph, &dem), 1);
+
+    // One meter up costs as much as ten on the flat, downhill is free
+    let expected = 100 + (10.0 * COST_PER_METER) as i64;
+    assert!((graph[&west][0].cost - expected).abs() < 1000);
+    assert_eq!(graph[&east][0].cost, 100);
+}
@@ -0,0 +1,93 @@
+use maps::drawing::{render_image, Layer, Projection, RenderOptions};
+use maps::graph::build_graph;
+use maps::isochrone::{isochrone, Budget};
+use maps::osm::read_osm_objects;
+use maps::osm_xml::OsmXmlReader;
+use maps::types::cached_data::CachedData;
+use maps::types::category::Category;
+use maps::types::coord::Coord;
+
+mod common;
+use common::highway;
+
+// A wood split into two outer ways with a clearing cut out of it.
+const FIXTURE: &str = r#"<osm version="0.6">
+  <node id="1" lat="0.0" lon="0.0"/>
+  <node id="2" lat="0.0" lon="0.01"/>
+  <node id="3" lat="0.01" lon="0.01"/>
+  <node id="4" lat="0.01" lon="0.0"/>
+  <node id="5" lat="0.002" lon="0.002"/>
+  <node id="6" lat="0.002" lon="0.008"/>
+  <node id="7" lat="0.008" lon="0.008"/>
+  <node id="8" lat="0.008" lon="0.002"/>
+  <way id="1"><nd ref="1"/><nd ref="2"/><nd ref="3"/></way>
+  <way id="2"><nd ref="3"/><nd ref="4"/><nd ref="1"/></way>
+  <way id="3"><nd ref="5"/><nd ref="6"/><nd ref="7"/><nd ref="8"/><nd ref="5"/></way>
+  <relation id="1">
+    <member type="way" ref="1" role="outer"/>
+    <member type="way" ref="2" role="outer"/>
+    <member type="way" ref="3" role="inner"/>
+    <tag k="type" v="multipolygon"/>
+    <tag k="natural" v="wood"/>
+  </relation>
+</osm>
+"#;
+
+#[test]
+fn layered_multipolygons_keep_their_holes() {
+    let data = read_osm_objects(
+        OsmXmlReader::new(FIXTURE.as_bytes()).map(Result::unwrap),
+        None,
+    );
+    // Assembled into one polygon instead of fragments in the layer
+    assert!(data.parks.is_empty());
+    let polygons: Vec<_> = data.polygons_in(Category::Park).collect();
+    assert_eq!(polygons.len(), 1);
+    assert_eq!(polygons[0].len(), 2);
+
+    let projection = Projection::new((0.0, 0.0, 0.01, 0.01), 100.0, 100.0);
+    let options = RenderOptions {
+        labels: false,
+        ..Default::default()
+    };
+    let img = render_image(&data, &projection, &[], &options);
+    // Inside the wood, in the clearing, and on the chord between the two
+    // outer ways, which must be filled like the rest of the wood
+    let wood = *img.get_pixel(10, 50);
+    assert_eq!(wood[3], 255);
+    assert_eq!(img.get_pixel(50, 50)[3], 0);
+    assert_eq!(*img.get_pixel(90, 90), wood);
+    assert_eq!(*img.get_pixel(10, 10), wood);
+}
+
+#[test]
+fn isochrone_is_shaded_evenly() {
+    let graph = build_graph(&[
+        highway(1, &[(0.001, 0.005), (0.005, 0.005), (0.009, 0.005)]),
+        highway(2, &[(0.005, 0.001), (0.005, 0.005), (0.005, 0.009)]),
+    ]);
+    let data = CachedData {
+        graph,
+        ..Default::default()
+    };
+    let reachable = isochrone(
+        &data.graph,
+        Coord::new(0.005, 0.005),
+        Budget::Meters(2000.0),
+    );
+    assert_eq!(reachable.reachable.len(), 5);
+
+    let projection = Projection::new((0.0, 0.0, 0.01, 0.01), 300.0, 300.0);
+    let options = RenderOptions {
+        labels: false,
+        layers: vec![Layer::Isochrone],
+        isochrone: Some(reachable),
+        ..Default::default()
+    };
+    let img = render_image(&data, &projection, &[], &options);
+    // Neighbouring runs of cells must not be shaded twice where they meet
+    let shade = *img.get_pixel(150, 150);
+    assert!(shade[3] > 0 && shade[3] < 255);
+    assert!(img.pixels().all(|&pixel| pixel[3] == 0 || pixel == shade));
+    assert_eq!(img.get_pixel(20, 20)[3], 0);
+}
@@ -0,0 +1,327 @@
+use maps::export::{
+    extract_features, route_to_geojson, write_features_geojson, write_gpx, Feature, Geometry,
+};
+use maps::flatgeobuf::write_flatgeobuf;
+use maps::osm::read_osm_objects;
+use maps::osm_xml::OsmXmlReader;
+use maps::types::coord::Coord;
+
+// A wood split into two outer ways with a clearing cut out of it, next to a
+// road.
+const FIXTURE: &str = r#"<osm version="0.6">
+  <node id="1" lat="0.0" lon="0.0"/>
+  <node id="2" lat="0.0" lon="0.01"/>
+  <node id="3" lat="0.01" lon="0.01"/>
+  <node id="4" lat="0.01" lon="0.0"/>
+  <node id="5" lat="0.002" lon="0.002"/>
+  <node id="6" lat="0.002" lon="0.004"/>
+  <node id="7" lat="0.004" lon="0.004"/>
+  <node id="8" lat="-0.001" lon="0.0"/>
+  <node id="9" lat="-0.001" lon="0.01"/>
+  <way id="1"><nd ref="1"/><nd ref="2"/><nd ref="3"/></way>
+  <way id="2"><nd ref="3"/><nd ref="4"/><nd ref="1"/></way>
+  <way id="3"><nd ref="5"/><nd ref="6"/><nd ref="7"/><nd ref="5"/></way>
+  <way id="4">
+    <nd ref="8"/>
+    <nd ref="9"/>
+    <tag k="highway" v="track"/>
+  </way>
+  <relation id="1">
+    <member type="way" ref="1" role="outer"/>
+    <member type="way" ref="2" role="outer"/>
+    <member type="way" ref="3" role="inner"/>
+    <tag k="type" v="multipolygon"/>
+    <tag k="natural" v="wood"/>
+  </relation>
+</osm>
+"#;
+
+#[test]
+fn multipolygon_is_assembled_with_its_hole() {
+    let data = read_osm_objects(
+        OsmXmlReader::new(FIXTURE.as_bytes()).map(Result::unwrap),
+        None,
+    );
+    let features = extract_features(&data);
+    assert_eq!(features.len(), 2);
+
+    let road = &features[0];
+    assert_eq!((road.id, road.layer), (4, "highway"));
+    assert!(matches!(road.geometry, Geometry::LineString(_)));
+
+    let wood = &features[1];
+    assert_eq!(
+        (wood.id, wood.osm_type, wood.layer),
+        (1, "relation", "park")
+    );
+    let Geometry::Polygon(rings) = &wood.geometry else {
+        panic!("wood should be a single polygon");
+    };
+    assert_eq!(rings.len(), 2);
+    assert_eq!(rings[0].len(), 5);
+    assert_eq!(rings[0].first(), rings[0].last());
+    assert_eq!(rings[1].len(), 4);
+}
+
+#[test]
+fn writes_geojson_and_flatgeobuf() {
+    let data = read_osm_objects(
+        OsmXmlReader::new(FIXTURE.as_bytes()).map(Result::unwrap),
+        None,
+    );
+    let features = extract_features(&data);
+
+    let mut geojson = Vec::new();
+    write_features_geojson(&features, &mut geojson).unwrap();
+    let geojson: serde_json::Value = serde_json::from_slice(&geojson).unwrap();
+    assert_eq!(geojson["features"][1]["geometry"]["type"], "Polygon");
+    assert_eq!(geojson["features"][1]["properties"]["natural"], "wood");
+
+    let mut fgb = Vec::new();
+    write_flatgeobuf(&features, &mut fgb).unwrap();
+    assert_eq!(&fgb[..8], b"fgb\x03fgb\x00");
+    // Header and features are each a size-prefixed buffer
+    let mut pos = 8;
+    let mut buffers = 0;
+    while pos < fgb.len() {
+        pos += 4 + u32::from_le_bytes(fgb[pos..pos + 4].try_into().unwrap()) as usize;
+        buffers += 1;
+    }
+    assert_eq!((pos, buffers), (fgb.len(), 1 + features.len()));
+}
+
+#[test]
+fn reserved_properties_win_over_tags() {
+    let features = [Feature {
+        id: 4,
+        osm_type: "way",
+        layer: "highway",
+        tags: vec![
+            ("highway".to_string(), "primary".to_string()),
+            ("layer".to_string(), "1".to_string()),
+            ("osm_id".to_string(), "7".to_string()),
+        ],
+        geometry: Geometry::LineString(vec![(0.0, 0.0), (0.01, 0.0)]),
+    }];
+    let mut geojson = Vec::new();
+    write_features_geojson(&features, &mut geojson).unwrap();
+    let geojson: serde_json::Value = serde_json::from_slice(&geojson).unwrap();
+    let properties = &geojson["features"][0]["properties"];
+    assert_eq!(properties["layer"], "highway");
+    assert_eq!(properties["osm_id"], 4);
+    assert_eq!(properties["highway"], "primary");
+}
+
+#[test]
+fn pois_and_places_keep_all_their_tags() {
+    let fixture = r#"<osm version="0.6">
+      <node id="1" lat="0.0" lon="0.0">
+        <tag k="amenity" v="cafe"/>
+        <tag k="name" v="Corner"/>
+        <tag k="opening_hours" v="Mo-Fr 08:00-18:00"/>
+      </node>
+      <node id="2" lat="0.01" lon="0.01">
+        <tag k="place" v="village"/>
+        <tag k="name" v="Hill"/>
+        <tag k="population" v="300"/>
+      </node>
+    </osm>"#;
+    let data = read_osm_objects(
+        OsmXmlReader::new(fixture.as_bytes()).map(Result::unwrap),
+        None,
+    );
+    let features = extract_features(&data);
+    let tag = |feature: &Feature, key: &str| {
+        feature
+            .tags
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value.clone())
+    };
+
+    let cafe = features.iter().find(|f| f.layer == "poi").unwrap();
+    assert_eq!(cafe.id, 1);
+    assert_eq!(
+        tag(cafe, "opening_hours").as_deref(),
+        Some("Mo-Fr 08:00-18:00")
+    );
+    assert_eq!(tag(cafe, "name").as_deref(), Some("Corner"));
+
+    let village = features.iter().find(|f| f.layer == "place").unwrap();
+    assert_eq!(village.id, 2);
+    assert_eq!(tag(village, "population").as_deref(), Some("300"));
+    assert_eq!(tag(village, "place").as_deref(), Some("village"));
+}
+
+/// Minimal flatbuffers reading over one size-prefixed buffer, enough to check
+/// what `write_flatgeobuf` writes.
+struct Buffer<'a>(&'a [u8]);
+
+impl Buffer<'_> {
+    fn u16(&self, pos: usize) -> usize {
+        u16::from_le_bytes(self.0[pos..pos + 2].try_into().unwrap()) as usize
+    }
+
+    fn u32(&self, pos: usize) -> usize {
+        u32::from_le_bytes(self.0[pos..pos + 4].try_into().unwrap()) as usize
+    }
+
+    fn root(&self) -> usize {
+        4 + self.u32(4)
+    }
+
+    /// Where a table's field is stored, if it is present.
+    fn field(&self, table: usize, index: usize) -> Option<usize> {
+        let vtable =
+            table - i32::from_le_bytes(self.0[table..table + 4].try_into().unwrap()) as usize;
+        let slot = 4 + 2 * index;
+        if slot >= self.u16(vtable) {
+            return None;
+        }
+        let offset = self.u16(vtable + slot);
+        (offset != 0).then_some(table + offset)
+    }
+
+    fn deref(&self, pos: usize) -> usize {
+        pos + self.u32(pos)
+    }
+
+    /// Start and length of the vector a field points to.
+    fn vector(&self, table: usize, index: usize) -> (usize, usize) {
+        let vector = self.deref(self.field(table, index).unwrap());
+        (vector + 4, self.u32(vector))
+    }
+
+    fn string(&self, table: usize, index: usize) -> &str {
+        let (start, len) = self.vector(table, index);
+        std::str::from_utf8(&self.0[start..start + len]).unwrap()
+    }
+
+    fn f64s(&self, table: usize, index: usize) -> Vec<f64> {
+        let (start, len) = self.vector(table, index);
+        (0..len)
+            .map(|i| {
+                f64::from_le_bytes(self.0[start + 8 * i..start + 8 * i + 8].try_into().unwrap())
+            })
+            .collect()
+    }
+
+    fn u32s(&self, table: usize, index: usize) -> Vec<usize> {
+        let (start, len) = self.vector(table, index);
+        (0..len).map(|i| self.u32(start + 4 * i)).collect()
+    }
+
+    fn tables(&self, table: usize, index: usize) -> Vec<usize> {
+        let (start, len) = self.vector(table, index);
+        (0..len).map(|i| self.deref(start + 4 * i)).collect()
+    }
+
+    fn table(&self, table: usize, index: usize) -> usize {
+        self.deref(self.field(table, index).unwrap())
+    }
+
+    fn u8(&self, table: usize, index: usize) -> Option<u8> {
+        self.field(table, index).map(|pos| self.0[pos])
+    }
+}
+
+#[test]
+fn flatgeobuf_decodes() {
+    let data = read_osm_objects(
+        OsmXmlReader::new(FIXTURE.as_bytes()).map(Result::unwrap),
+        None,
+    );
+    let features = extract_features(&data);
+    let mut fgb = Vec::new();
+    write_flatgeobuf(&features, &mut fgb).unwrap();
+
+    let mut buffers = Vec::new();
+    let mut pos = 8;
+    while pos < fgb.len() {
+        let end = pos + 4 + u32::from_le_bytes(fgb[pos..pos + 4].try_into().unwrap()) as usize;
+        buffers.push(Buffer(&fgb[pos..end]));
+        pos = end;
+    }
+
+    let header = &buffers[0];
+    let root = header.root();
+    assert_eq!(header.string(root, 0), "maps");
+    assert_eq!(header.f64s(root, 1), [0.0, -0.001, 0.01, 0.01]);
+    assert_eq!(header.u8(root, 2), Some(0));
+    let columns: Vec<_> = header
+        .tables(root, 7)
+        .into_iter()
+        .map(|column| (header.string(column, 0), header.u8(column, 1).unwrap()))
+        .collect();
+    assert_eq!(
+        columns,
+        [
+            ("osm_id", 7),
+            ("osm_type", 11),
+            ("layer", 11),
+            ("name", 11),
+            ("tags", 12)
+        ]
+    );
+    let count = header.field(root, 8).unwrap();
+    assert_eq!(
+        u64::from_le_bytes(header.0[count..count + 8].try_into().unwrap()),
+        2
+    );
+    assert_eq!(header.u16(header.field(root, 9).unwrap()), 0);
+    let crs = header.table(root, 10);
+    assert_eq!(header.string(crs, 0), "EPSG");
+    assert_eq!(header.u32(header.field(crs, 1).unwrap()), 4326);
+
+    // The road, a line string with its id first in the properties
+    let road = &buffers[1];
+    let geometry = road.table(road.root(), 0);
+    assert_eq!(road.u8(geometry, 6), Some(2));
+    assert_eq!(road.f64s(geometry, 1), [0.0, -0.001, 0.01, -0.001]);
+    assert!(road.field(geometry, 0).is_none());
+    let (start, _) = road.vector(road.root(), 1);
+    assert_eq!(road.u16(start), 0);
+    assert_eq!(
+        i64::from_le_bytes(road.0[start + 2..start + 10].try_into().unwrap()),
+        4
+    );
+
+    // The wood, a polygon whose outer ring ends after five points
+    let wood = &buffers[2];
+    let geometry = wood.table(wood.root(), 0);
+    assert_eq!(wood.u8(geometry, 6), Some(3));
+    assert_eq!(wood.u32s(geometry, 0), [5, 9]);
+    let xy = wood.f64s(geometry, 1);
+    assert_eq!(xy.len(), 18);
+    assert_eq!(xy[..2], xy[8..10]);
+}
+
+// 0.01 degrees of latitude, 1111.95 m, in two legs
+fn route() -> Vec<Coord> {
+    vec![
+        Coord::new(13.0, 52.0),
+        Coord::new(13.0, 52.004),
+        Coord::new(13.0, 52.01),
+    ]
+}
+
+#[test]
+fn writes_route_as_gpx() {
+    let mut gpx = Vec::new();
+    write_gpx(&route(), "A & B", &mut gpx).unwrap();
+    let gpx = String::from_utf8(gpx).unwrap();
+    assert!(gpx.contains("<name>A &amp; B</name>"));
+    assert!(gpx.contains("<desc>Distance: 1111.9 m</desc>"));
+    assert_eq!(gpx.matches("<trkpt ").count(), 3);
+    assert!(gpx.contains(r#"<trkpt lat="52.0040000" lon="13.0000000"></trkpt>"#));
+}
+
+#[test]
+fn writes_route_as_geojson() {
+    let geojson = route_to_geojson(&route());
+    assert_eq!(geojson["geometry"]["type"], "LineString");
+    assert_eq!(geojson["geometry"]["coordinates"][2][0], 13.0);
+    assert_eq!(geojson["geometry"]["coordinates"][2][1], 52.01);
+    let distance = geojson["properties"]["distance_m"].as_f64().unwrap();
+    assert!((distance - 1111.95).abs() < 0.01);
+}
@@ -0,0 +1,43 @@
+use image::{Rgba, RgbaImage};
+use maps::dem::Dem;
+use maps::drawing::Projection;
+use maps::georef::{write_geotiff, write_world_file};
+
+mod common;
+use common::temp_dir;
+
+#[test]
+fn writes_world_file_and_prj() {
+    let dir = temp_dir("georef-pgw");
+    let projection = Projection::new((10.0, 50.0, 11.0, 51.0), 100.0, 200.0);
+    write_world_file(dir.join("map.png"), &projection).unwrap();
+
+    let world = std::fs::read_to_string(dir.join("map.pgw")).unwrap();
+    let values: Vec<f64> = world.lines().map(|line| line.parse().unwrap()).collect();
+    let expected = [0.01, 0.0, 0.0, -0.005, 10.005, 50.9975];
+    for (value, expected) in values.iter().zip(expected) {
+        assert!((value - expected).abs() < 1e-9, "{} != {}", value, expected);
+    }
+    assert_eq!(values.len(), 6);
+    let prj = std::fs::read_to_string(dir.join("map.prj")).unwrap();
+    assert!(prj.starts_with("GEOGCS[\"GCS_WGS_1984\""));
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn geotiff_places_pixels_where_they_were_drawn() {
+    let dir = temp_dir("georef-tif");
+    let path = dir.join("map.tif");
+    let projection = Projection::new((10.0, 50.0, 11.0, 51.0), 10.0, 10.0);
+    let image = RgbaImage::from_fn(10, 10, |x, y| Rgba([(x * 10 + y) as u8, 0, 0, 255]));
+    write_geotiff(&image, &projection, &path).unwrap();
+
+    // Read the red band back as heights
+    let dem = Dem::open(&path).unwrap();
+    std::fs::remove_dir_all(dir).unwrap();
+    for (x, y) in [(0, 0), (3, 7), (9, 9)] {
+        let (lon, lat) = projection.unproject(x as f64 + 0.5, y as f64 + 0.5);
+        let red = dem.elevation(lon, lat).unwrap();
+        assert!((red - (x * 10 + y) as f64).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,112 @@
+use maps::graph::build_graph;
+use maps::import::{merge_overlay, read_geojson};
+use maps::osm::read_osm_objects;
+use maps::osm_xml::OsmXmlReader;
+use maps::types::category::Category;
+use maps::types::coord::Coord;
+
+// A planned road whose west end is about 2 m from the end of the existing
+// road, a building with a courtyard, a cafe, and a feature with nothing to
+// draw.
+const OVERLAY: &str = r#"{
+  "type": "FeatureCollection",
+  "features": [
+    {
+      "type": "Feature",
+      "properties": {"highway": "residential", "name": "New Lane"},
+      "geometry": {"type": "LineString", "coordinates": [[0.01002, 0.0], [0.02, 0.0]]}
+    },
+    {
+      "type": "Feature",
+      "properties": {"building": "yes", "levels": 3},
+      "geometry": {
+        "type": "Polygon",
+        "coordinates": [
+          [[0.0, 0.001], [0.004, 0.001], [0.004, 0.005], [0.0, 0.005], [0.0, 0.001]],
+          [[0.001, 0.002], [0.003, 0.002], [0.003, 0.004], [0.001, 0.004], [0.001, 0.002]]
+        ]
+      }
+    },
+    {
+      "type": "Feature",
+      "properties": {"amenity": "cafe", "name": "Corner Cafe"},
+      "geometry": {"type": "Point", "coordinates": [0.005, 0.0005]}
+    },
+    {
+      "type": "Feature",
+      "properties": {"note": "survey"},
+      "geometry": {"type": "Point", "coordinates": [0.0, 0.0]}
+    }
+  ]
+}"#;
+
+const BASE: &str = r#"<osm version="0.6">
+  <node id="1" lat="0.0" lon="0.0"/>
+  <node id="2" lat="0.0" lon="0.01"/>
+  <node id="3" lat="0.0005" lon="0.002">
+    <tag k="amenity" v="bench"/>
+  </node>
+  <way id="1">
+    <nd ref="1"/>
+    <nd ref="2"/>
+    <tag k="highway" v="residential"/>
+  </way>
+</osm>
+"#;
+
+fn read_overlay() -> maps::types::cached_data::CachedData {
+    let path = std::env::temp_dir().join(format!("maps-overlay-{}.geojson", std::process::id()));
+    std::fs::write(&path, OVERLAY).unwrap();
+    let overlay = read_geojson(&path).unwrap();
+    std::fs::remove_file(path).unwrap();
+    overlay
+}
+
+#[test]
+fn geojson_features_are_read_as_tagged_objects() {
+    let overlay = read_overlay();
+    assert_eq!(overlay.highways.len(), 1);
+    assert_eq!(overlay.highways[0].id, -1);
+    assert_eq!(overlay.highways[0].name.as_deref(), Some("New Lane"));
+
+    // The building keeps its courtyard
+    assert!(overlay.buildings.is_empty());
+    assert_eq!(overlay.polygons.len(), 1);
+    let (category, polygon) = &overlay.polygons[0];
+    assert_eq!(*category, Category::Building);
+    assert_eq!(polygon.len(), 2);
+    assert_eq!(polygon[1][0], (0.001, 0.002));
+
+    assert_eq!(overlay.pois.len(), 1);
+    assert_eq!(overlay.pois[0].id, -3);
+    assert!(overlay.pois[0].has_kind("amenity", Some("cafe")));
+    assert!(overlay.graph.is_empty());
+}
+
+#[test]
+fn overlay_ids_move_below_the_data_and_road_ends_snap() {
+    let mut data = read_osm_objects(OsmXmlReader::new(BASE.as_bytes()).map(Result::unwrap), None);
+    data.graph = build_graph(&data.highways);
+    merge_overlay(&mut data, read_overlay());
+
+    // Overlay ids count down from below the lowest id already there
+    let ids: Vec<i64> = data.highways.iter().map(|highway| highway.id).collect();
+    assert_eq!(ids, [1, -1]);
+    let ids: Vec<i64> = data.pois.iter().map(|poi| poi.id).collect();
+    assert_eq!(ids, [3, -3]);
+    assert_eq!(data.polygons.len(), 1);
+
+    // The new road starts on the existing road's end, so both are routable
+    // as one network
+    let joint = Coord::new(0.01, 0.0);
+    assert_eq!(data.highways[1].coords[0], (0.01, 0.0));
+    assert_eq!(data.graph[&joint].len(), 2);
+    assert_eq!(data.component.len(), 3);
+
+    // A second overlay moves below the first
+    merge_overlay(&mut data, read_overlay());
+    let ids: Vec<i64> = data.highways.iter().map(|highway| highway.id).collect();
+    assert_eq!(ids, [1, -1, -4]);
+    let ids: Vec<i64> = data.pois.iter().map(|poi| poi.id).collect();
+    assert_eq!(ids, [3, -3, -6]);
+}
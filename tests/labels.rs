@@ -0,0 +1,38 @@
+use image::{Rgba, RgbaImage};
+use maps::labels::Labeler;
+
+fn blank() -> RgbaImage {
+    RgbaImage::from_pixel(300, 100, Rgba([0, 0, 0, 0]))
+}
+
+#[test]
+fn overlapping_labels_are_skipped() {
+    let mut img = blank();
+    let mut labeler = Labeler::new();
+    labeler.draw_point_label(&mut img, "Alpha", 60.0, 50.0, 15.0);
+    let first = img.clone();
+    assert_ne!(first, blank());
+
+    // Over the first label, so nothing changes
+    labeler.draw_point_label(&mut img, "Beta", 70.0, 55.0, 15.0);
+    assert_eq!(img, first);
+
+    // Clear of it, so it is drawn
+    labeler.draw_point_label(&mut img, "Gamma", 220.0, 50.0, 15.0);
+    assert_ne!(img, first);
+}
+
+#[test]
+fn labels_must_fit_and_names_are_not_repeated_nearby() {
+    let mut img = blank();
+    let mut labeler = Labeler::new();
+    // Running off the left edge
+    labeler.draw_point_label(&mut img, "Alpha", 5.0, 50.0, 15.0);
+    assert_eq!(img, blank());
+
+    labeler.draw_point_label(&mut img, "Alpha", 60.0, 50.0, 15.0);
+    let first = img.clone();
+    // Clear of the first, but the same name too close by
+    labeler.draw_point_label(&mut img, "Alpha", 220.0, 50.0, 15.0);
+    assert_eq!(img, first);
+}
@@ -0,0 +1,117 @@
+use maps::graph::build_graph;
+use maps::import::read_gpx;
+use maps::matching::{match_track, EdgeIndex};
+use maps::types::coord::Coord;
+use maps::types::highway::Highway;
+
+mod common;
+use common::highway;
+
+// Way 1 runs east to a junction where way 2 turns north and way 3 carries on
+// east. Way 4 is a separate road further east.
+fn highways() -> Vec<Highway> {
+    vec![
+        highway(1, &[(0.0, 0.0), (0.002, 0.0), (0.004, 0.0)]),
+        highway(2, &[(0.004, 0.0), (0.004, 0.002), (0.004, 0.004)]),
+        highway(3, &[(0.004, 0.0), (0.006, 0.0), (0.008, 0.0)]),
+        highway(4, &[(0.02, 0.0), (0.02, 0.004)]),
+    ]
+}
+
+/// Writes the points as a GPX track and reads them back.
+fn track(name: &str, points: &[(f64, f64)]) -> Vec<Coord> {
+    let trkpts: String = points
+        .iter()
+        .map(|(lon, lat)| format!("<trkpt lat=\"{}\" lon=\"{}\"/>\n", lat, lon))
+        .collect();
+    let gpx = format!(
+        "<gpx version=\"1.1\"><trk><trkseg>\n{}</trkseg></trk></gpx>\n",
+        trkpts
+    );
+    let path = std::env::temp_dir().join(format!("maps-{}-{}.gpx", name, std::process::id()));
+    std::fs::write(&path, gpx).unwrap();
+    let track = read_gpx(&path).unwrap();
+    std::fs::remove_file(path).unwrap();
+    track
+}
+
+/// Fixes every 0.0003 degrees, about 33 m, from `from` to `to`, alternately
+/// about 9 m to either side.
+fn noisy(from: (f64, f64), to: (f64, f64)) -> Vec<(f64, f64)> {
+    let steps = ((to.0 - from.0).abs().max((to.1 - from.1).abs()) / 0.0003) as usize;
+    (0..=steps)
+        .map(|i| {
+            let t = i as f64 / steps as f64;
+            let noise = if i % 2 == 0 { 0.00008 } else { -0.00008 };
+            (
+                from.0 + (to.0 - from.0) * t + noise,
+                from.1 + (to.1 - from.1) * t - noise,
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn noisy_track_is_matched_around_the_turn() {
+    let highways = highways();
+    let graph = build_graph(&highways);
+    let index = EdgeIndex::new(&graph);
+    let mut points = noisy((0.0, 0.0), (0.0039, 0.0));
+    points.extend(noisy((0.004, 0.0003), (0.004, 0.0039)));
+
+    let matched = match_track(&graph, &index, &track("turn", &points)).unwrap();
+    assert_eq!(matched.ways, [1, 2]);
+    assert_eq!(matched.snapped.len(), points.len());
+    assert!(matched.path.contains(&Coord::new(0.004, 0.0)));
+    assert!(!matched.path.contains(&Coord::new(0.006, 0.0)));
+    // Every fix is snapped onto its road, within the noise
+    for &(i, snapped) in &matched.snapped {
+        let fix = points[i];
+        assert!((fix.0 - snapped.lon).abs() < 0.0001 && (fix.1 - snapped.lat).abs() < 0.0001);
+    }
+    assert!(matched.confidence > 0.0 && matched.confidence <= 1.0);
+}
+
+#[test]
+fn gap_in_the_track_breaks_the_match() {
+    // From way 1 the track jumps to way 4, which no road leads to
+    let highways = highways();
+    let graph = build_graph(&highways);
+    let index = EdgeIndex::new(&graph);
+    let mut points = noisy((0.0, 0.0), (0.003, 0.0));
+    let first_leg = points.len();
+    points.extend(noisy((0.02, 0.001), (0.02, 0.003)));
+
+    let matched = match_track(&graph, &index, &track("gap", &points)).unwrap();
+    assert_eq!(matched.ways, [1, 4]);
+    // The path jumps straight from the last fix on way 1 to the first on way 4
+    let split = matched
+        .snapped
+        .iter()
+        .position(|&(i, _)| i >= first_leg)
+        .unwrap();
+    let (before, after) = (matched.snapped[split - 1].1, matched.snapped[split].1);
+    let jump = matched
+        .path
+        .iter()
+        .position(|&node| node == before)
+        .unwrap();
+    assert_eq!(matched.path[jump + 1], after);
+    assert!(!matched.path.contains(&Coord::new(0.004, 0.0)));
+}
+
+#[test]
+fn repeated_fixes_are_thinned_out() {
+    let graph = build_graph(&highways());
+    let index = EdgeIndex::new(&graph);
+    // Every fix recorded twice, as a receiver standing still would
+    let points: Vec<(f64, f64)> = noisy((0.0, 0.0), (0.0039, 0.0))
+        .into_iter()
+        .flat_map(|point| [point, point])
+        .collect();
+
+    let matched = match_track(&graph, &index, &track("repeated", &points)).unwrap();
+    let indices: Vec<usize> = matched.snapped.iter().map(|&(i, _)| i).collect();
+    let expected: Vec<usize> = (0..points.len()).step_by(2).collect();
+    assert_eq!(indices, expected);
+}
@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+
+use maps::graph::{build_graph, find_path};
+use maps::matrix::distance_matrix;
+use maps::types::coord::Coord;
+
+mod common;
+use common::highway;
+
+// A block with a diagonal, and a separate road that cannot be reached.
+fn points() -> Vec<Coord> {
+    [
+        (0.0, 0.0),
+        (0.002, 0.0),
+        (0.002, 0.001),
+        (0.0, 0.001),
+        (0.01, 0.01),
+    ]
+    .iter()
+    .map(|&(lon, lat)| Coord::new(lon, lat))
+    .collect()
+}
+
+#[test]
+fn matrix_is_symmetric_on_two_way_roads() {
+    let graph = build_graph(&[
+        highway(1, &[(0.0, 0.0), (0.002, 0.0), (0.002, 0.001)]),
+        highway(2, &[(0.002, 0.001), (0.0, 0.001), (0.0, 0.0)]),
+        highway(3, &[(0.0, 0.0), (0.002, 0.001)]),
+        highway(4, &[(0.01, 0.01), (0.011, 0.01)]),
+    ]);
+    let points = points();
+    let matrix = distance_matrix(&graph, &HashMap::new(), &points, &points);
+
+    for i in 0..points.len() {
+        assert_eq!(matrix[i][i], Some(0));
+        for j in 0..points.len() {
+            assert_eq!(matrix[i][j], matrix[j][i]);
+            // Every entry matches a search for just that pair
+            let expected = (i != j)
+                .then(|| find_path(&graph, &HashMap::new(), points[i], points[j]))
+                .map_or(Some(0), |path| path.map(|(_, cost)| cost));
+            assert_eq!(matrix[i][j], expected);
+        }
+    }
+    assert_eq!(matrix[0][4], None);
+
+    // Rows follow the origins and columns the destinations
+    let row = distance_matrix(&graph, &HashMap::new(), &points[..1], &points[1..3]);
+    assert_eq!(row, vec![vec![matrix[0][1], matrix[0][2]]]);
+}
+
+#[test]
+fn matrix_obeys_turn_restrictions() {
+    use maps::types::restriction::{RestrictionKind, TurnRestriction};
+
+    // W - C - E along way 1, C - N on way 2 and E - NE - N on way 3, with no
+    // turning from way 1 onto way 2 at C
+    let [w, c, e, n, ne] = [
+        (0.0, 0.0),
+        (0.001, 0.0),
+        (0.002, 0.0),
+        (0.001, 0.001),
+        (0.002, 0.001),
+    ];
+    let graph = build_graph(&[
+        highway(1, &[w, c, e]),
+        highway(2, &[c, n]),
+        highway(3, &[e, ne, n]),
+    ]);
+    let via = Coord::new(c.0, c.1);
+    let restrictions = HashMap::from([(
+        via,
+        vec![TurnRestriction {
+            from_way: 1,
+            via,
+            to_way: 2,
+            kind: RestrictionKind::No,
+        }],
+    )]);
+    let points: Vec<Coord> = [w, n]
+        .iter()
+        .map(|&(lon, lat)| Coord::new(lon, lat))
+        .collect();
+
+    let free = distance_matrix(&graph, &HashMap::new(), &points, &points);
+    let matrix = distance_matrix(&graph, &restrictions, &points, &points);
+    let (_, expected) = find_path(&graph, &restrictions, points[0], points[1]).unwrap();
+    assert_eq!(matrix[0][1], Some(expected));
+    assert!(matrix[0][1] > free[0][1]);
+    // Coming the other way the turn is allowed
+    assert_eq!(matrix[1][0], free[1][0]);
+}
@@ -0,0 +1,108 @@
+use maps::mvt::write_vector_tiles;
+use maps::osm::read_osm_objects;
+use maps::osm_xml::OsmXmlReader;
+
+// A road crossing the prime meridian, so it falls into two tiles at every
+// zoom but the first, and a shop just east of it. Both are far enough north
+// to stay out of the tile buffer along the equator.
+const FIXTURE: &str = r#"<osm version="0.6">
+  <node id="1" lat="10.001" lon="-1.0"/>
+  <node id="2" lat="10.001" lon="1.0"/>
+  <node id="3" lat="10.0005" lon="0.5">
+    <tag k="shop" v="bakery"/>
+    <tag k="name" v="Crust"/>
+  </node>
+  <way id="1">
+    <nd ref="1"/>
+    <nd ref="2"/>
+    <tag k="highway" v="residential"/>
+  </way>
+</osm>
+"#;
+
+#[test]
+fn writes_tiles_for_each_zoom() {
+    let data = read_osm_objects(
+        OsmXmlReader::new(FIXTURE.as_bytes()).map(Result::unwrap),
+        None,
+    );
+    let dir = std::env::temp_dir().join(format!("maps-mvt-{}", std::process::id()));
+    let count = write_vector_tiles(&data, 0..=2, &dir).unwrap();
+    assert_eq!(count, 1 + 2 + 2);
+
+    // The shop is only in the tile east of the meridian
+    let west = std::fs::read(dir.join("2/1/1.mvt")).unwrap();
+    let east = std::fs::read(dir.join("2/2/1.mvt")).unwrap();
+    let contains = |tile: &[u8], text: &str| {
+        tile.windows(text.len())
+            .any(|window| window == text.as_bytes())
+    };
+    assert!(contains(&west, "highway") && !contains(&west, "Crust"));
+    assert!(contains(&east, "highway") && contains(&east, "Crust"));
+
+    let metadata: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(dir.join("metadata.json")).unwrap()).unwrap();
+    assert_eq!(metadata["maxzoom"], 2);
+    assert_eq!(metadata["vector_layers"].as_array().unwrap().len(), 2);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+fn write_tiles(
+    fixture: &str,
+    zooms: std::ops::RangeInclusive<u32>,
+    name: &str,
+) -> (usize, std::path::PathBuf) {
+    let data = read_osm_objects(
+        OsmXmlReader::new(fixture.as_bytes()).map(Result::unwrap),
+        None,
+    );
+    let dir = std::env::temp_dir().join(format!("maps-mvt-{}-{}", name, std::process::id()));
+    let count = write_vector_tiles(&data, zooms, &dir).unwrap();
+    (count, dir)
+}
+
+#[test]
+fn writes_tiles_past_the_u32_range_of_tile_units() {
+    // East of the meridian at zoom 21, tile x times the 4096 units of a tile
+    // no longer fits in 32 bits
+    let fixture = r#"<osm version="0.6">
+      <node id="1" lat="10.0" lon="0.5"><tag k="shop" v="bakery"/></node>
+    </osm>"#;
+    let (count, dir) = write_tiles(fixture, 21..=21, "deep");
+    assert_eq!(count, 1);
+    let column = std::fs::read_dir(dir.join("21"))
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap();
+    let x: u32 = column.file_name().to_str().unwrap().parse().unwrap();
+    assert_eq!(x, 1_051_488);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn splits_large_areas_into_every_tile_they_cover() {
+    // About 3.3 by 3.4 tiles at zoom 12, so four by four tiles
+    let fixture = r#"<osm version="0.6">
+      <node id="1" lat="10.01" lon="0.01"/>
+      <node id="2" lat="10.01" lon="0.3"/>
+      <node id="3" lat="10.3" lon="0.3"/>
+      <node id="4" lat="10.3" lon="0.01"/>
+      <way id="1">
+        <nd ref="1"/>
+        <nd ref="2"/>
+        <nd ref="3"/>
+        <nd ref="4"/>
+        <nd ref="1"/>
+        <tag k="landuse" v="forest"/>
+      </way>
+    </osm>"#;
+    let (count, dir) = write_tiles(fixture, 12..=12, "area");
+    assert_eq!(count, 16);
+    // A tile in the middle is covered by the buffered tile square
+    let tile = std::fs::read(dir.join("12/2049/1930.mvt")).unwrap();
+    assert!(tile
+        .windows("landuse".len())
+        .any(|window| window == b"landuse"));
+    std::fs::remove_dir_all(dir).unwrap();
+}
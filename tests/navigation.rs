@@ -0,0 +1,63 @@
+use maps::graph::build_graph;
+use maps::navigation::{instructions, Maneuver};
+use maps::types::coord::Coord;
+use maps::types::highway::Highway;
+
+mod common;
+use common::named_highway;
+
+// Main Street runs north into High Street across Side Lane. High Street bends
+// right where North Lane carries on north, then the route turns left onto
+// Park Road, which bends right away from any junction.
+fn highways() -> Vec<Highway> {
+    vec![
+        named_highway(1, "Main Street", &[(0.0, 0.0), (0.0, 0.001)]),
+        named_highway(2, "Side Lane", &[(0.0, 0.001), (-0.001, 0.001)]),
+        named_highway(
+            3,
+            "High Street",
+            &[(0.0, 0.001), (0.0, 0.002), (0.001, 0.002)],
+        ),
+        named_highway(4, "North Lane", &[(0.0, 0.002), (0.0, 0.003)]),
+        named_highway(
+            5,
+            "Park Road",
+            &[(0.001, 0.002), (0.001, 0.003), (0.002, 0.004)],
+        ),
+    ]
+}
+
+#[test]
+fn turns_are_announced_at_junctions() {
+    let highways = highways();
+    let graph = build_graph(&highways);
+    let path: Vec<Coord> = [
+        (0.0, 0.0),
+        (0.0, 0.001),
+        (0.0, 0.002),
+        (0.001, 0.002),
+        (0.001, 0.003),
+        (0.002, 0.004),
+    ]
+    .iter()
+    .map(|&(lon, lat)| Coord::new(lon, lat))
+    .collect();
+
+    let steps = instructions(&path, &graph, &highways);
+    let maneuvers: Vec<(Maneuver, Option<&str>)> = steps
+        .iter()
+        .map(|step| (step.maneuver, step.street.as_deref()))
+        .collect();
+    assert_eq!(
+        maneuvers,
+        [
+            (Maneuver::Depart, Some("Main Street")),
+            (Maneuver::Continue, Some("High Street")),
+            (Maneuver::Right, Some("High Street")),
+            (Maneuver::Left, Some("Park Road")),
+            (Maneuver::Arrive, None),
+        ]
+    );
+    assert_eq!(steps[2].location, path[2]);
+    assert_eq!(steps[1].text(), "Continue onto High Street in 110 m");
+}
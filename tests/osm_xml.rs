@@ -0,0 +1,117 @@
+use maps::graph::{build_graph, find_path};
+use maps::osm::read_osm_objects;
+use maps::osm_xml::OsmXmlReader;
+use maps::types::cached_data::CachedData;
+use maps::types::coord::Coord;
+use maps::types::restriction::RestrictionKind;
+
+// The crossroads from the turn restriction tests, with no left turn from
+// way 1 onto way 2, a building and a town. Node 6 and way 5 were deleted in
+// JOSM and must not show up.
+const FIXTURE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<osm version="0.6" generator="JOSM">
+  <node id="1" lat="0.0" lon="0.0"/>
+  <node id="2" lat="0.0" lon="0.001"/>
+  <node id="3" lat="0.0" lon="0.002"/>
+  <node id="4" lat="0.001" lon="0.001"/>
+  <node id="5" lat="0.001" lon="0.002">
+    <tag k="place" v="town"/>
+    <tag k="name" v="Crossroads"/>
+  </node>
+  <node id="6" action="delete" lat="0.5" lon="0.5">
+    <tag k="place" v="city"/>
+    <tag k="name" v="Gone"/>
+  </node>
+  <node id="10" lat="0.0005" lon="0.0002"/>
+  <node id="11" lat="0.0005" lon="0.0004"/>
+  <node id="12" lat="0.0007" lon="0.0004"/>
+  <way id="1">
+    <nd ref="1"/>
+    <nd ref="2"/>
+    <nd ref="3"/>
+    <tag k="highway" v="residential"/>
+    <tag k="name" v="Main &amp; High"/>
+  </way>
+  <way id="2">
+    <nd ref="2"/>
+    <nd ref="4"/>
+    <tag k="highway" v="residential"/>
+  </way>
+  <way id="3">
+    <nd ref="3"/>
+    <nd ref="5"/>
+    <nd ref="4"/>
+    <tag k="highway" v="residential"/>
+  </way>
+  <way id="4">
+    <nd ref="10"/>
+    <nd ref="11"/>
+    <nd ref="12"/>
+    <nd ref="10"/>
+    <tag k="building" v="yes"/>
+  </way>
+  <way id="5" action="delete">
+    <nd ref="1"/>
+    <nd ref="4"/>
+    <tag k="highway" v="primary"/>
+  </way>
+  <relation id="1">
+    <member type="way" ref="1" role="from"/>
+    <member type="node" ref="2" role="via"/>
+    <member type="way" ref="2" role="to"/>
+    <tag k="type" v="restriction"/>
+    <tag k="restriction" v="no_left_turn"/>
+  </relation>
+</osm>
+"#;
+
+fn fixture() -> CachedData {
+    let mut data = read_osm_objects(
+        OsmXmlReader::new(FIXTURE.as_bytes()).map(Result::unwrap),
+        None,
+    );
+    data.graph = build_graph(&data.highways);
+    data
+}
+
+#[test]
+fn reads_ways_by_category() {
+    let data = fixture();
+    assert_eq!(data.nodes.len(), 8);
+    assert_eq!(data.highways.len(), 3);
+    assert_eq!(data.highways[0].name.as_deref(), Some("Main & High"));
+    assert_eq!(
+        data.highways[2].coords,
+        vec![(0.002, 0.0), (0.002, 0.001), (0.001, 0.001)]
+    );
+    assert_eq!(data.buildings.len(), 1);
+    assert_eq!(data.buildings[0].len(), 4);
+}
+
+#[test]
+fn reads_tagged_nodes_and_skips_deleted_objects() {
+    let data = fixture();
+    assert_eq!(data.places.len(), 1);
+    assert_eq!(data.places[0].name, "Crossroads");
+    assert!(!data.nodes.contains_key(&6));
+    assert!(data.highways.iter().all(|highway| highway.id != 5));
+}
+
+#[test]
+fn restriction_is_applied_to_routes() {
+    let data = fixture();
+    let via = Coord::new(0.001, 0.0);
+    let restriction = &data.restrictions[&via][0];
+    assert_eq!((restriction.from_way, restriction.to_way), (1, 2));
+    assert!(matches!(restriction.kind, RestrictionKind::No));
+
+    // Going west to north at the crossroads means driving around the block
+    let (path, _) = find_path(
+        &data.graph,
+        &data.restrictions,
+        Coord::new(0.0, 0.0),
+        Coord::new(0.001, 0.001),
+    )
+    .unwrap();
+    assert_eq!(path.len(), 5);
+}
@@ -0,0 +1,101 @@
+use maps::drawing::RenderOptions;
+use maps::osm::read_osm_objects;
+use maps::osm_xml::OsmXmlReader;
+use maps::pdf::{write_pdf, PaperSize};
+
+// A named primary road 0.01 degrees long on the equator, which is 1113.2 m.
+const FIXTURE: &str = r#"<osm version="0.6">
+  <node id="1" lat="0.0" lon="0.0"/>
+  <node id="2" lat="0.0" lon="0.01"/>
+  <way id="1">
+    <nd ref="1"/>
+    <nd ref="2"/>
+    <tag k="highway" v="primary"/>
+    <tag k="name" v="High Street"/>
+  </way>
+</osm>
+"#;
+
+fn write_fixture_pdf(name: &str, options: &RenderOptions) -> String {
+    let data = read_osm_objects(
+        OsmXmlReader::new(FIXTURE.as_bytes()).map(Result::unwrap),
+        None,
+    );
+    let path = std::env::temp_dir().join(format!("maps-{}-{}.pdf", name, std::process::id()));
+    write_pdf(
+        &data,
+        &[],
+        options,
+        PaperSize::A4,
+        10000.0,
+        path.to_str().unwrap(),
+    )
+    .unwrap();
+    let pdf = std::fs::read(&path).unwrap();
+    std::fs::remove_file(path).unwrap();
+    String::from_utf8(pdf).unwrap()
+}
+
+#[test]
+fn xref_points_at_each_object() {
+    let pdf = write_fixture_pdf("xref", &RenderOptions::default());
+
+    let startxref: usize = pdf
+        .split("startxref\n")
+        .nth(1)
+        .and_then(|rest| rest.lines().next())
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!(pdf[startxref..].starts_with("xref\n0 6\n"));
+    let offsets: Vec<usize> = pdf[startxref..]
+        .lines()
+        .filter(|line| line.ends_with(" n "))
+        .map(|line| line[..10].parse().unwrap())
+        .collect();
+    assert_eq!(offsets.len(), 5);
+    for (i, offset) in offsets.into_iter().enumerate() {
+        assert!(pdf[offset..].starts_with(&format!("{} 0 obj\n", i + 1)));
+    }
+}
+
+#[test]
+fn page_is_paper_size_and_map_is_to_scale() {
+    let options = RenderOptions {
+        labels: false,
+        ..Default::default()
+    };
+    let pdf = write_fixture_pdf("scale", &options);
+    assert!(pdf.contains("/MediaBox [0 0 595.28 841.89]"));
+    assert!(pdf.contains("(Scale 1:10000) Tj"));
+
+    // 1113.2 m at 1:10000 is 111.32 mm on paper
+    let expected = 111.32 * 72.0 / 25.4;
+    let lines: Vec<Vec<&str>> = pdf.lines().map(|line| line.split(' ').collect()).collect();
+    let road = lines
+        .windows(2)
+        .find_map(|pair| match (&pair[0][..], &pair[1][..]) {
+            ([x1, y1, "m"], [x2, y2, "l"]) if y1 == y2 => {
+                Some(x2.parse::<f64>().unwrap() - x1.parse::<f64>().unwrap())
+            }
+            _ => None,
+        });
+    assert!((road.unwrap().abs() - expected).abs() < 0.05);
+}
+
+#[test]
+fn labels_are_drawn_as_glyph_outlines() {
+    // With no areas in the fixture, only glyph contours are closed on a line
+    // of their own
+    let contours = |pdf: &str| pdf.lines().filter(|&line| line == "h").count();
+    let without = RenderOptions {
+        labels: false,
+        ..Default::default()
+    };
+    assert_eq!(contours(&write_fixture_pdf("unlabelled", &without)), 0);
+
+    // Each letter of the name has at least one contour, for the halo and the text
+    let pdf = write_fixture_pdf("labelled", &RenderOptions::default());
+    assert!(contours(&pdf) >= 2 * "HighStreet".len());
+    assert!(pdf.contains("0.000 0.000 0.000 RG 1.50 w"));
+}
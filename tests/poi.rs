@@ -0,0 +1,55 @@
+use maps::osm::read_osm_objects;
+use maps::osm_xml::OsmXmlReader;
+use maps::poi::{pois_in_bbox, PoiStyles};
+
+// A bakery with a café, a plain shop and a railway node that is no station.
+const FIXTURE: &str = r#"<osm version="0.6">
+  <node id="1" lat="0.001" lon="0.001">
+    <tag k="amenity" v="cafe"/>
+    <tag k="shop" v="bakery"/>
+    <tag k="name" v="Crust"/>
+  </node>
+  <node id="2" lat="0.002" lon="0.002">
+    <tag k="shop" v="books"/>
+  </node>
+  <node id="3" lat="0.003" lon="0.003">
+    <tag k="railway" v="switch"/>
+  </node>
+</osm>
+"#;
+
+const EVERYWHERE: (f64, f64, f64, f64) = (-1.0, -1.0, 1.0, 1.0);
+
+#[test]
+fn pois_keep_every_matching_tag() {
+    let data = read_osm_objects(
+        OsmXmlReader::new(FIXTURE.as_bytes()).map(Result::unwrap),
+        None,
+    );
+    assert_eq!(data.pois.len(), 2);
+    assert_eq!(
+        data.pois[0].kinds,
+        vec![
+            ("amenity".to_string(), "cafe".to_string()),
+            ("shop".to_string(), "bakery".to_string())
+        ]
+    );
+
+    let ids = |key, value| -> Vec<i64> {
+        pois_in_bbox(&data.pois, key, value, EVERYWHERE)
+            .iter()
+            .map(|poi| poi.id)
+            .collect()
+    };
+    assert_eq!(ids("shop", Some("bakery")), vec![1]);
+    assert_eq!(ids("amenity", Some("cafe")), vec![1]);
+    assert_eq!(ids("shop", None), vec![1, 2]);
+    assert!(ids("amenity", Some("school")).is_empty());
+    let inside = pois_in_bbox(&data.pois, "shop", None, (0.0, 0.0, 0.0015, 0.0015));
+    assert_eq!(inside.len(), 1);
+
+    // The first rule matching any kind gives the symbol, here amenity's
+    let styles = PoiStyles::default();
+    let symbol = styles.symbol_for(&data.pois[0]).unwrap();
+    assert_eq!(symbol.color, styles.rules[3].2.color);
+}
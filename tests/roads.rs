@@ -0,0 +1,88 @@
+use maps::drawing::{render_image, road_style, road_visible, Layer, Projection, RenderOptions};
+use maps::types::cached_data::CachedData;
+use maps::types::highway::{Highway, HighwayClass};
+
+#[test]
+fn maps_tags_to_classes() {
+    let cases = [
+        ("motorway", HighwayClass::Motorway),
+        ("motorway_link", HighwayClass::Motorway),
+        ("trunk_link", HighwayClass::Trunk),
+        ("primary", HighwayClass::Primary),
+        ("secondary_link", HighwayClass::Secondary),
+        ("tertiary", HighwayClass::Tertiary),
+        ("residential", HighwayClass::Residential),
+        ("unclassified", HighwayClass::Residential),
+        ("service", HighwayClass::Service),
+        ("footway", HighwayClass::Path),
+        ("steps", HighwayClass::Path),
+        ("track", HighwayClass::Path),
+    ];
+    for (tag, class) in cases {
+        assert_eq!(HighwayClass::from_tag(tag), class, "{}", tag);
+    }
+}
+
+#[test]
+fn hides_minor_classes_when_zoomed_out() {
+    // About 0.37 and 1112 meters per pixel
+    let close = Projection::new((0.0, 0.0, 0.001, 0.001), 300.0, 300.0);
+    let far = Projection::new((0.0, 0.0, 1.0, 1.0), 100.0, 100.0);
+    for class in HighwayClass::ALL {
+        assert!(road_visible(class, &close), "{:?}", class);
+        let limit = road_style(class).max_meters_per_pixel;
+        assert_eq!(road_visible(class, &far), far.meters_per_pixel() <= limit);
+    }
+    assert!(road_visible(HighwayClass::Primary, &far));
+    assert!(!road_visible(HighwayClass::Residential, &far));
+    assert!(!road_visible(HighwayClass::Path, &far));
+}
+
+fn crossing() -> CachedData {
+    let road = |id, highway: &str, coords| Highway {
+        id,
+        highway: highway.to_string(),
+        name: None,
+        coords,
+    };
+    // The primary road comes first so only the drawing order puts it on top
+    CachedData {
+        highways: vec![
+            road(1, "primary", vec![(0.005, 0.001), (0.005, 0.009)]),
+            road(2, "residential", vec![(0.001, 0.005), (0.009, 0.005)]),
+        ],
+        ..Default::default()
+    }
+}
+
+fn render(data: &CachedData, projection: &Projection) -> image::RgbaImage {
+    let options = RenderOptions {
+        labels: false,
+        layers: vec![Layer::Roads],
+        ..Default::default()
+    };
+    render_image(data, projection, &[], &options)
+}
+
+#[test]
+fn draws_major_roads_over_minor_ones() {
+    let data = crossing();
+    let projection = Projection::new((0.0, 0.0, 0.01, 0.01), 300.0, 300.0);
+    let img = render(&data, &projection);
+    let primary = road_style(HighwayClass::Primary).color;
+    let residential = road_style(HighwayClass::Residential).color;
+    assert_eq!(*img.get_pixel(150, 150), primary);
+    assert_eq!(*img.get_pixel(60, 150), residential);
+}
+
+#[test]
+fn leaves_out_hidden_roads() {
+    let data = crossing();
+    // About 111 meters per pixel, far past the residential limit
+    let projection = Projection::new((0.0, 0.0, 0.01, 0.01), 10.0, 10.0);
+    let img = render(&data, &projection);
+    let residential = road_style(HighwayClass::Residential).color;
+    let primary = road_style(HighwayClass::Primary).color;
+    assert!(img.pixels().all(|&pixel| pixel != residential));
+    assert!(img.pixels().any(|&pixel| pixel == primary));
+}
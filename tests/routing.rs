@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+
+use maps::graph::{build_graph, find_path, reverse_graph};
+use maps::landmarks::Landmarks;
+use maps::routing::{Router, Strategy};
+use maps::types::coord::Coord;
+use maps::types::highway::Highway;
+use maps::types::restriction::{RestrictionKind, TurnRestriction};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+mod common;
+use common::highway;
+
+/// Checks that every strategy finds the cost `find_path` does between every
+/// pair of nodes.
+fn assert_strategies_agree(
+    highways: &[Highway],
+    restrictions: &HashMap<Coord, Vec<TurnRestriction>>,
+    pairs: &[(Coord, Coord)],
+) {
+    let graph = build_graph(highways);
+    let landmarks = Landmarks::select(&graph, &reverse_graph(&graph), 4);
+    let router = Router::new(&graph, restrictions, Some(&landmarks));
+    for &(start, goal) in pairs {
+        let expected = find_path(&graph, restrictions, start, goal).map(|(_, cost)| cost);
+        for strategy in Strategy::ALL {
+            let (path, _) = router.find_path(start, goal, strategy);
+            assert_eq!(
+                path.map(|(_, cost)| cost),
+                expected,
+                "{:?} from {:?} to {:?}",
+                strategy,
+                start,
+                goal
+            );
+        }
+    }
+}
+
+#[test]
+fn strategies_agree_around_a_restricted_junction() {
+    // The crossroads of tests/turn_restrictions.rs, with the left turn from
+    // way 1 onto way 2 at C forbidden
+    let (w, c, e, n, ne) = (
+        (0.0, 0.0),
+        (0.001, 0.0),
+        (0.002, 0.0),
+        (0.001, 0.001),
+        (0.002, 0.001),
+    );
+    let highways = [
+        highway(1, &[w, c, e]),
+        highway(2, &[c, n]),
+        highway(3, &[e, ne, n]),
+    ];
+    let via = Coord::new(c.0, c.1);
+    let restrictions = HashMap::from([(
+        via,
+        vec![TurnRestriction {
+            from_way: 1,
+            via,
+            to_way: 2,
+            kind: RestrictionKind::No,
+        }],
+    )]);
+
+    let nodes: Vec<Coord> = [w, c, e, n, ne]
+        .iter()
+        .map(|&(lon, lat)| Coord::new(lon, lat))
+        .collect();
+    let pairs: Vec<(Coord, Coord)> = nodes
+        .iter()
+        .flat_map(|&a| nodes.iter().map(move |&b| (a, b)))
+        .collect();
+    assert_strategies_agree(&highways, &restrictions, &pairs);
+}
+
+#[test]
+fn strategies_agree_on_random_pairs() {
+    // A jittered grid of rows and columns with some blocks missing and some
+    // turns from rows onto columns forbidden
+    let mut rng = StdRng::seed_from_u64(7);
+    const SIZE: usize = 8;
+    let points: Vec<Vec<(f64, f64)>> = (0..SIZE)
+        .map(|row| {
+            (0..SIZE)
+                .map(|column| {
+                    (
+                        column as f64 * 0.001 + rng.gen_range(-0.0003..0.0003),
+                        row as f64 * 0.001 + rng.gen_range(-0.0003..0.0003),
+                    )
+                })
+                .collect()
+        })
+        .collect();
+
+    let mut highways = Vec::new();
+    let mut restrictions: HashMap<Coord, Vec<TurnRestriction>> = HashMap::new();
+    for (i, row) in points.iter().enumerate() {
+        for j in 0..SIZE - 1 {
+            if rng.gen_bool(0.85) {
+                highways.push(highway(i as i64, &[row[j], row[j + 1]]));
+            }
+            if rng.gen_bool(0.85) {
+                highways.push(highway(100 + i as i64, &[points[j][i], points[j + 1][i]]));
+            }
+        }
+        for (j, &(lon, lat)) in row.iter().enumerate() {
+            if rng.gen_bool(0.3) {
+                let via = Coord::new(lon, lat);
+                restrictions.entry(via).or_default().push(TurnRestriction {
+                    from_way: i as i64,
+                    via,
+                    to_way: 100 + j as i64,
+                    kind: RestrictionKind::No,
+                });
+            }
+        }
+    }
+
+    let nodes: Vec<Coord> = points
+        .iter()
+        .flatten()
+        .map(|&(lon, lat)| Coord::new(lon, lat))
+        .collect();
+    let pairs: Vec<(Coord, Coord)> = (0..200)
+        .map(|_| {
+            (
+                nodes[rng.gen_range(0..nodes.len())],
+                nodes[rng.gen_range(0..nodes.len())],
+            )
+        })
+        .collect();
+    assert_strategies_agree(&highways, &restrictions, &pairs);
+}
+
+#[test]
+fn landmarks_do_not_match_a_graph_with_changed_costs() {
+    let highways = [highway(1, &[(0.0, 0.0), (0.001, 0.0), (0.002, 0.0)])];
+    let mut graph = build_graph(&highways);
+    let landmarks = Landmarks::select(&graph, &reverse_graph(&graph), 2);
+    assert!(landmarks.matches(&graph));
+
+    // Same nodes, but one edge got cheaper
+    graph.get_mut(&Coord::new(0.001, 0.0)).unwrap()[0].cost /= 2;
+    assert!(!landmarks.matches(&graph));
+}
@@ -0,0 +1,135 @@
+use std::f64::consts::PI;
+
+use maps::drawing::RenderOptions;
+use maps::graph::{build_graph, largest_component};
+use maps::osm::read_osm_objects;
+use maps::osm_xml::OsmXmlReader;
+use maps::server::MapService;
+
+// Two roads meeting at a corner, and a railway along latitude 60 long enough
+// to cross a zoom 3 tile, where Mercator and linear latitudes are far apart.
+const FIXTURE: &str = r#"<osm version="0.6">
+  <node id="1" lat="60.0" lon="10.0"/>
+  <node id="2" lat="60.0" lon="10.02"/>
+  <node id="3" lat="59.99" lon="10.02"/>
+  <node id="4" lat="60.0" lon="1.0"/>
+  <node id="5" lat="60.0" lon="44.0"/>
+  <way id="1">
+    <nd ref="1"/>
+    <nd ref="2"/>
+    <tag k="highway" v="primary"/>
+  </way>
+  <way id="2">
+    <nd ref="2"/>
+    <nd ref="3"/>
+    <tag k="highway" v="primary"/>
+  </way>
+  <way id="3">
+    <nd ref="4"/>
+    <nd ref="5"/>
+    <tag k="railway" v="rail"/>
+  </way>
+</osm>
+"#;
+
+fn service(cache_size: usize) -> MapService {
+    let mut data = read_osm_objects(
+        OsmXmlReader::new(FIXTURE.as_bytes()).map(Result::unwrap),
+        None,
+    );
+    data.graph = build_graph(&data.highways);
+    data.component = largest_component(&data.graph);
+    let options = RenderOptions {
+        labels: false,
+        ..Default::default()
+    };
+    MapService::new(data, options, cache_size)
+}
+
+/// The tile at zoom `z` containing a point, and the pixel row of its latitude
+/// within the tile.
+fn tile_at(z: u32, lon: f64, lat: f64) -> (u32, u32, f64) {
+    let n = (1u32 << z) as f64;
+    let x = (lon + 180.0) / 360.0 * n;
+    let y = (1.0 - (PI / 4.0 + lat.to_radians() / 2.0).tan().ln() / PI) / 2.0 * n;
+    (x as u32, y as u32, y.fract() * 256.0)
+}
+
+#[test]
+fn tiles_are_drawn_in_web_mercator() {
+    let service = service(8);
+    // A linear projection would put the railway 17 pixels higher
+    let (x, y, row) = tile_at(3, 22.5, 60.0);
+    assert_eq!((x, y), (4, 2));
+
+    let reply = service.get(&format!("/tiles/3/{}/{}.png", x, y));
+    assert_eq!((reply.status, reply.content_type), (200, "image/png"));
+    let img = image::load_from_memory(&reply.body).unwrap().to_rgba8();
+    assert_eq!(img.dimensions(), (256, 256));
+
+    // The middle of the line is where Mercator puts its latitude
+    let drawn: Vec<u32> = (0..256).filter(|&y| img.get_pixel(128, y)[3] > 0).collect();
+    let middle = (drawn[0] + drawn[drawn.len() - 1]) as f64 / 2.0;
+    assert!((middle - row).abs() <= 1.5);
+
+    assert_eq!(service.get("/tiles/13/0/99999.png").status, 404);
+    assert_eq!(service.get("/tiles/13/0/x.png").status, 400);
+}
+
+#[test]
+fn tiles_draw_what_crosses_them() {
+    let service = service(8);
+    let tile = |z: u32, lon: f64, lat: f64| {
+        let (x, y, _) = tile_at(z, lon, lat);
+        let reply = service.get(&format!("/tiles/{}/{}/{}.png", z, x, y));
+        image::load_from_memory(&reply.body).unwrap().to_rgba8()
+    };
+    // The railway crosses this tile with both ends outside it
+    assert!(tile(5, 15.0, 60.0).pixels().any(|pixel| pixel[3] > 0));
+    assert!(tile(13, 10.01, 61.0).pixels().all(|pixel| pixel[3] == 0));
+}
+
+#[test]
+fn least_recently_used_tile_is_dropped() {
+    let service = service(2);
+    let get = |x: u32| service.get(&format!("/tiles/2/{}/1.png", x)).body;
+    let first = get(0);
+    get(1);
+    // Using tile 0 again keeps it, so tile 1 makes way for tile 2
+    assert_eq!(get(0), first);
+    get(2);
+    assert!(service.is_cached(2, 0, 1));
+    assert!(!service.is_cached(2, 1, 1));
+    assert!(service.is_cached(2, 2, 1));
+}
+
+#[test]
+fn bbox_keeps_the_ground_aspect_ratio() {
+    let service = service(8);
+    // 0.02 degrees of longitude at latitude 60 are as long as 0.01 of latitude
+    let reply = service.get("/bbox.png?bbox=10.0%2C59.99%2C10.02%2C60.0&width=200");
+    assert_eq!(reply.status, 200);
+    let img = image::load_from_memory(&reply.body).unwrap();
+    assert_eq!((img.width(), img.height()), (200, 200));
+
+    assert_eq!(service.get("/bbox.png?bbox=10,60,9,61").status, 400);
+    assert_eq!(service.get("/bbox.png").status, 400);
+}
+
+#[test]
+fn route_is_returned_as_geojson() {
+    let service = service(8);
+    let reply = service.get("/route?from=10.0,60.0&to=10.02,59.99");
+    assert_eq!(
+        (reply.status, reply.content_type),
+        (200, "application/geo+json")
+    );
+    let route: serde_json::Value = serde_json::from_slice(&reply.body).unwrap();
+    let coordinates = route["geometry"]["coordinates"].as_array().unwrap();
+    assert_eq!(coordinates.len(), 3);
+    assert_eq!(coordinates[1][0], 10.02);
+    assert_eq!(coordinates[1][1], 60.0);
+
+    assert_eq!(service.get("/route?from=10.0,60.0").status, 400);
+    assert_eq!(service.get("/nowhere").status, 404);
+}
@@ -0,0 +1,39 @@
+use maps::simplify::{douglas_peucker, visvalingam};
+
+// A road with a small wobble, then a sharp bend.
+const LINE: [(f64, f64); 5] = [(0.0, 0.0), (1.0, 0.1), (2.0, 0.0), (3.0, 0.0), (3.0, 2.0)];
+
+// A square with a notch in one side, closed.
+const RING: [(f64, f64); 8] = [
+    (0.0, 0.0),
+    (4.0, 0.0),
+    (4.0, 4.0),
+    (2.1, 4.0),
+    (2.0, 3.9),
+    (1.9, 4.0),
+    (0.0, 4.0),
+    (0.0, 0.0),
+];
+
+#[test]
+fn douglas_peucker_keeps_bends() {
+    assert_eq!(
+        douglas_peucker(&LINE, 0.5),
+        vec![(0.0, 0.0), (3.0, 0.0), (3.0, 2.0)]
+    );
+    assert_eq!(douglas_peucker(&LINE, 0.01), LINE.to_vec());
+}
+
+#[test]
+fn visvalingam_drops_small_triangles() {
+    assert_eq!(
+        visvalingam(&RING, 0.5),
+        vec![(0.0, 0.0), (4.0, 0.0), (4.0, 4.0), (0.0, 4.0), (0.0, 0.0)]
+    );
+    assert_eq!(visvalingam(&RING, 0.001), RING.to_vec());
+
+    // However large the tolerance, the ring stays a polygon
+    let simplified = visvalingam(&RING, 100.0);
+    assert_eq!(simplified.len(), 4);
+    assert_eq!(simplified.first(), simplified.last());
+}
@@ -0,0 +1,87 @@
+use std::collections::HashMap;
+
+use maps::graph::{build_graph, find_path};
+use maps::types::coord::Coord;
+use maps::types::highway::Highway;
+use maps::types::restriction::{RestrictionKind, TurnRestriction};
+
+mod common;
+use common::highway;
+
+// A crossroads at C with a block to the north east:
+//
+//   N ---- NE
+//   |      |
+//   W ---- C ---- E
+//
+// Way 1 runs W-C-E, way 2 runs C-N, and way 3 runs E-NE-N.
+const W: (f64, f64) = (0.0, 0.0);
+const C: (f64, f64) = (0.001, 0.0);
+const E: (f64, f64) = (0.002, 0.0);
+const N: (f64, f64) = (0.001, 0.001);
+const NE: (f64, f64) = (0.002, 0.001);
+
+fn coord((lon, lat): (f64, f64)) -> Coord {
+    Coord::new(lon, lat)
+}
+
+fn junction() -> Vec<Highway> {
+    vec![
+        highway(1, &[W, C, E]),
+        highway(2, &[C, N]),
+        highway(3, &[E, NE, N]),
+    ]
+}
+
+fn restriction(
+    from_way: i64,
+    to_way: i64,
+    kind: RestrictionKind,
+) -> HashMap<Coord, Vec<TurnRestriction>> {
+    HashMap::from([(
+        coord(C),
+        vec![TurnRestriction {
+            from_way,
+            via: coord(C),
+            to_way,
+            kind,
+        }],
+    )])
+}
+
+#[test]
+fn unrestricted_route_turns_at_junction() {
+    let graph = build_graph(&junction());
+    let (path, _) = find_path(&graph, &HashMap::new(), coord(W), coord(N)).unwrap();
+    assert_eq!(path, vec![coord(W), coord(C), coord(N)]);
+}
+
+#[test]
+fn no_left_turn_routes_around_the_block() {
+    let graph = build_graph(&junction());
+    let restrictions = restriction(1, 2, RestrictionKind::No);
+    let (path, _) = find_path(&graph, &restrictions, coord(W), coord(N)).unwrap();
+    assert_eq!(
+        path,
+        vec![coord(W), coord(C), coord(E), coord(NE), coord(N)]
+    );
+}
+
+#[test]
+fn only_straight_on_forbids_the_turn() {
+    let graph = build_graph(&junction());
+    let restrictions = restriction(1, 1, RestrictionKind::Only);
+    let (path, _) = find_path(&graph, &restrictions, coord(W), coord(N)).unwrap();
+    assert_eq!(
+        path,
+        vec![coord(W), coord(C), coord(E), coord(NE), coord(N)]
+    );
+}
+
+#[test]
+fn restriction_only_applies_to_its_from_way() {
+    let graph = build_graph(&junction());
+    let restrictions = restriction(1, 2, RestrictionKind::No);
+    let (path, _) = find_path(&graph, &restrictions, coord(N), coord(W)).unwrap();
+    assert_eq!(path, vec![coord(N), coord(C), coord(W)]);
+}
@@ -0,0 +1,172 @@
+use maps::graph::{build_graph, find_path, prune_islands};
+use maps::osm::read_osm_objects;
+use maps::osm_xml::OsmXmlReader;
+use maps::types::cached_data::CachedData;
+use maps::types::coord::Coord;
+use maps::update::apply_changes;
+
+// Two roads meeting at node 2 and a building:
+//
+//   4
+//   |
+//   1 ---- 2 ---- 3
+const FIXTURE: &str = r#"<osm version="0.6">
+  <node id="1" lat="0.0" lon="0.0"/>
+  <node id="2" lat="0.0" lon="0.001"/>
+  <node id="3" lat="0.0" lon="0.002"/>
+  <node id="4" lat="0.001" lon="0.0"/>
+  <node id="10" lat="0.0005" lon="0.0005"/>
+  <node id="11" lat="0.0005" lon="0.0007"/>
+  <node id="12" lat="0.0007" lon="0.0007"/>
+  <way id="1">
+    <nd ref="1"/>
+    <nd ref="2"/>
+    <nd ref="3"/>
+    <tag k="highway" v="residential"/>
+  </way>
+  <way id="2">
+    <nd ref="1"/>
+    <nd ref="4"/>
+    <tag k="highway" v="residential"/>
+  </way>
+  <way id="3">
+    <nd ref="10"/>
+    <nd ref="11"/>
+    <nd ref="12"/>
+    <nd ref="10"/>
+    <tag k="building" v="yes"/>
+  </way>
+</osm>
+"#;
+
+fn fixture() -> CachedData {
+    let mut data = read_osm_objects(
+        OsmXmlReader::new(FIXTURE.as_bytes()).map(Result::unwrap),
+        None,
+    );
+    data.graph = build_graph(&data.highways);
+    data
+}
+
+fn node(data: &CachedData, id: i64) -> Coord {
+    let (lon, lat) = data.nodes[&id];
+    Coord::new(lon, lat)
+}
+
+fn apply(data: &mut CachedData, osc: &str) {
+    let changes = OsmXmlReader::new(osc.as_bytes())
+        .changes()
+        .map(Result::unwrap);
+    apply_changes(data, changes);
+}
+
+#[test]
+fn moved_node_moves_its_roads() {
+    let mut data = fixture();
+    apply(
+        &mut data,
+        r#"<osmChange version="0.6">
+          <modify><node id="2" lat="0.0001" lon="0.001"/></modify>
+        </osmChange>"#,
+    );
+
+    let old = Coord::new(0.001, 0.0);
+    assert!(!data.graph.contains_key(&old));
+    assert_eq!(data.graph[&node(&data, 2)].len(), 2);
+    let edges: Vec<_> = data.graph.values().flatten().collect();
+    assert_eq!(edges.len(), 6);
+    assert!(edges.iter().all(|edge| edge.target != old));
+}
+
+#[test]
+fn created_way_is_routable_and_deleted_way_is_gone() {
+    let mut data = fixture();
+    apply(
+        &mut data,
+        r#"<osmChange version="0.6">
+          <create>
+            <node id="-1" lat="0.001" lon="0.002"/>
+            <way id="-1">
+              <nd ref="4"/>
+              <nd ref="-1"/>
+              <nd ref="3"/>
+              <tag k="highway" v="service"/>
+            </way>
+          </create>
+          <delete>
+            <way id="2"/>
+            <way id="3"/>
+          </delete>
+        </osmChange>"#,
+    );
+
+    assert!(data.highways.iter().all(|highway| highway.id != 2));
+    assert!(data.buildings.is_empty());
+    // 1 to 4 now goes the long way round over the new road
+    let (path, _) = find_path(
+        &data.graph,
+        &data.restrictions,
+        node(&data, 1),
+        node(&data, 4),
+    )
+    .unwrap();
+    assert_eq!(path.len(), 5);
+    assert_eq!(path[3], node(&data, -1));
+}
+
+#[test]
+fn pruned_islands_stay_pruned() {
+    let mut data = fixture();
+    prune_islands(&mut data.graph, 3);
+    data.min_component_size = Some(3);
+    apply(
+        &mut data,
+        r#"<osmChange version="0.6">
+          <create>
+            <node id="-1" lat="0.01" lon="0.01"/>
+            <node id="-2" lat="0.01" lon="0.011"/>
+            <way id="-1">
+              <nd ref="-1"/>
+              <nd ref="-2"/>
+              <tag k="highway" v="track"/>
+            </way>
+          </create>
+        </osmChange>"#,
+    );
+
+    // The new road is drawn but is too small to route on
+    assert!(data.highways.iter().any(|highway| highway.id == -1));
+    assert!(!data.graph.contains_key(&node(&data, -1)));
+    assert_eq!(data.graph.len(), 4);
+    assert_eq!(data.component.len(), 4);
+}
+
+#[test]
+fn nodes_and_buildings_are_replaced_once() {
+    let mut data = fixture();
+    apply(
+        &mut data,
+        r#"<osmChange version="0.6">
+          <create>
+            <node id="20" lat="0.0002" lon="0.0002">
+              <tag k="amenity" v="cafe"/>
+            </node>
+          </create>
+          <modify>
+            <node id="20" lat="0.0003" lon="0.0002">
+              <tag k="amenity" v="bar"/>
+            </node>
+            <node id="11" lat="0.0005" lon="0.0008"/>
+          </modify>
+        </osmChange>"#,
+    );
+
+    // Only the latest version of the node is kept
+    assert_eq!(data.pois.len(), 1);
+    assert!((data.pois[0].lat - 0.0003).abs() < 1e-9);
+    assert_eq!(data.pois[0].kinds[0].1, "bar");
+    // The moved building replaces the old one
+    assert_eq!(data.buildings.len(), 1);
+    let (lon, _) = data.buildings[0][1];
+    assert!((lon - 0.0008).abs() < 1e-9);
+}
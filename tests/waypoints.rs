@@ -0,0 +1,50 @@
+use std::collections::HashMap;
+
+use maps::graph::build_graph;
+use maps::types::coord::Coord;
+use maps::waypoints::{optimise_order, route_via};
+
+mod common;
+use common::highway;
+
+// Six stops along one straight road, visited in a zig-zag order.
+fn stops() -> Vec<Coord> {
+    (0..6).map(|i| Coord::new(i as f64 * 0.001, 0.0)).collect()
+}
+
+fn road() -> HashMap<Coord, Vec<maps::types::edge::Edge>> {
+    let coords: Vec<(f64, f64)> = stops().iter().map(|coord| (coord.lon, coord.lat)).collect();
+    build_graph(&[highway(1, &coords)])
+}
+
+#[test]
+fn optimised_order_is_shorter_than_the_input() {
+    let graph = road();
+    let stops = stops();
+    let zig_zag = vec![stops[0], stops[3], stops[1], stops[4], stops[2], stops[5]];
+
+    let order = optimise_order(&graph, &HashMap::new(), &zig_zag, false);
+    assert_eq!(order, stops);
+
+    let (_, given) = route_via(&graph, &HashMap::new(), &zig_zag).unwrap();
+    let (path, optimised) = route_via(&graph, &HashMap::new(), &order).unwrap();
+    assert!(optimised * 2 < given);
+    // Legs are joined without repeating the waypoints between them
+    assert_eq!(path, stops);
+}
+
+#[test]
+fn round_trip_returns_to_the_start() {
+    let graph = road();
+    let stops = stops();
+    let zig_zag = vec![stops[2], stops[5], stops[0], stops[4], stops[1]];
+
+    let order = optimise_order(&graph, &HashMap::new(), &zig_zag, true);
+    assert_eq!(order.len(), zig_zag.len() + 1);
+    assert_eq!((order[0], order[order.len() - 1]), (stops[2], stops[2]));
+    // Out to one end and back past the start to the other, covering the
+    // road twice
+    let (_, cost) = route_via(&graph, &HashMap::new(), &order).unwrap();
+    let (_, road) = route_via(&graph, &HashMap::new(), &[stops[0], stops[5]]).unwrap();
+    assert!((cost - 2 * road).abs() < 10);
+}